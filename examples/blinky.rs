@@ -3,7 +3,7 @@ use moku::StateMachine;
 
 #[moku::state_machine]
 mod blinky {
-    use std::time::{Duration, Instant};
+    use std::time::Duration;
 
     use moku::*;
 
@@ -43,19 +43,11 @@ mod blinky {
         }
     }
 
-    struct LedOn {
-        entry_time: Instant,
-    }
+    struct LedOn;
 
     impl Substate<Enabled> for LedOn {
-        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
-            Self {
-                entry_time: Instant::now(),
-            }
-        }
-
         fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
-            if self.entry_time.elapsed() >= ctx.top.blink_period {
+            if ctx.time_in_state() >= ctx.top.blink_period {
                 Next::Target(State::LedOff)
             } else {
                 Next::None
@@ -63,19 +55,11 @@ mod blinky {
         }
     }
 
-    struct LedOff {
-        entry_time: Instant,
-    }
+    struct LedOff;
 
     impl Substate<Enabled> for LedOff {
-        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
-            Self {
-                entry_time: Instant::now(),
-            }
-        }
-
         fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
-            if self.entry_time.elapsed() >= ctx.top.blink_period {
+            if ctx.time_in_state() >= ctx.top.blink_period {
                 Next::Target(State::LedOn)
             } else {
                 Next::None
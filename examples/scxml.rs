@@ -0,0 +1,53 @@
+use door::{machine::*, *};
+use moku::*;
+
+#[state_machine]
+mod door {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+
+    // Generates the `Event` enum, the transitions of the chart, and the states that aren't
+    // defined below.
+    include_scxml!("door.scxml");
+
+    pub struct Top;
+    impl TopState for Top {}
+
+    pub struct Locked {
+        pub code: u16,
+    }
+
+    impl Substate<Closed> for Locked {
+        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            log::info!("bolt thrown");
+            Self { code: 1234 }
+        }
+
+        fn exit(self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            log::info!("bolt drawn with code {}", self.code);
+        }
+    }
+}
+
+fn main() {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Info)
+        .init();
+
+    println!("\nDoor state chart:\n\n{}\n", STATE_CHART);
+
+    let mut machine = Builder::new(Top).build();
+
+    for event in [
+        Event::Lock,
+        Event::Open,
+        Event::Unlock,
+        Event::Open,
+        Event::Close,
+    ] {
+        println!();
+        machine.handle_event(&event);
+    }
+}
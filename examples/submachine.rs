@@ -0,0 +1,179 @@
+use moku::*;
+
+/// A reusable handshake that retries a request a limited number of times.
+#[state_machine]
+mod handshake {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Ack,
+        Timeout,
+    }
+
+    pub struct Top {
+        pub retries: u8,
+    }
+
+    impl TopState for Top {
+        // packets to send, executed by whoever drives the handshake
+        type Output = &'static str;
+
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Waiting
+        }
+    }
+
+    struct Waiting;
+
+    impl Substate<Top> for Waiting {
+        fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            ctx.outputs.push("SYN").unwrap();
+            Self
+        }
+
+        #[on(Event::Ack)]
+        fn ack() -> State {
+            State::Done
+        }
+
+        #[on(Event::Timeout)]
+        fn timeout(ctx: &mut Self::Context<'_>) -> Next<State> {
+            if ctx.top.retries == 0 {
+                State::Failed.into()
+            } else {
+                // re-enter to send again
+                ctx.top.retries -= 1;
+                Next::ExactTarget(State::Waiting)
+            }
+        }
+    }
+
+    pub struct Done;
+    impl Substate<Top> for Done {}
+
+    pub struct Failed;
+    impl Substate<Top> for Failed {}
+}
+
+/// A link that performs a handshake both when connecting and when resuming.
+#[state_machine]
+mod link {
+    use moku::*;
+
+    use super::handshake;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Ack,
+        Timeout,
+        Pause,
+    }
+
+    pub struct Top;
+
+    impl TopState for Top {
+        type Output = &'static str;
+
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Connecting
+        }
+    }
+
+    // forward the events that the handshake understands
+    impl TryFrom<&Event> for handshake::Event {
+        type Error = ();
+
+        fn try_from(event: &Event) -> Result<Self, ()> {
+            match event {
+                Event::Ack => Ok(handshake::Event::Ack),
+                Event::Timeout => Ok(handshake::Event::Timeout),
+                Event::Pause => Err(()),
+            }
+        }
+    }
+
+    /// A state that runs a handshake submachine while it is active.
+    pub struct Handshaking {
+        handshake: handshake::machine::Machine,
+    }
+
+    #[submachine(handshake)]
+    impl Substate<Top> for Handshaking {
+        fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            let retries = 2;
+            let mut handshake =
+                handshake::machine::Builder::new(handshake::Top { retries }).build();
+            for packet in handshake.drain_outputs() {
+                ctx.outputs.push(packet).unwrap();
+            }
+            Self { handshake }
+        }
+
+        // called after the handshake updates, to pass on its packets and transition when it
+        // completes
+        fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            for packet in self.handshake.drain_outputs() {
+                ctx.outputs.push(packet).unwrap();
+            }
+            match self.handshake.state() {
+                handshake::machine::State::Done => State::Connected.into(),
+                handshake::machine::State::Failed => State::Offline.into(),
+                _ => Next::None,
+            }
+        }
+
+        // called after the handshake shuts down
+        fn exit(mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            for packet in self.handshake.drain_outputs() {
+                ctx.outputs.push(packet).unwrap();
+            }
+        }
+    }
+
+    struct Connecting;
+    impl Substate<Handshaking> for Connecting {}
+
+    struct Connected;
+
+    impl Substate<Top> for Connected {
+        #[on(Event::Pause)]
+        fn pause() -> State {
+            State::Resuming
+        }
+    }
+
+    struct Resuming;
+    impl Substate<Handshaking> for Resuming {}
+
+    struct Offline;
+    impl Substate<Top> for Offline {}
+}
+
+fn main() {
+    let mut machine = link::machine::Builder::new(link::Top).build();
+
+    machine.handle_event(&link::Event::Timeout);
+    machine.handle_event(&link::Event::Ack);
+    machine.update();
+    assert_eq!(machine.state(), link::machine::State::Connected);
+
+    machine.handle_event(&link::Event::Pause);
+    machine.handle_event(&link::Event::Timeout);
+    machine.handle_event(&link::Event::Timeout);
+    machine.handle_event(&link::Event::Timeout);
+    machine.update();
+    assert_eq!(machine.state(), link::machine::State::Offline);
+
+    for packet in machine.drain_outputs() {
+        println!("sending {packet}");
+    }
+}
@@ -1,12 +1,18 @@
 use example::*;
 use moku::*;
 
-/// Some real hardware interface.
-pub struct Gpio;
+/// A hardware interface.
+pub trait Gpio {
+    fn set_high(&mut self);
+    fn set_low(&mut self);
+}
+
+/// Some real hardware.
+pub struct RealGpio;
 
-impl Gpio {
-    pub fn set_high(&mut self) {}
-    pub fn set_low(&mut self) {}
+impl Gpio for RealGpio {
+    fn set_high(&mut self) {}
+    fn set_low(&mut self) {}
 }
 
 /// A test mock for hardware.
@@ -14,12 +20,12 @@ pub struct TestGpio {
     level: bool,
 }
 
-impl TestGpio {
-    pub fn set_high(&mut self) {
+impl Gpio for TestGpio {
+    fn set_high(&mut self) {
         self.level = true;
     }
 
-    pub fn set_low(&mut self) {
+    fn set_low(&mut self) {
         self.level = false;
     }
 }
@@ -28,39 +34,24 @@ impl TestGpio {
 mod example {
     use moku::*;
 
-    #[allow(unused_imports)]
-    use super::{Gpio, TestGpio};
+    use super::Gpio;
 
     #[machine_module]
     mod machine {}
     pub use machine::*;
 
-    pub struct Top {
-        #[cfg(not(test))]
-        pub gpio: Gpio,
-
-        #[cfg(test)]
-        pub gpio: TestGpio,
-    }
-
-    impl Top {
-        #[allow(clippy::new_without_default)]
-        pub fn new() -> Self {
-            #[cfg(not(test))]
-            let gpio = Gpio;
-
-            #[cfg(test)]
-            let gpio = TestGpio { level: false };
-
-            Self { gpio }
-        }
+    // The generic parameters of the top state are carried through to the generated `Machine`,
+    // `Builder`, and state contexts.
+    pub struct Top<G: Gpio> {
+        pub gpio: G,
     }
 
-    impl TopState for Top {}
+    impl<G: Gpio> TopState for Top<G> {}
 
     struct Foo;
 
-    impl Substate<Top> for Foo {
+    // Substates that name the generic parameters of the top state must declare them.
+    impl<G: Gpio> Substate<Top<G>> for Foo {
         fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
             ctx.top.gpio.set_high();
             Self
@@ -69,7 +60,8 @@ mod example {
 
     struct Bar;
 
-    impl Substate<Top> for Bar {
+    // Substates that don't name them, e.g. those of other superstates, have them added.
+    impl Substate<Foo> for Bar {
         fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
             ctx.top.gpio.set_low();
             Self
@@ -78,8 +70,8 @@ mod example {
 }
 
 fn main() {
-    // Non-test code can use the real hardware interface [Gpio].
-    let _machine = Builder::new(Top::new()).build();
+    // Non-test code can use the real hardware interface [RealGpio].
+    let _machine: Machine<RealGpio> = Builder::new(Top { gpio: RealGpio }).build();
 }
 
 #[cfg(test)]
@@ -93,7 +85,10 @@ mod tests {
     #[test]
     fn test_level() {
         // Test code can use the test interface [TestGpio].
-        let mut machine = Builder::new(Top::new()).build();
+        let mut machine = Builder::new(Top {
+            gpio: TestGpio { level: false },
+        })
+        .build();
 
         machine.transition(State::Foo);
         assert!(machine.top_ref().gpio.level);
@@ -0,0 +1,439 @@
+#![warn(missing_docs)]
+
+//! Generate state charts and diagrams of moku state machines from source files.
+//!
+//! Source files are parsed and each `#[state_machine]` module is validated with the same
+//! unpacker used by the `moku::state_machine` macro, without compiling the crate that defines
+//! them.
+//!
+//! ## Example
+//! ```
+//! let source = r#"
+//!     #[moku::state_machine]
+//!     mod blinky {
+//!         #[moku::machine_module]
+//!         mod machine {}
+//!
+//!         struct Top;
+//!         impl moku::TopState for Top {}
+//!
+//!         struct Disabled;
+//!         impl moku::Substate<Top> for Disabled {}
+//!
+//!         struct Enabled;
+//!         impl moku::Substate<Top> for Enabled {}
+//!     }
+//! "#;
+//!
+//! let charts = moku_cli::charts_from_source(source).unwrap();
+//! assert_eq!(charts[0].name, "Blinky");
+//! assert_eq!(
+//!     charts[0].render(moku_cli::Format::Chart),
+//!     "Top
+//! ├─ Disabled
+//! └─ Enabled"
+//! );
+//! ```
+
+use std::{
+    fmt::{self, Display, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use moku_codegen::{build_metadata, default_name, filter_attributes};
+use syn::{spanned::Spanned, Ident, Item, ItemMod, Meta};
+
+/// An output format for a [`Chart`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// The plain text state chart, identical to the generated `STATE_CHART`.
+    Chart,
+
+    /// A Graphviz DOT graph with a cluster for each superstate, and an edge for each declared
+    /// transition.
+    Dot,
+
+    /// A Mermaid state diagram with a composite state for each superstate, and a transition for
+    /// each declared transition.
+    Mermaid,
+
+    /// A JSON object of the machine name, module path, and state tree, including declared
+    /// transitions.
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chart" => Ok(Format::Chart),
+            "dot" => Ok(Format::Dot),
+            "mermaid" => Ok(Format::Mermaid),
+            "json" => Ok(Format::Json),
+            _ => Err(format!(
+                "unknown format `{s}`, expected one of `chart`, `dot`, `mermaid`, or `json`"
+            )),
+        }
+    }
+}
+
+/// A state in the state tree of a [`Chart`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateNode {
+    /// The name of the state.
+    pub name: String,
+
+    /// The substates of this state.
+    pub children: Vec<StateNode>,
+
+    /// The states that this state may transition to, if declared with `moku::transitions`.
+    pub transitions: Option<Vec<String>>,
+}
+
+impl From<&moku_codegen::State> for StateNode {
+    fn from(state: &moku_codegen::State) -> Self {
+        Self {
+            name: state.ident().to_string(),
+            children: state.children().iter().map(StateNode::from).collect(),
+            transitions: state
+                .transitions()
+                .map(|transitions| transitions.iter().map(Ident::to_string).collect()),
+        }
+    }
+}
+
+impl StateNode {
+    /// Find this state or one of its descendents by name.
+    fn find(&self, name: &str) -> Option<&StateNode> {
+        if self.name == name {
+            return Some(self);
+        }
+
+        self.children.iter().find_map(|child| child.find(name))
+    }
+
+    /// Get the first leaf state of this state, standing in for its cluster in a DOT graph.
+    fn first_leaf(&self) -> &StateNode {
+        match self.children.first() {
+            Some(child) => child.first_leaf(),
+            None => self,
+        }
+    }
+
+    /// Get each declared transition of this state and its descendents.
+    fn edges(&self) -> Vec<(&str, &str)> {
+        let mut edges: Vec<_> = self
+            .transitions
+            .iter()
+            .flatten()
+            .map(|target| (self.name.as_str(), target.as_str()))
+            .collect();
+        for child in &self.children {
+            edges.extend(child.edges());
+        }
+        edges
+    }
+}
+
+/// The structure of a single `state_machine` module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chart {
+    /// The name of the state machine, as returned by `StateMachine::name`.
+    pub name: String,
+
+    /// The path of the `state_machine` module within its file, e.g. `outer::blinky`.
+    pub module: String,
+
+    /// The top state and its substates.
+    pub top: StateNode,
+
+    state_chart: String,
+}
+
+/// An error encountered while reading or unpacking a source file.
+#[derive(Debug)]
+pub enum Error {
+    /// The file could not be read.
+    Io {
+        /// The path of the file.
+        path: PathBuf,
+
+        /// The underlying error.
+        error: std::io::Error,
+    },
+
+    /// The file could not be parsed, or a `state_machine` module is invalid.
+    Source {
+        /// The path of the file, if reading from a file.
+        path: Option<PathBuf>,
+
+        /// The line of the error, starting from 1.
+        line: usize,
+
+        /// The column of the error, starting from 1.
+        column: usize,
+
+        /// The error message, identical to the one emitted by the moku macros.
+        message: String,
+    },
+}
+
+impl Error {
+    fn from_syn(path: Option<&Path>, error: syn::Error) -> Self {
+        let start = error.span().start();
+        Error::Source {
+            path: path.map(Path::to_path_buf),
+            line: start.line,
+            column: start.column + 1,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            Error::Source {
+                path,
+                line,
+                column,
+                message,
+            } => {
+                let path = match path {
+                    Some(path) => path.display().to_string(),
+                    None => String::from("<source>"),
+                };
+                write!(f, "{path}:{line}:{column}: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { error, .. } => Some(error),
+            Error::Source { .. } => None,
+        }
+    }
+}
+
+/// Find and unpack every `state_machine` module in a source file.
+pub fn charts_from_file(path: impl AsRef<Path>) -> Result<Vec<Chart>, Error> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path).map_err(|error| Error::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    charts(&source, Some(path))
+}
+
+/// Find and unpack every `state_machine` module in Rust source code.
+pub fn charts_from_source(source: &str) -> Result<Vec<Chart>, Error> {
+    charts(source, None)
+}
+
+fn charts(source: &str, path: Option<&Path>) -> Result<Vec<Chart>, Error> {
+    let file = syn::parse_file(source).map_err(|error| Error::from_syn(path, error))?;
+    let mut charts = Vec::new();
+    find_charts(file.items, &mut Vec::new(), &mut charts)
+        .map_err(|error| Error::from_syn(path, error))?;
+    Ok(charts)
+}
+
+/// Recursively search a list of items for `state_machine` modules.
+fn find_charts(
+    items: Vec<Item>,
+    path: &mut Vec<String>,
+    charts: &mut Vec<Chart>,
+) -> Result<(), syn::Error> {
+    for item in items {
+        let Item::Mod(mut module) = item else {
+            continue;
+        };
+
+        path.push(module.ident.to_string());
+
+        if let Some(name) = state_machine_name(&module)? {
+            let metadata = build_metadata(name, module)?;
+            charts.push(Chart {
+                name: metadata.name.to_string(),
+                module: path.join("::"),
+                top: StateNode::from(&metadata.top_state),
+                state_chart: metadata.top_state.state_chart(),
+            });
+        } else if let Some((_, content)) = module.content.take() {
+            find_charts(content, path, charts)?;
+        }
+
+        path.pop();
+    }
+
+    Ok(())
+}
+
+/// Get the state machine name of a module if it is attributed with `state_machine`.
+fn state_machine_name(module: &ItemMod) -> Result<Option<Ident>, syn::Error> {
+    let attr = match filter_attributes(&module.attrs, "state_machine").first() {
+        Some(attr) => *attr,
+        None => return Ok(None),
+    };
+
+    match &attr.meta {
+        Meta::Path(_) => Ok(Some(default_name(module))),
+        Meta::List(list) => list.parse_args().map(Some),
+        Meta::NameValue(_) => Err(syn::Error::new(
+            attr.span(),
+            "expected `#[state_machine]` or `#[state_machine(Name)]`",
+        )),
+    }
+}
+
+impl Chart {
+    /// Render this chart in the given format.
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Chart => self.state_chart.clone(),
+            Format::Dot => self.dot(),
+            Format::Mermaid => self.mermaid(),
+            Format::Json => self.json(),
+        }
+    }
+
+    fn dot(&self) -> String {
+        let mut out = format!(
+            "digraph {} {{\n    node [shape=box, style=rounded];\n",
+            self.name
+        );
+        dot_state(&self.top, 1, &mut out);
+
+        let edges = self.top.edges();
+        if !edges.is_empty() {
+            // edges between clusters are drawn between their first leaves
+            out.insert_str(out.find('\n').unwrap() + 1, "    compound=true;\n");
+        }
+        for (source, target) in edges {
+            let mut attrs = Vec::new();
+            let mut anchor = |name: &str, attr: &str| match self.top.find(name) {
+                Some(state) if !state.children.is_empty() => {
+                    attrs.push(format!("{attr}=cluster_{name}"));
+                    state.first_leaf().name.clone()
+                }
+                _ => name.to_string(),
+            };
+            let source = anchor(source, "ltail");
+            let target = anchor(target, "lhead");
+
+            if attrs.is_empty() {
+                writeln!(out, "    {source} -> {target};").unwrap();
+            } else {
+                writeln!(out, "    {source} -> {target} [{}];", attrs.join(", ")).unwrap();
+            }
+        }
+
+        out.push('}');
+        out
+    }
+
+    fn mermaid(&self) -> String {
+        let mut out = String::from("stateDiagram-v2\n");
+        mermaid_state(&self.top, 1, &mut out);
+        for (source, target) in self.top.edges() {
+            writeln!(out, "    {source} --> {target}").unwrap();
+        }
+        out.pop();
+        out
+    }
+
+    fn json(&self) -> String {
+        let mut out = String::new();
+        write!(
+            out,
+            "{{\"name\":{},\"module\":{},\"top\":",
+            json_string(&self.name),
+            json_string(&self.module)
+        )
+        .unwrap();
+        json_state(&self.top, &mut out);
+        out.push('}');
+        out
+    }
+}
+
+/// Render a list of charts in the given format.
+///
+/// Charts are separated by an empty line, or collected into an array for [`Format::Json`].
+pub fn render_all(charts: &[Chart], format: Format) -> String {
+    let rendered = charts.iter().map(|chart| chart.render(format));
+
+    match format {
+        Format::Json => format!("[{}]", rendered.collect::<Vec<_>>().join(",")),
+        _ => rendered.collect::<Vec<_>>().join("\n\n"),
+    }
+}
+
+fn dot_state(state: &StateNode, depth: usize, out: &mut String) {
+    let pad = "    ".repeat(depth);
+    if state.children.is_empty() {
+        writeln!(out, "{pad}{};", state.name).unwrap();
+    } else {
+        writeln!(out, "{pad}subgraph cluster_{} {{", state.name).unwrap();
+        writeln!(out, "{pad}    label=\"{}\";", state.name).unwrap();
+        for child in &state.children {
+            dot_state(child, depth + 1, out);
+        }
+        writeln!(out, "{pad}}}").unwrap();
+    }
+}
+
+fn mermaid_state(state: &StateNode, depth: usize, out: &mut String) {
+    let pad = "    ".repeat(depth);
+    if state.children.is_empty() {
+        writeln!(out, "{pad}{}", state.name).unwrap();
+    } else {
+        writeln!(out, "{pad}state {} {{", state.name).unwrap();
+        for child in &state.children {
+            mermaid_state(child, depth + 1, out);
+        }
+        writeln!(out, "{pad}}}").unwrap();
+    }
+}
+
+fn json_state(state: &StateNode, out: &mut String) {
+    write!(
+        out,
+        "{{\"name\":{},\"children\":[",
+        json_string(&state.name)
+    )
+    .unwrap();
+    for (i, child) in state.children.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        json_state(child, out);
+    }
+    out.push(']');
+
+    if let Some(transitions) = &state.transitions {
+        let transitions: Vec<_> = transitions.iter().map(|name| json_string(name)).collect();
+        write!(out, ",\"transitions\":[{}]", transitions.join(",")).unwrap();
+    }
+    out.push('}');
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
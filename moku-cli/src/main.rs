@@ -0,0 +1,89 @@
+use std::process::ExitCode;
+
+use moku_cli::{charts_from_file, render_all, Format};
+
+const USAGE: &str = "\
+Print the state charts of moku state machines found in Rust source files.
+
+Usage: moku [OPTIONS] <FILE>...
+
+Options:
+  -f, --format <FORMAT>  Output format: chart, dot, mermaid, or json [default: chart]
+  -m, --machine <NAME>   Only print the machine with this name or module path
+  -h, --help             Print help
+  -V, --version          Print version";
+
+struct Args {
+    format: Format,
+    machine: Option<String>,
+    files: Vec<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut parsed = Args {
+        format: Format::Chart,
+        machine: None,
+        files: Vec::new(),
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(None);
+            }
+            "-V" | "--version" => {
+                println!("moku {}", env!("CARGO_PKG_VERSION"));
+                return Ok(None);
+            }
+            "-f" | "--format" => {
+                let format = args.next().ok_or("missing value for `--format`")?;
+                parsed.format = format.parse()?;
+            }
+            "-m" | "--machine" => {
+                parsed.machine = Some(args.next().ok_or("missing value for `--machine`")?);
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+            _ => parsed.files.push(arg),
+        }
+    }
+
+    if parsed.files.is_empty() {
+        return Err(String::from("no input files"));
+    }
+
+    Ok(Some(parsed))
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => return ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut charts = Vec::new();
+    for file in &args.files {
+        match charts_from_file(file) {
+            Ok(found) => charts.extend(found),
+            Err(error) => {
+                eprintln!("error: {error}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if let Some(machine) = &args.machine {
+        charts.retain(|chart| chart.name == *machine || chart.module == *machine);
+        if charts.is_empty() {
+            eprintln!("error: no state machine named `{machine}` was found");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    println!("{}", render_all(&charts, args.format));
+    ExitCode::SUCCESS
+}
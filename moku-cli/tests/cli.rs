@@ -0,0 +1,192 @@
+use std::process::Command;
+
+use moku_cli::{charts_from_file, charts_from_source, render_all, Error, Format, StateNode};
+
+const MACHINES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/machines.rs");
+const INVALID: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/invalid.rs");
+
+fn leaf(name: &str) -> StateNode {
+    StateNode {
+        name: name.to_string(),
+        children: Vec::new(),
+        transitions: None,
+    }
+}
+
+fn leaf_with_transitions(name: &str, transitions: &[&str]) -> StateNode {
+    StateNode {
+        transitions: Some(transitions.iter().map(|name| name.to_string()).collect()),
+        ..leaf(name)
+    }
+}
+
+#[test]
+fn find_charts() {
+    let charts = charts_from_file(MACHINES).unwrap();
+    assert_eq!(charts.len(), 2);
+
+    assert_eq!(charts[0].name, "Blinky");
+    assert_eq!(charts[0].module, "outer::blinky");
+    assert_eq!(
+        charts[0].top,
+        StateNode {
+            name: String::from("Top"),
+            children: vec![
+                leaf_with_transitions("Disabled", &["Enabled"]),
+                StateNode {
+                    name: String::from("Enabled"),
+                    children: vec![
+                        leaf_with_transitions("LedOn", &["LedOff", "Disabled"]),
+                        leaf("LedOff"),
+                    ],
+                    transitions: None,
+                },
+            ],
+            transitions: None,
+        }
+    );
+
+    assert_eq!(charts[1].name, "Kikai");
+    assert_eq!(charts[1].module, "named");
+    assert_eq!(charts[1].top, leaf("Top"));
+}
+
+#[test]
+fn chart() {
+    let charts = charts_from_file(MACHINES).unwrap();
+    assert_eq!(
+        charts[0].render(Format::Chart),
+        "Top
+├─ Disabled
+└─ Enabled
+   ├─ LedOn
+   └─ LedOff"
+    );
+}
+
+#[test]
+fn dot() {
+    let charts = charts_from_file(MACHINES).unwrap();
+    assert_eq!(
+        charts[0].render(Format::Dot),
+        r#"digraph Blinky {
+    compound=true;
+    node [shape=box, style=rounded];
+    subgraph cluster_Top {
+        label="Top";
+        Disabled;
+        subgraph cluster_Enabled {
+            label="Enabled";
+            LedOn;
+            LedOff;
+        }
+    }
+    Disabled -> LedOn [lhead=cluster_Enabled];
+    LedOn -> LedOff;
+    LedOn -> Disabled;
+}"#
+    );
+}
+
+#[test]
+fn mermaid() {
+    let charts = charts_from_file(MACHINES).unwrap();
+    assert_eq!(
+        charts[0].render(Format::Mermaid),
+        "stateDiagram-v2
+    state Top {
+        Disabled
+        state Enabled {
+            LedOn
+            LedOff
+        }
+    }
+    Disabled --> Enabled
+    LedOn --> LedOff
+    LedOn --> Disabled"
+    );
+}
+
+#[test]
+fn json() {
+    let charts = charts_from_file(MACHINES).unwrap();
+    assert_eq!(
+        render_all(&charts, Format::Json),
+        concat!(
+            r#"[{"name":"Blinky","module":"outer::blinky","top":{"name":"Top","children":["#,
+            r#"{"name":"Disabled","children":[],"transitions":["Enabled"]},"#,
+            r#"{"name":"Enabled","children":[{"name":"LedOn","children":[],"#,
+            r#""transitions":["LedOff","Disabled"]},{"name":"LedOff","children":[]}]}]}},"#,
+            r#"{"name":"Kikai","module":"named","top":{"name":"Top","children":[]}}]"#,
+        )
+    );
+}
+
+#[test]
+fn invalid_machine() {
+    match charts_from_file(INVALID).unwrap_err() {
+        Error::Source {
+            line,
+            column,
+            message,
+            ..
+        } => {
+            assert_eq!((line, column), (10, 24));
+            assert!(message.contains("superstate `Tob`"));
+        }
+        error => panic!("unexpected error: {error}"),
+    }
+}
+
+#[test]
+fn invalid_syntax() {
+    let error = charts_from_source("mod foo {").unwrap_err();
+    assert!(matches!(error, Error::Source { path: None, .. }));
+}
+
+#[test]
+fn missing_file() {
+    let error = charts_from_file("does/not/exist.rs").unwrap_err();
+    assert!(matches!(error, Error::Io { .. }));
+}
+
+fn moku(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_moku"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn cli() {
+    let output = moku(&["--format", "mermaid", "--machine", "Kikai", MACHINES]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "stateDiagram-v2\n    Top\n"
+    );
+
+    let output = moku(&["-m", "outer::blinky", MACHINES]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .starts_with("Top\n├─ Disabled"));
+}
+
+#[test]
+fn cli_errors() {
+    let output = moku(&[INVALID]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("invalid.rs:10:24: superstate `Tob`"));
+
+    let output = moku(&["-m", "Missing", MACHINES]);
+    assert!(!output.status.success());
+
+    let output = moku(&["-f", "svg", MACHINES]);
+    assert!(!output.status.success());
+
+    let output = moku(&[]);
+    assert!(!output.status.success());
+}
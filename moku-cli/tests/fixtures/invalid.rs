@@ -0,0 +1,11 @@
+#[moku::state_machine]
+mod invalid {
+    #[moku::machine_module]
+    mod machine {}
+
+    struct Top;
+    impl moku::TopState for Top {}
+
+    struct Foo;
+    impl moku::Substate<Tob> for Foo {}
+}
@@ -0,0 +1,37 @@
+mod outer {
+    #[moku::state_machine]
+    mod blinky {
+        #[moku::machine_module]
+        mod machine {}
+
+        use machine::BlinkyState;
+
+        struct Top;
+        impl moku::TopState for Top {}
+
+        struct Disabled;
+
+        #[moku::transitions(Enabled)]
+        impl moku::Substate<Top> for Disabled {}
+
+        struct Enabled;
+        impl moku::Substate<Top> for Enabled {}
+
+        struct LedOn;
+
+        #[moku::transitions(LedOff, Disabled)]
+        impl moku::Substate<Enabled> for LedOn {}
+
+        struct LedOff;
+        impl moku::Substate<Enabled> for LedOff {}
+    }
+}
+
+#[moku::state_machine(Kikai)]
+mod named {
+    #[moku::machine_module]
+    mod machine {}
+
+    struct Top;
+    impl moku::TopState for Top {}
+}
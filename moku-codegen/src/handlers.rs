@@ -0,0 +1,376 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    parse_quote, spanned::Spanned, visit_mut::VisitMut, ExprPath, FnArg, GenericParam, Ident,
+    ImplItem, ImplItemFn, ItemImpl, Pat, Path, ReturnType, Type, TypePath,
+};
+
+use crate::util::{find_mention, param_name, path_matches, prepend_params};
+
+/// Functions of the `Substate` and `TopState` traits, which handlers must not be named after.
+const HOOKS: [&str; 7] = [
+    "enter",
+    "init",
+    "update",
+    "top_down_update",
+    "exit",
+    "handle_event",
+    "on_unhandled",
+];
+
+/// What to do with events that don't match any `moku::on` handler.
+enum Unmatched {
+    Defer,
+    Drop,
+}
+
+/// A method attributed with `moku::on`.
+struct Handler {
+    patterns: Vec<Pat>,
+    args: Vec<TokenStream>,
+    fun: ImplItemFn,
+}
+
+/// Move the `moku::on` handler methods of a `Substate` or `TopState` impl into an inherent impl
+/// and generate a `handle_event` function that dispatches events to them.
+///
+/// Returns the inherent impl if any handlers were found.
+pub fn expand_handlers(
+    imp: &mut ItemImpl,
+    is_top_state: bool,
+) -> Result<Option<ItemImpl>, syn::Error> {
+    let unmatched = take_unmatched(imp, is_top_state)?;
+
+    let mut handlers = Vec::new();
+    let mut handle_event = None;
+    let mut items = Vec::new();
+
+    for item in imp.items.drain(..) {
+        match item {
+            ImplItem::Fn(mut fun) => {
+                let mut patterns = Vec::new();
+                let mut error = None;
+                fun.attrs.retain(|attr| {
+                    if !path_matches(attr.path(), "on") {
+                        return true;
+                    }
+
+                    match attr.parse_args_with(Pat::parse_multi_with_leading_vert) {
+                        Ok(pattern) => patterns.push(pattern),
+                        Err(e) => {
+                            error.get_or_insert(e);
+                        }
+                    }
+                    false
+                });
+
+                if let Some(error) = error {
+                    return Err(error);
+                }
+
+                if patterns.is_empty() {
+                    if fun.sig.ident == "handle_event" {
+                        handle_event = Some(fun.sig.ident.span());
+                    }
+                    items.push(ImplItem::Fn(fun));
+                } else {
+                    handlers.push(Handler {
+                        args: handler_args(&fun)?,
+                        patterns,
+                        fun,
+                    });
+                }
+            }
+            item => items.push(item),
+        }
+    }
+
+    imp.items = items;
+
+    if handlers.is_empty() && unmatched.is_none() {
+        return Ok(None);
+    }
+
+    if let Some(span) = handle_event {
+        return Err(syn::Error::new(
+            span,
+            "`handle_event` must not be defined alongside `moku::on` handlers or `moku::unmatched`",
+        ));
+    }
+
+    // generic parameters of the machine that the state type doesn't use can't be declared by an
+    // inherent impl, so they are declared by each handler instead
+    let (impl_params, fn_params): (Vec<_>, Vec<_>) =
+        imp.generics.params.iter().partition(|param| {
+            find_mention(imp.self_ty.to_token_stream(), &param_name(param)).is_some()
+        });
+
+    // lifetimes are left to be inferred
+    let fn_args: Vec<_> = fn_params
+        .iter()
+        .filter(|param| !matches!(param, GenericParam::Lifetime(_)))
+        .map(|param| param_ident(param))
+        .collect();
+
+    let arms = handlers.iter().flat_map(|handler| {
+        let ident = &handler.fun.sig.ident;
+        let args = &handler.args;
+        let turbofish = if fn_args.is_empty() {
+            TokenStream::new()
+        } else {
+            let inferred = handler.fun.sig.generics.type_params().map(|_| quote! { _ });
+            quote! { ::<#(#fn_args,)* #(#inferred),*> }
+        };
+        // handlers of substates that return nothing handle the event without a transition
+        let returns_unit = match &handler.fun.sig.output {
+            ReturnType::Default => true,
+            ReturnType::Type(_, ty) => {
+                matches!(ty.as_ref(), Type::Tuple(tuple) if tuple.elems.is_empty())
+            }
+        };
+        let call = if returns_unit && !is_top_state {
+            quote! {{
+                Self::#ident #turbofish(#(#args),*);
+                ::moku::Response::Handled
+            }}
+        } else {
+            quote! { Self::#ident #turbofish(#(#args),*).into() }
+        };
+        handler
+            .patterns
+            .iter()
+            .map(move |pattern| quote! { #pattern => #call })
+    });
+
+    if is_top_state {
+        imp.items.push(parse_quote! {
+            #[allow(unused_variables)]
+            fn handle_event(
+                &mut self,
+                event: &Self::Event,
+            ) -> impl ::core::convert::Into<::moku::Next<Self::State>> {
+                #[allow(unreachable_patterns)]
+                let next: ::moku::Next<Self::State> = match event {
+                    #(#arms,)*
+                    _ => ::moku::Next::None,
+                };
+                next
+            }
+        });
+    } else {
+        let unmatched = match unmatched {
+            Some(Unmatched::Drop) => quote! { ::moku::Response::Drop },
+            _ => quote! { ::moku::Response::Next(::moku::Next::None) },
+        };
+
+        imp.items.push(parse_quote! {
+            #[allow(unused_variables)]
+            fn handle_event(
+                &mut self,
+                ctx: &mut Self::Context<'_>,
+                event: &Self::Event,
+            ) -> impl ::core::convert::Into<::moku::Response<Self::State>> {
+                #[allow(unreachable_patterns)]
+                let response: ::moku::Response<Self::State> = match event {
+                    #(#arms,)*
+                    _ => #unmatched,
+                };
+                response
+            }
+        });
+    }
+
+    if handlers.is_empty() {
+        return Ok(None);
+    }
+
+    // associated types of the state trait are ambiguous in an inherent impl
+    let mut qualifier = QualifySelf {
+        trait_path: imp.trait_.as_ref().unwrap().1.to_token_stream(),
+    };
+    let predicates: Vec<_> = imp
+        .generics
+        .where_clause
+        .iter()
+        .flat_map(|where_clause| &where_clause.predicates)
+        .collect();
+    let mut funs = Vec::new();
+    for mut handler in handlers {
+        qualifier.visit_impl_item_fn_mut(&mut handler.fun);
+
+        if !fn_params.is_empty() {
+            let generics = &mut handler.fun.sig.generics;
+            let params = fn_params
+                .iter()
+                .map(|param| syn::parse2(param.to_token_stream()))
+                .collect::<Result<_, _>>()?;
+            prepend_params(generics, params);
+            handler.fun.attrs.push(parse_quote! {
+                #[allow(clippy::extra_unused_type_parameters)]
+            });
+            for predicate in &predicates {
+                generics
+                    .make_where_clause()
+                    .predicates
+                    .push(syn::parse2(predicate.to_token_stream())?);
+            }
+        }
+
+        funs.push(handler.fun);
+    }
+
+    let self_ty = &imp.self_ty;
+    let where_clause = if fn_params.is_empty() {
+        imp.generics.where_clause.to_token_stream()
+    } else {
+        TokenStream::new()
+    };
+
+    // the handlers are compiled out along with the state
+    let cfgs = imp.attrs.iter().filter(|attr| attr.path().is_ident("cfg"));
+
+    Ok(Some(parse_quote! {
+        #(#cfgs)*
+        impl<#(#impl_params),*> #self_ty #where_clause {
+            #(#funs)*
+        }
+    }))
+}
+
+/// Take the `moku::unmatched` attribute of a state impl, if any.
+fn take_unmatched(imp: &mut ItemImpl, is_top_state: bool) -> Result<Option<Unmatched>, syn::Error> {
+    let mut unmatched = None;
+
+    for attr in &imp.attrs {
+        if !path_matches(attr.path(), "unmatched") {
+            continue;
+        }
+
+        if unmatched.is_some() {
+            return Err(syn::Error::new(
+                attr.span(),
+                "multiple `moku::unmatched` attributes defined for this impl",
+            ));
+        }
+
+        let ident: Ident = attr.parse_args()?;
+        unmatched = Some(if ident == "defer" {
+            Unmatched::Defer
+        } else if ident == "drop" {
+            if is_top_state {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "the `moku::TopState` can't drop events, it is the last state to handle them",
+                ));
+            }
+            Unmatched::Drop
+        } else {
+            return Err(syn::Error::new(
+                ident.span(),
+                "expected `defer` or `drop`, e.g. `#[moku::unmatched(drop)]`",
+            ));
+        });
+    }
+
+    imp.attrs
+        .retain(|attr| !path_matches(attr.path(), "unmatched"));
+
+    Ok(unmatched)
+}
+
+/// Get the Ident of a generic parameter, e.g. `a` for `'a`.
+fn param_ident(param: &GenericParam) -> &Ident {
+    match param {
+        GenericParam::Type(param) => &param.ident,
+        GenericParam::Const(param) => &param.ident,
+        GenericParam::Lifetime(param) => &param.lifetime.ident,
+    }
+}
+
+/// Get the arguments to call a handler with.
+///
+/// Each parameter is passed by name, ignoring leading underscores, so that it can be bound to
+/// `ctx`, `event`, or any binding of the handler's patterns.
+fn handler_args(fun: &ImplItemFn) -> Result<Vec<TokenStream>, syn::Error> {
+    let ident = &fun.sig.ident;
+    if HOOKS.iter().any(|hook| ident == hook) {
+        return Err(syn::Error::new(
+            ident.span(),
+            format!("`moku::on` handlers must not be named `{ident}`"),
+        ));
+    }
+
+    fun.sig
+        .inputs
+        .iter()
+        .map(|input| match input {
+            FnArg::Receiver(_) => Ok(quote! { self }),
+            FnArg::Typed(arg) => {
+                let name = match arg.pat.as_ref() {
+                    Pat::Ident(pat) => {
+                        let name = pat.ident.to_string();
+                        let name = name.trim_start_matches('_');
+                        (!name.is_empty()).then(|| Ident::new(name, pat.ident.span()))
+                    }
+                    _ => None,
+                };
+
+                match name {
+                    Some(name) => Ok(name.into_token_stream()),
+                    None => Err(syn::Error::new(
+                        arg.pat.span(),
+                        "`moku::on` handler parameters must be named `ctx`, `event`, or after a \
+                        binding of the event pattern",
+                    )),
+                }
+            }
+        })
+        .collect()
+}
+
+/// Qualify `Self::Context`, `Self::State`, `Self::Event`, and `Self::Input` with a state trait, e.g.
+/// `<Self as Substate<Top>>::State`.
+struct QualifySelf {
+    trait_path: TokenStream,
+}
+
+impl QualifySelf {
+    fn qualify(&self, path: &Path) -> Option<TypePath> {
+        let mut segments = path.segments.iter();
+        let first = segments.next()?;
+        let second = segments.next()?;
+
+        let associated = ["Context", "State", "Event", "Input"];
+        if path.leading_colon.is_some()
+            || first.ident != "Self"
+            || !associated.iter().any(|ty| second.ident == ty)
+        {
+            return None;
+        }
+
+        let trait_path = &self.trait_path;
+        let rest = path.segments.iter().skip(1);
+        Some(parse_quote! { <Self as #trait_path>::#(#rest)::* })
+    }
+}
+
+impl VisitMut for QualifySelf {
+    fn visit_type_path_mut(&mut self, ty: &mut TypePath) {
+        if ty.qself.is_none() {
+            if let Some(qualified) = self.qualify(&ty.path) {
+                *ty = qualified;
+            }
+        }
+        syn::visit_mut::visit_type_path_mut(self, ty);
+    }
+
+    fn visit_expr_path_mut(&mut self, expr: &mut ExprPath) {
+        if expr.qself.is_none() {
+            if let Some(qualified) = self.qualify(&expr.path) {
+                expr.qself = qualified.qself;
+                expr.path = qualified.path;
+            }
+        }
+        syn::visit_mut::visit_expr_path_mut(self, expr);
+    }
+}
@@ -0,0 +1,16 @@
+#![allow(unused)]
+
+//! State machine code generation for moku.
+//!
+//! Collects and validates the structure of a `state_machine` module and writes the generated
+//! state machine. Shared by the `moku-macros` procedural macros and the `moku-cli` tooling.
+
+mod handlers;
+mod metadata;
+mod submachine;
+mod unpacker;
+mod util;
+
+pub use metadata::{MachineGenerics, Metadata, State};
+pub use unpacker::build_metadata;
+pub use util::{default_name, filter_attributes, path_matches};
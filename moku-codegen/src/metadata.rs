@@ -0,0 +1,2365 @@
+use std::collections::HashMap;
+
+use convert_case::{Case, Casing};
+use proc_macro2::{Literal, Span, TokenStream};
+use quote::{format_ident, quote, ToTokens};
+use syn::{parse_quote, Ident, ImplItem, Item, ItemImpl, ItemMod};
+
+use crate::util::{push_unique, scope, submodule_items};
+
+pub struct State {
+    ident: Ident,
+    /// The type of this state, e.g. `Foo` or `Foo<H>`, named from the `state_machine` module.
+    ty: TokenStream,
+    /// The submodules of the `state_machine` module that contain this state.
+    path: Vec<Ident>,
+    children: Vec<State>,
+    autogen_enter: bool,
+    imp: Option<ItemImpl>,
+    /// A narrower event type declared for this state and its descendents.
+    event: Option<TokenStream>,
+    /// The predicates of the `cfg` attributes of this state and its ancestors.
+    cfgs: Vec<TokenStream>,
+    /// The `moku::describe` attribute or doc comment of this state.
+    description: Option<String>,
+    /// The `moku::tag`s of this state.
+    tags: Vec<Ident>,
+    /// The states that this state may transition to, if declared with `moku::transitions`.
+    transitions: Option<Vec<Ident>>,
+}
+
+impl From<&Ident> for State {
+    fn from(ident: &Ident) -> Self {
+        Self {
+            ident: ident.clone(),
+            ty: ident.to_token_stream(),
+            path: Vec::new(),
+            children: Vec::new(),
+            autogen_enter: false,
+            imp: None,
+            event: None,
+            cfgs: Vec::new(),
+            description: None,
+            tags: Vec::new(),
+            transitions: None,
+        }
+    }
+}
+
+impl State {
+    /// Get the Ident of this State.
+    pub fn ident(&self) -> &Ident {
+        &self.ident
+    }
+
+    /// Set the type of this State, if it has generic arguments.
+    pub fn set_ty(&mut self, ty: TokenStream) {
+        self.ty = ty;
+    }
+
+    /// Set the predicates of the `cfg` attributes of this State.
+    pub fn set_cfgs(&mut self, cfgs: Vec<TokenStream>) {
+        self.cfgs = cfgs;
+    }
+
+    /// Set the description of this State.
+    pub fn set_description(&mut self, description: Option<String>) {
+        self.description = description;
+    }
+
+    /// Set the tags of this State.
+    pub fn set_tags(&mut self, tags: Vec<Ident>) {
+        self.tags = tags;
+    }
+
+    /// Get the states that this State may transition to, if declared with `moku::transitions`.
+    pub fn transitions(&self) -> Option<&[Ident]> {
+        self.transitions.as_deref()
+    }
+
+    /// Set the states that this State may transition to.
+    pub fn set_transitions(&mut self, transitions: Option<Vec<Ident>>) {
+        self.transitions = transitions;
+    }
+
+    /// Get the direct substates of this State.
+    pub fn children(&self) -> &[State] {
+        &self.children
+    }
+
+    /// Get the `cfg` attributes of this State, to be put on each generated item that mentions it.
+    fn cfg_attrs(&self) -> TokenStream {
+        let cfgs = &self.cfgs;
+        quote! { #(#[cfg(#cfgs)])* }
+    }
+
+    /// Does this State have `cfg` attributes that its parent doesn't?
+    fn has_own_cfgs(&self, parent: &State) -> bool {
+        self.cfgs.len() > parent.cfgs.len()
+    }
+
+    /// Add the `cfg` predicates of the ancestors of this State and its descendents.
+    fn inherit_cfgs(&mut self, inherited: &[TokenStream]) {
+        let mut cfgs = inherited.to_vec();
+        for cfg in self.cfgs.drain(..) {
+            if !cfgs
+                .iter()
+                .any(|other| other.to_string() == cfg.to_string())
+            {
+                cfgs.push(cfg);
+            }
+        }
+        self.cfgs = cfgs;
+
+        for child in &mut self.children {
+            child.inherit_cfgs(&self.cfgs);
+        }
+    }
+
+    /// Get the Ident for this State's Node.
+    fn node_ident(&self) -> Ident {
+        format_ident!("{}Node", self.ident)
+    }
+
+    /// Get the Ident for this State's SubstateEnum.
+    fn substate_enum_ident(&self) -> Ident {
+        format_ident!("{}Substate", self.ident)
+    }
+
+    /// Get the Ident for this State's Context.
+    fn context_ident(&self) -> Ident {
+        format_ident!("{}Context", self.ident)
+    }
+
+    /// Get the Ident for the alias of this State's type, which names a placeholder if the State is
+    /// compiled out.
+    fn type_alias_ident(&self) -> Ident {
+        format_ident!("{}Type", self.ident)
+    }
+
+    /// Get the Ident for the narrowed target type of this State's declared transitions.
+    fn target_ident(&self) -> Ident {
+        format_ident!("{}Target", self.ident)
+    }
+
+    /// Get the type of this State's Node, given the generic arguments of the machine.
+    fn node_ty(&self, args: &[TokenStream]) -> TokenStream {
+        let node = self.node_ident();
+        quote! { #node<#(#args),*> }
+    }
+
+    /// Get the type of this State's SubstateEnum, given the generic arguments of the machine.
+    ///
+    /// The SubstateEnums of leaf states have no variants that could use generic parameters.
+    fn substate_enum_ty(&self, args: &[TokenStream]) -> TokenStream {
+        let substate = self.substate_enum_ident();
+        if self.children.is_empty() {
+            substate.into_token_stream()
+        } else {
+            quote! { #substate<#(#args),*> }
+        }
+    }
+
+    /// Get the type of this State's Context, given the generic arguments of the machine.
+    fn context_ty(&self, lifetime: TokenStream, args: &[TokenStream]) -> TokenStream {
+        let context = self.context_ident();
+        quote! { #context<#lifetime, #(#args),*> }
+    }
+
+    /// Add a child to this State or one of its descendents. Returns the child if no parent is
+    /// found.
+    fn add_child(&mut self, mut child: State, parent: &Ident) -> Option<State> {
+        if self.ident == *parent {
+            self.children.push(child);
+            None
+        } else {
+            for state in &mut self.children {
+                if let Some(reject) = state.add_child(child, parent) {
+                    child = reject;
+                } else {
+                    return None;
+                }
+            }
+
+            Some(child)
+        }
+    }
+
+    /// Generate a simple text state chart of this State and its children.
+    pub fn state_chart(&self) -> String {
+        format!("{}", self.ident) + &self.state_chart_children(&mut Vec::new())
+    }
+
+    /// Helper function for formatting the children of a `state_chart`.
+    fn state_chart_children(&self, levels: &mut Vec<bool>) -> String {
+        if let Some((last, firsts)) = self.children.split_last() {
+            let mut acc = String::new();
+
+            for child in firsts {
+                acc += &child.state_chart_acc(levels, false);
+            }
+
+            acc + &last.state_chart_acc(levels, true)
+        } else {
+            String::new()
+        }
+    }
+
+    /// Collect a `ChartLine` for this State and each of its descendents, enabled by their `cfg`
+    /// predicates.
+    fn chart_lines(&self, depth: usize, lines: &mut Vec<TokenStream>) {
+        let cfgs = &self.cfgs;
+        let name = self.ident.to_string();
+        lines.push(quote! {
+            ::moku::internal::ChartLine {
+                enabled: cfg!(all(#(#cfgs),*)),
+                depth: #depth,
+                name: #name,
+            }
+        });
+
+        for child in &self.children {
+            child.chart_lines(depth + 1, lines);
+        }
+    }
+
+    /// Helper function for recursively formatting `state_chart`.
+    fn state_chart_acc(&self, levels: &mut Vec<bool>, last: bool) -> String {
+        let mut pad = String::new();
+        for bar in levels.iter() {
+            if *bar {
+                pad += "\u{02502}  ";
+            } else {
+                pad += "   ";
+            }
+        }
+
+        let vert = if last { '\u{02514}' } else { '\u{0251C}' };
+
+        levels.push(!last);
+        let ret =
+            format!("\n{pad}{vert}\u{02500} {}", self.ident) + &self.state_chart_children(levels);
+        levels.pop();
+
+        ret
+    }
+
+    /// Create a copy that does not include an ItemImpl or children.
+    fn shallow_copy(&self) -> Self {
+        Self {
+            ident: self.ident.clone(),
+            ty: self.ty.clone(),
+            path: self.path.clone(),
+            children: Vec::new(),
+            autogen_enter: self.autogen_enter,
+            imp: None,
+            event: self.event.clone(),
+            cfgs: self.cfgs.clone(),
+            description: self.description.clone(),
+            tags: self.tags.clone(),
+            transitions: self.transitions.clone(),
+        }
+    }
+
+    /// Execute a function for this state and each substate, with a list of shallow-copy
+    /// ancestors.
+    fn for_each_state<F: FnMut(&mut State, &Vec<State>)>(&mut self, mut fun: F) {
+        self.for_each_state_acc(&mut fun, &mut Vec::new());
+    }
+
+    /// Helper to accumulate ancestors for `for_each_state`.
+    fn for_each_state_acc<F: FnMut(&mut State, &Vec<State>)>(
+        &mut self,
+        fun: &mut F,
+        ancestors: &mut Vec<State>,
+    ) {
+        fun(self, ancestors);
+        ancestors.push(self.shallow_copy());
+        for child in &mut self.children {
+            child.for_each_state_acc(fun, ancestors);
+        }
+        ancestors.pop();
+    }
+
+    /// Get all descendents of this State.
+    fn descendent_states(&self) -> Vec<&State> {
+        let mut res = Vec::new();
+        for child in &self.children {
+            res.push(child);
+            res.extend(child.descendent_states());
+        }
+        res
+    }
+
+    /// Get the Idents of all descendents of this State.
+    fn descendents(&self) -> Vec<Ident> {
+        let mut res = Vec::new();
+        for child in &self.children {
+            child.descendents_acc(&mut res);
+        }
+        res
+    }
+
+    /// Accumulate the types of all descendents of this State.
+    fn descendent_tys(&self, acc: &mut Vec<TokenStream>) {
+        for child in &self.children {
+            acc.push(child.ty.clone());
+            child.descendent_tys(acc);
+        }
+    }
+
+    /// Helper to accumulate descendent list for `descendents`.
+    fn descendents_acc(&self, acc: &mut Vec<Ident>) {
+        acc.push(self.ident.clone());
+        for child in &self.children {
+            child.descendents_acc(acc);
+        }
+    }
+}
+
+/// The generic parameters of a `TopState`, carried through to the generated types.
+#[derive(Clone, Default)]
+pub struct MachineGenerics {
+    /// Parameters with their bounds, e.g. `H: Hal`.
+    pub params: Vec<TokenStream>,
+    /// Parameters without their bounds, for type aliases, e.g. `H`.
+    pub alias_params: Vec<TokenStream>,
+    /// Parameters as arguments, e.g. `H`.
+    pub args: Vec<TokenStream>,
+    /// Predicates of the `TopState` impl's where clause.
+    pub predicates: Vec<TokenStream>,
+}
+
+impl MachineGenerics {
+    /// Check if the `TopState` has any generic parameters.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+pub struct Metadata {
+    pub name: Ident,
+    /// Event type path for use in machine module (e.g., `super::Event`)
+    pub event: TokenStream,
+    /// Event type path for use in main module (e.g., `Event`)
+    pub event_local: TokenStream,
+    /// Event types to combine into a generated `Event` enum, if there are multiple
+    pub events: Vec<Ident>,
+    pub top_state: State,
+    pub generics: MachineGenerics,
+    pub top_state_impl: Option<ItemImpl>,
+    pub states: HashMap<Ident, State>,
+    pub machine_mod: ItemMod,
+    pub main_mod: ItemMod,
+}
+
+impl Metadata {
+    /// Add a state machine state.
+    pub fn add_state(
+        &mut self,
+        ident: &Ident,
+        autogen_enter: bool,
+        ty: TokenStream,
+        path: Vec<Ident>,
+        imp: ItemImpl,
+        event: Option<TokenStream>,
+    ) -> &mut State {
+        let mut state: State = ident.into();
+        state.ty = ty;
+        state.path = path;
+        state.autogen_enter = autogen_enter;
+        state.imp = Some(imp);
+        state.event = event;
+        self.states
+            .entry(ident.clone())
+            .insert_entry(state)
+            .into_mut()
+    }
+
+    /// Add the Substate impl item to a state.
+    pub fn add_state_impl(&mut self, ident: &Ident, imp: ItemImpl) {
+        self.states
+            .get_mut(ident)
+            .unwrap_or_else(|| panic!("add_state_impl: could not find state {}", ident))
+            .imp = Some(imp);
+    }
+
+    /// Add a parent-child relation to the state graph, while detecting state graph cycles.
+    pub fn add_relation(&mut self, parent: &Ident, child: &Ident) -> Result<(), syn::Error> {
+        let mut child = self
+            .states
+            .remove(child)
+            .unwrap_or_else(|| panic!("add_relation: could not find child {}", child));
+
+        for state in self.states.values_mut().chain([&mut self.top_state]) {
+            if let Some(reject) = state.add_child(child, parent) {
+                child = reject;
+            } else {
+                return Ok(());
+            }
+        }
+
+        // we didn't find the parent, this is a cycle
+        Err(syn::Error::new(
+            parent.span(),
+            format!(
+                "state graph cycle detected in children of {parent}:\n{parent}{}",
+                child.state_chart_acc(&mut Vec::new(), true),
+            ),
+        ))
+    }
+
+    /// Does the TopState impl declare its own associated type with the given name?
+    fn declares_type(&self, name: &str) -> bool {
+        self.top_state_impl.as_ref().is_some_and(|imp| {
+            imp.items.iter().any(|item| match item {
+                ImplItem::Type(ty) => ty.ident == name,
+                _ => false,
+            })
+        })
+    }
+
+    /// Write the state machine and return the complete main module.
+    pub fn write_state_machine(mut self) -> ItemMod {
+        // states that are compiled out take their descendents with them
+        self.top_state.inherit_cfgs(&[]);
+
+        // the bounds of generic parameters may name items of the main module
+        if !self.generics.is_empty() {
+            self.push_to_machine_mod(parse_quote! {
+                #[allow(unused_imports)]
+                use super::*;
+            });
+        }
+
+        self.write_state_chart();
+        self.write_state_enum();
+        self.write_events();
+        self.write_machine();
+        self.write_builder();
+        self.write_states();
+        self.write_top_state_impl();
+
+        // put the machine_mod back into the main module
+        let main_mod_content = &mut self
+            .main_mod
+            .content
+            .as_mut()
+            .expect("main_mod_content: no content in module")
+            .1;
+
+        main_mod_content.push(Item::Mod(self.machine_mod));
+
+        self.main_mod
+    }
+
+    /// Get a mutable reference to the contents of the machine module.
+    fn machine_mod_content(&mut self) -> &mut Vec<Item> {
+        &mut self
+            .machine_mod
+            .content
+            .as_mut()
+            .expect("machine_mod_content: no content in module")
+            .1
+    }
+
+    /// Push an Item into the contents of the machine module.
+    fn push_to_machine_mod(&mut self, item: Item) {
+        self.machine_mod_content().push(item)
+    }
+
+    /// Get an Iterator of all states in this machine.
+    fn all_states(&self) -> impl Iterator<Item = Ident> {
+        [self.top_state.ident.clone()]
+            .into_iter()
+            .chain(self.top_state.descendents())
+    }
+
+    /// Get the `cfg` attributes of all states in this machine.
+    fn all_state_cfgs(&self) -> Vec<TokenStream> {
+        [&self.top_state]
+            .into_iter()
+            .chain(self.top_state.descendent_states())
+            .map(State::cfg_attrs)
+            .collect()
+    }
+
+    /// Get the types of all states in this machine.
+    fn all_state_tys(&self) -> Vec<TokenStream> {
+        let mut tys = vec![self.top_state.ty.clone()];
+        self.top_state.descendent_tys(&mut tys);
+        tys
+    }
+
+    /// Write the `Event` enum combining multiple event types to the machine module.
+    fn write_events(&mut self) {
+        if self.events.is_empty() {
+            return;
+        }
+
+        let events = self.events.clone();
+        let docs = events.iter().map(|event| format!("A `{event}` event."));
+
+        self.push_to_machine_mod(parse_quote! {
+            /// An event of any of the event types of the state machine.
+            pub enum Event {
+                #(
+                    #[doc = #docs]
+                    #events(super::#events),
+                )*
+            }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+            impl ::moku::StateMachineEvent for Event {
+                fn name(&self) -> &str {
+                    match self {
+                        #(Self::#events(event) => ::moku::StateMachineEvent::name(event),)*
+                    }
+                }
+            }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+            impl ::core::fmt::Debug for Event
+            where
+                #(for<'a> super::#events: ::core::fmt::Debug,)*
+            {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    match self {
+                        #(Self::#events(event) => ::core::fmt::Debug::fmt(event, f),)*
+                    }
+                }
+            }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+            impl ::core::clone::Clone for Event
+            where
+                #(for<'a> super::#events: ::core::clone::Clone,)*
+            {
+                fn clone(&self) -> Self {
+                    match self {
+                        #(Self::#events(event) => Self::#events(event.clone()),)*
+                    }
+                }
+            }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+            impl ::core::cmp::PartialEq for Event
+            where
+                #(for<'a> super::#events: ::core::cmp::PartialEq,)*
+            {
+                fn eq(&self, other: &Self) -> bool {
+                    match (self, other) {
+                        #((Self::#events(a), Self::#events(b)) => a == b,)*
+                        _ => false,
+                    }
+                }
+            }
+        });
+
+        for event in &events {
+            self.push_to_machine_mod(parse_quote! {
+                impl ::core::convert::From<super::#event> for Event {
+                    fn from(event: super::#event) -> Self {
+                        Self::#event(event)
+                    }
+                }
+            });
+
+            self.push_to_machine_mod(parse_quote! {
+                impl ::core::convert::TryFrom<&Event> for super::#event
+                where
+                    for<'a> super::#event: ::core::clone::Clone,
+                {
+                    type Error = ();
+
+                    fn try_from(event: &Event) -> ::core::result::Result<Self, ()> {
+                        match event {
+                            Event::#event(event) => ::core::result::Result::Ok(event.clone()),
+                            _ => ::core::result::Result::Err(()),
+                        }
+                    }
+                }
+            });
+        }
+
+        let top_state = &self.top_state.ty;
+        let has_resources = self.declares_type("Resources");
+        let handlers = events.iter().map(|event| {
+            let handler = format_ident!("handle_{}", event.to_string().to_case(Case::Snake));
+            let handler_with = format_ident!("{handler}_with");
+            let doc = format!("Handle a `{event}` event.");
+            let doc_with = format!(
+                "Handle a `{event}` event, passing [`moku::TopState::Resources`] to the contexts of the states."
+            );
+            let handler = if has_resources {
+                TokenStream::new()
+            } else {
+                quote! {
+                    #[doc = #doc]
+                    ///
+                    /// See [`moku::StateMachine::handle_event`].
+                    pub fn #handler(&mut self, event: super::#event) -> ::moku::EventOutcome<State> {
+                        self.#handler_with(&mut (), event)
+                    }
+                }
+            };
+            quote! {
+                #handler
+
+                #[doc = #doc_with]
+                ///
+                /// See [`moku::StateMachine::handle_event_with`].
+                pub fn #handler_with(
+                    &mut self,
+                    resources: &mut <super::#top_state as ::moku::TopState>::Resources,
+                    event: super::#event,
+                ) -> ::moku::EventOutcome<State> {
+                    self.top_node.handle_event(resources, &Event::#event(event))
+                }
+            }
+        });
+
+        let MachineGenerics {
+            params,
+            args,
+            predicates,
+            ..
+        } = &self.generics;
+
+        self.push_to_machine_mod(parse_quote! {
+            impl<#(#params,)* __O: ::moku::Observer<State>, const __C: usize> Machine<#(#args,)* __O, __C>
+            where
+                #(#predicates,)*
+            {
+                #(#handlers)*
+            }
+        });
+    }
+
+    /// Write the state chart to the machine module.
+    fn write_state_chart(&mut self) {
+        let has_cfgs = self
+            .top_state
+            .descendent_states()
+            .iter()
+            .any(|state| !state.cfgs.is_empty());
+
+        if !has_cfgs {
+            let chart = self.top_state.state_chart();
+            self.push_to_machine_mod(parse_quote! {
+                pub const STATE_CHART: &str = #chart;
+            });
+            return;
+        }
+
+        // states that may be compiled out are rendered in a const context, so that the chart only
+        // shows the enabled ones
+        let mut lines = Vec::new();
+        self.top_state.chart_lines(0, &mut lines);
+
+        self.push_to_machine_mod(parse_quote! {
+            pub const STATE_CHART: &str = {
+                const LINES: &[::moku::internal::ChartLine] = &[#(#lines),*];
+                const CHART: [u8; ::moku::internal::state_chart_len(LINES)] =
+                    ::moku::internal::state_chart(LINES);
+                match ::core::str::from_utf8(&CHART) {
+                    Ok(chart) => chart,
+                    Err(_) => panic!("invalid state chart"),
+                }
+            };
+        });
+    }
+
+    /// Write the StateEnum to the machine module.
+    fn write_state_enum(&mut self) {
+        let states: Vec<_> = self.all_states().collect();
+        let cfgs = self.all_state_cfgs();
+
+        let mut docs = Vec::new();
+        let mut descriptions = Vec::new();
+        let mut state_tags = Vec::new();
+        let mut all_tags = Vec::new();
+        let mut state_transitions = Vec::new();
+        let all: Vec<_> = [&self.top_state]
+            .into_iter()
+            .chain(self.top_state.descendent_states())
+            .collect();
+        for state in &all {
+            match &state.description {
+                Some(description) => {
+                    docs.push(quote! { #[doc = #description] });
+                    descriptions.push(quote! { Some(#description) });
+                }
+                None => {
+                    docs.push(TokenStream::new());
+                    descriptions.push(quote! { None });
+                }
+            }
+
+            let tags = &state.tags;
+            state_tags.push(quote! { &[#(Tag::#tags),*] });
+            for tag in tags {
+                push_unique(&mut all_tags, tag.clone());
+            }
+
+            state_transitions.push(match &state.transitions {
+                None => quote! { None },
+                Some(transitions) => {
+                    let target_cfgs = transitions.iter().map(|transition| {
+                        all.iter()
+                            .find(|other| other.ident == *transition)
+                            .map(|other| other.cfg_attrs())
+                            .unwrap_or_default()
+                    });
+                    quote! { Some(&[#(#target_cfgs State::#transitions),*]) }
+                }
+            });
+        }
+
+        self.push_to_machine_mod(parse_quote! {
+          #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+          pub enum State {
+              #(#cfgs #docs #states,)*
+          }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+          /// The tags given to states with the `moku::tag` attribute.
+          #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+          pub enum Tag {
+              #(#all_tags,)*
+          }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+          impl State {
+              /// Get the `moku::describe` attribute or doc comment of this state, if any.
+              pub fn description(self) -> Option<&'static str> {
+                  match self {
+                      #(#cfgs State::#states => #descriptions,)*
+                  }
+              }
+
+              /// Get the `moku::tag`s of this state.
+              pub fn tags(self) -> &'static [Tag] {
+                  match self {
+                      #(#cfgs State::#states => #state_tags,)*
+                  }
+              }
+
+              /// Get the states that this state may transition to, if declared with
+              /// `moku::transitions`.
+              pub fn transitions(self) -> Option<&'static [State]> {
+                  match self {
+                      #(#cfgs State::#states => #state_transitions,)*
+                  }
+              }
+          }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+          impl ::moku::StateEnum for State {
+              const ALL: &'static [Self] = &[#(#cfgs State::#states,)*];
+
+              fn index(self) -> usize {
+                  self as usize
+              }
+          }
+        });
+
+        if cfg!(feature = "metrics") {
+            self.push_to_machine_mod(parse_quote! {
+                /// The [`moku::Metrics`] of every state in this machine.
+                pub type Metrics = ::moku::Metrics<State, { <State as ::moku::StateEnum>::ALL.len() }>;
+            });
+        }
+
+        if cfg!(feature = "arbitrary") {
+            self.push_to_machine_mod(parse_quote! {
+                impl<'a> ::moku::arbitrary::Arbitrary<'a> for State {
+                    fn arbitrary(
+                        u: &mut ::moku::arbitrary::Unstructured<'a>,
+                    ) -> ::moku::arbitrary::Result<Self> {
+                        u.choose(<Self as ::moku::StateEnum>::ALL).copied()
+                    }
+                }
+            });
+        }
+    }
+
+    /// Write the StateMachine to the machine module.
+    fn write_machine(&mut self) {
+        let event = self.event.clone();
+        let MachineGenerics {
+            params,
+            args,
+            predicates,
+            ..
+        } = self.generics.clone();
+        let top_state = self.top_state.ty.clone();
+        let top_substate = self.top_state.substate_enum_ty(&args);
+
+        let observer = if cfg!(feature = "metrics") {
+            quote! {
+                ::moku::internal::Metered<State, __O, { <State as ::moku::StateEnum>::ALL.len() }>
+            }
+        } else {
+            quote! { __O }
+        };
+
+        let top_node = quote! {
+            ::moku::internal::TopNode<State, #event, super::#top_state, #top_substate, #observer, __C>
+        };
+
+        let (observer_field, metrics) = if cfg!(feature = "metrics") {
+            (
+                quote! { observer.observer },
+                quote! {
+                    /// Get the [`Metrics`] collected by this machine.
+                    pub fn metrics(&self) -> &Metrics {
+                        &self.top_node.observer.metrics
+                    }
+
+                    /// Reset the [`Metrics`] collected by this machine.
+                    pub fn reset_metrics(&mut self) {
+                        self.top_node.observer.metrics.reset()
+                    }
+                },
+            )
+        } else {
+            (quote! { observer }, TokenStream::new())
+        };
+
+        self.push_to_machine_mod(parse_quote! {
+            pub struct Machine<#(#params,)* __O: ::moku::Observer<State> = (), const __C: usize = 16>
+            where
+                #(#predicates,)*
+            {
+                top_node: #top_node,
+            }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+            impl<#(#params,)* __O, const __C: usize> ::core::clone::Clone for Machine<#(#args,)* __O, __C>
+            where
+                #(#predicates,)*
+                __O: ::moku::Observer<State>,
+                #top_node: ::core::clone::Clone,
+            {
+                fn clone(&self) -> Self {
+                    Self {
+                        top_node: self.top_node.clone(),
+                    }
+                }
+            }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+            impl<#(#params,)* __O, const __C: usize> ::core::cmp::PartialEq for Machine<#(#args,)* __O, __C>
+            where
+                #(#predicates,)*
+                __O: ::moku::Observer<State>,
+                #top_node: ::core::cmp::PartialEq,
+            {
+                fn eq(&self, other: &Self) -> bool {
+                    self.top_node == other.top_node
+                }
+            }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+            impl<#(#params,)* __O, const __C: usize> ::core::cmp::Eq for Machine<#(#args,)* __O, __C>
+            where
+                #(#predicates,)*
+                __O: ::moku::Observer<State>,
+                #top_node: ::core::cmp::Eq,
+            {
+            }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+            impl<#(#params,)* __O, const __C: usize> ::core::hash::Hash for Machine<#(#args,)* __O, __C>
+            where
+                #(#predicates,)*
+                __O: ::moku::Observer<State>,
+                #top_node: ::core::hash::Hash,
+            {
+                fn hash<__H: ::core::hash::Hasher>(&self, state: &mut __H) {
+                    ::core::hash::Hash::hash(&self.top_node, state);
+                }
+            }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+            impl<#(#params,)* __O: ::moku::Observer<State>, const __C: usize> Machine<#(#args,)* __O, __C>
+            where
+                #(#predicates,)*
+            {
+                fn new(
+                    top_node: #top_node,
+                    resources: &mut <super::#top_state as ::moku::TopState>::Resources,
+                ) -> Self {
+                    let mut new = Self { top_node };
+                    new.top_node.init(resources);
+                    new
+                }
+
+                /// Get a reference to the [`moku::Observer`] of this machine.
+                pub fn observer(&self) -> &__O {
+                    &self.top_node.#observer_field
+                }
+
+                /// Get a mutable reference to the [`moku::Observer`] of this machine.
+                pub fn observer_mut(&mut self) -> &mut __O {
+                    &mut self.top_node.#observer_field
+                }
+
+                #metrics
+
+                /// Get the time at which a state was entered, as measured by the [`moku::Clock`] of
+                /// this machine, if the state is active.
+                pub fn entry_time(&self, state: State) -> Option<::core::time::Duration> {
+                    self.top_node.entry_time(state)
+                }
+
+                /// Get the time that a state has been active, as measured by the [`moku::Clock`] of
+                /// this machine, if the state is active.
+                pub fn time_in_state(&self, state: State) -> Option<::core::time::Duration> {
+                    self.top_node.time_in_state(state)
+                }
+
+                /// Check if any active state of this machine has the given [`Tag`].
+                pub fn in_tag(&self, tag: Tag) -> bool {
+                    <State as ::moku::StateEnum>::ALL
+                        .iter()
+                        .any(|state| state.tags().contains(&tag) && self.top_node.state_matches(*state))
+                }
+            }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+            impl<#(#params,)* const __N: usize, const __C: usize> Machine<#(#args,)* ::moku::History<State, __N>, __C>
+            where
+                #(#predicates,)*
+            {
+                /// Get the [`moku::History`] kept by this machine.
+                pub fn history(&self) -> &::moku::History<State, __N> {
+                    self.observer()
+                }
+            }
+        });
+
+        let set_name = if cfg!(feature = "std") {
+            quote! {
+                fn set_name(&mut self, name: String) {
+                    self.top_node.set_name(name)
+                }
+            }
+        } else {
+            TokenStream::new()
+        };
+
+        let state_list = if cfg!(feature = "std") {
+            quote! {
+                fn state_list(&self) -> Vec<State> {
+                    self.top_node.node.state_list(Vec::new())
+                }
+            }
+        } else {
+            TokenStream::new()
+        };
+
+        self.push_to_machine_mod(parse_quote! {
+            impl<#(#params,)* __O: ::moku::Observer<State>, const __C: usize> ::moku::StateMachine<State, #event, super::#top_state>
+                for Machine<#(#args,)* __O, __C>
+            where
+                #(#predicates,)*
+            {
+                fn update_with_input(
+                    &mut self,
+                    resources: &mut <super::#top_state as ::moku::TopState>::Resources,
+                    input: &<super::#top_state as ::moku::TopState>::Input,
+                ) {
+                    self.top_node.update(resources, input)
+                }
+
+                fn top_down_update_with_input(
+                    &mut self,
+                    resources: &mut <super::#top_state as ::moku::TopState>::Resources,
+                    input: &<super::#top_state as ::moku::TopState>::Input,
+                ) {
+                    self.top_node.top_down_update(resources, input)
+                }
+
+                fn transition_with(
+                    &mut self,
+                    resources: &mut <super::#top_state as ::moku::TopState>::Resources,
+                    target: State,
+                ) {
+                    self.top_node.transition(resources, target, false, false);
+                }
+
+                fn exact_transition_with(
+                    &mut self,
+                    resources: &mut <super::#top_state as ::moku::TopState>::Resources,
+                    target: State,
+                ) {
+                    self.top_node.transition(resources, target, false, true);
+                }
+
+                fn shutdown_with(&mut self, resources: &mut <super::#top_state as ::moku::TopState>::Resources) {
+                    self.top_node.shutdown(resources);
+                }
+
+                fn state(&self) -> State {
+                    self.top_node.state()
+                }
+
+                fn name(&self) -> &str {
+                    self.top_node.name()
+                }
+
+                #set_name
+
+                fn state_matches(&self, state: State) -> bool {
+                    self.top_node.state_matches(state)
+                }
+
+                fn top_ref(&self) -> &super::#top_state {
+                    &self.top_node.node.state
+                }
+
+                fn top_mut(&mut self) -> &mut super::#top_state {
+                    &mut self.top_node.node.state
+                }
+
+                fn handle_event_with(
+                    &mut self,
+                    resources: &mut <super::#top_state as ::moku::TopState>::Resources,
+                    event: &#event,
+                ) -> ::moku::EventOutcome<State> {
+                    self.top_node.handle_event(resources, event)
+                }
+
+                #state_list
+
+                fn outputs(
+                    &self,
+                ) -> &dyn ::moku::OutputQueue<<super::#top_state as ::moku::TopState>::Output> {
+                    &self.top_node.outputs
+                }
+
+                fn outputs_mut(
+                    &mut self,
+                ) -> &mut dyn ::moku::OutputQueue<<super::#top_state as ::moku::TopState>::Output> {
+                    &mut self.top_node.outputs
+                }
+            }
+        });
+
+        for (state, cfgs) in self.all_state_tys().into_iter().zip(self.all_state_cfgs()) {
+            self.push_to_machine_mod(parse_quote! {
+                #cfgs
+                impl<#(#params,)* __O: ::moku::Observer<State>, const __C: usize> ::moku::StateRef<State, #event, super::#state>
+                    for Machine<#(#args,)* __O, __C>
+                where
+                    #(#predicates,)*
+                {
+                    fn state_ref(&self) -> Option<&super::#state> {
+                        self.top_node.node.state_ref()
+                    }
+
+                    fn state_mut(&mut self) -> Option<&mut super::#state> {
+                        self.top_node.node.state_mut()
+                    }
+                }
+            });
+        }
+
+        // runtime lookup of active states by their State, built on the StateRef impls
+        let states: Vec<_> = self.all_states().collect();
+        let tys = self.all_state_tys();
+        let cfgs = self.all_state_cfgs();
+
+        // states are 'static if the generic parameters of the machine are
+        let static_bounds: Vec<_> = args
+            .iter()
+            .zip(&self.generics.alias_params)
+            .filter(|(_, param)| !param.to_string().starts_with("const"))
+            .map(|(arg, _)| quote! { #arg: 'static })
+            .collect();
+
+        // conditionally compiled states are named through an alias with a placeholder
+        let debug_tys: Vec<_> = [&self.top_state]
+            .into_iter()
+            .chain(self.top_state.descendent_states())
+            .map(|state| {
+                if state.cfgs.is_empty() {
+                    let ty = &state.ty;
+                    quote! { super::#ty }
+                } else {
+                    let alias = state.type_alias_ident();
+                    quote! { #alias<#(#args),*> }
+                }
+            })
+            .collect();
+        self.push_to_machine_mod(parse_quote! {
+            impl<#(#params,)* __O: ::moku::Observer<State>, const __C: usize> Machine<#(#args,)* __O, __C>
+            where
+                #(#predicates,)*
+            {
+                /// Get a reference to a state as [`::core::any::Any`], if currently active.
+                ///
+                /// Returns `None` if the state machine is not currently in the state. Only
+                /// available if every state is `'static`.
+                pub fn state_any(&self, state: State) -> Option<&dyn ::core::any::Any>
+                where
+                    #(#static_bounds,)*
+                {
+                    match state {
+                        #(#cfgs State::#states => {
+                            <Self as ::moku::StateRef<State, #event, super::#tys>>::state_ref(self)
+                                .map(|state| state as &dyn ::core::any::Any)
+                        })*
+                    }
+                }
+
+                /// Get a mutable reference to a state as [`::core::any::Any`], if currently
+                /// active.
+                ///
+                /// Returns `None` if the state machine is not currently in the state. Only
+                /// available if every state is `'static`.
+                pub fn state_any_mut(&mut self, state: State) -> Option<&mut dyn ::core::any::Any>
+                where
+                    #(#static_bounds,)*
+                {
+                    match state {
+                        #(#cfgs State::#states => {
+                            <Self as ::moku::StateRef<State, #event, super::#tys>>::state_mut(self)
+                                .map(|state| state as &mut dyn ::core::any::Any)
+                        })*
+                    }
+                }
+
+                /// Get a reference to a state as [`::core::fmt::Debug`], if currently active.
+                ///
+                /// Returns `None` if the state machine is not currently in the state. Only
+                /// available if every state implements `Debug`.
+                pub fn state_debug(&self, state: State) -> Option<&dyn ::core::fmt::Debug>
+                where
+                    // not a trivial bound, so that machines with other states still compile
+                    #(for<'__> #debug_tys: ::core::fmt::Debug,)*
+                {
+                    match state {
+                        #(#cfgs State::#states => {
+                            <Self as ::moku::StateRef<State, #event, super::#tys>>::state_ref(self)
+                                .map(|state| state as &dyn ::core::fmt::Debug)
+                        })*
+                    }
+                }
+            }
+        });
+
+        let render_active_chart = if cfg!(feature = "std") {
+            quote! {
+                /// Get the [`STATE_CHART`] with each active state marked with a `*`.
+                pub fn render_active_chart(&self) -> ::std::string::String {
+                    let lines: ::std::vec::Vec<_> = STATE_CHART
+                        .lines()
+                        .zip(<State as ::moku::StateEnum>::ALL)
+                        .map(|(line, state)| {
+                            if self.top_node.state_matches(*state) {
+                                ::std::format!("{line} *")
+                            } else {
+                                ::std::string::String::from(line)
+                            }
+                        })
+                        .collect();
+                    lines.join("\n")
+                }
+            }
+        } else {
+            TokenStream::new()
+        };
+
+        self.push_to_machine_mod(parse_quote! {
+            impl<#(#params,)* __O: ::moku::Observer<State>, const __C: usize> Machine<#(#args,)* __O, __C>
+            where
+                #(#predicates,)*
+            {
+                /// Get an active state as `dyn Debug` if it implements `Debug`, or its State
+                /// otherwise.
+                fn debug_state(&self, state: &'static State) -> &dyn ::core::fmt::Debug {
+                    use ::moku::internal::{DebugStateFallback as _, DebugStateImpl as _};
+
+                    let debug = match state {
+                        #(#cfgs State::#states => {
+                            <Self as ::moku::StateRef<State, #event, super::#tys>>::state_ref(self)
+                                .and_then(|state| (&::moku::internal::DebugState(state)).debug_state())
+                        })*
+                    };
+                    debug.unwrap_or(state)
+                }
+
+                #render_active_chart
+            }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+            impl<#(#params,)* __O: ::moku::Observer<State>, const __C: usize> ::core::fmt::Debug for Machine<#(#args,)* __O, __C>
+            where
+                #(#predicates,)*
+            {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    let active = ::moku::internal::DebugWith(|f: &mut ::core::fmt::Formatter<'_>| {
+                        f.debug_list()
+                            .entries(
+                                <State as ::moku::StateEnum>::ALL
+                                    .iter()
+                                    .filter(|state| self.top_node.state_matches(**state))
+                                    .map(|state| self.debug_state(state)),
+                            )
+                            .finish()
+                    });
+
+                    f.debug_struct("Machine")
+                        .field("name", &self.top_node.name())
+                        .field("active", &active)
+                        .finish()
+                }
+            }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+            impl<#(#params,)* __O: ::moku::Observer<State>, const __C: usize> ::core::fmt::Display for Machine<#(#args,)* __O, __C>
+            where
+                #(#predicates,)*
+            {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    ::core::write!(f, "{}[", self.top_node.name())?;
+                    let active = <State as ::moku::StateEnum>::ALL
+                        .iter()
+                        .filter(|state| self.top_node.state_matches(**state));
+                    for (i, state) in active.enumerate() {
+                        if i > 0 {
+                            f.write_str("/")?;
+                        }
+                        ::core::write!(f, "{state:?}")?;
+                    }
+                    f.write_str("]")
+                }
+            }
+        });
+    }
+
+    /// Write the Builder to the machine module.
+    fn write_builder(&mut self) {
+        let MachineGenerics {
+            params,
+            args,
+            predicates,
+            ..
+        } = self.generics.clone();
+        let top_state = self.top_state.ty.clone();
+
+        let name_field = if cfg!(feature = "std") {
+            quote! {
+                name: Option<String>,
+            }
+        } else {
+            TokenStream::new()
+        };
+
+        self.push_to_machine_mod(parse_quote! {
+            pub struct Builder<#(#params,)* __O: ::moku::Observer<State> = (), const __C: usize = 16>
+            where
+                #(#predicates,)*
+            {
+                top_state: super::#top_state,
+                #name_field
+                observer: __O,
+                clock: ::moku::Clock,
+            }
+        });
+
+        let name_field = if cfg!(feature = "std") {
+            quote! {
+                name: None,
+            }
+        } else {
+            TokenStream::new()
+        };
+
+        let name_setter = if cfg!(feature = "std") {
+            quote! {
+                fn name(mut self, name: String) -> Self {
+                    self.name = Some(name);
+                    self
+                }
+            }
+        } else {
+            TokenStream::new()
+        };
+
+        let pub_name_setter = if cfg!(feature = "std") {
+            quote! {
+                /// Set the name of the [`moku::StateMachine`].
+                ///
+                /// See [`moku::StateMachineBuilder::name`].
+                pub #name_setter
+            }
+        } else {
+            TokenStream::new()
+        };
+
+        let name_move = if cfg!(feature = "std") {
+            quote! {
+                name: self.name,
+            }
+        } else {
+            TokenStream::new()
+        };
+
+        let observer = if cfg!(feature = "metrics") {
+            quote! {
+                ::moku::internal::Metered::new(self.observer, self.clock)
+            }
+        } else {
+            quote! { self.observer }
+        };
+
+        let name = self.name.to_string();
+
+        let name_arg = if cfg!(feature = "std") {
+            quote! {
+                self.name.unwrap_or_else(|| String::from(#name)),
+            }
+        } else {
+            quote! {
+                #name,
+            }
+        };
+
+        let event = &self.event;
+
+        // without resources, machines can be built without passing any
+        let build = if self.declares_type("Resources") {
+            TokenStream::new()
+        } else {
+            quote! {
+                /// Build the [`moku::StateMachine`].
+                pub fn build(self) -> Machine<#(#args,)* __O, __C> {
+                    self.build_with(&mut ())
+                }
+            }
+        };
+
+        self.push_to_machine_mod(parse_quote! {
+            impl<#(#params),*> ::moku::StateMachineBuilder<State, #event, super::#top_state, Machine<#(#args),*>>
+                for Builder<#(#args),*>
+            where
+                #(#predicates,)*
+            {
+                fn new(top_state: super::#top_state) -> Self {
+                    Self {
+                        top_state,
+                        #name_field
+                        observer: (),
+                        clock: ::moku::Clock::default(),
+                    }
+                }
+
+                #name_setter
+
+                fn build_with(
+                    self,
+                    resources: &mut <super::#top_state as ::moku::TopState>::Resources,
+                ) -> Machine<#(#args),*> {
+                    Builder::build_with(self, resources)
+                }
+            }
+        });
+
+        self.push_to_machine_mod(parse_quote! {
+            impl<#(#params,)* __O: ::moku::Observer<State>, const __C: usize> Builder<#(#args,)* __O, __C>
+            where
+                #(#predicates,)*
+            {
+                /// Set the [`moku::Observer`] of the [`moku::StateMachine`].
+                pub fn observer<__P: ::moku::Observer<State>>(self, observer: __P) -> Builder<#(#args,)* __P, __C> {
+                    Builder {
+                        top_state: self.top_state,
+                        #name_move
+                        observer,
+                        clock: self.clock,
+                    }
+                }
+
+                /// Set the number of [`moku::TopState::Output`]s that the [`moku::StateMachine`] can
+                /// hold until they are drained, 16 by default.
+                ///
+                /// See [`moku::Outputs`].
+                pub fn output_capacity<const __D: usize>(self) -> Builder<#(#args,)* __O, __D> {
+                    Builder {
+                        top_state: self.top_state,
+                        #name_move
+                        observer: self.observer,
+                        clock: self.clock,
+                    }
+                }
+
+                #pub_name_setter
+
+                /// Set the [`moku::Clock`] used by the [`moku::StateMachine`] to measure time, e.g.
+                /// for [`Machine::time_in_state`].
+                pub fn clock(mut self, clock: ::moku::Clock) -> Self {
+                    self.clock = clock;
+                    self
+                }
+
+                #build
+
+                /// Build the [`moku::StateMachine`], passing [`moku::TopState::Resources`] to the
+                /// contexts of the states entered during its initial transition.
+                pub fn build_with(
+                    self,
+                    resources: &mut <super::#top_state as ::moku::TopState>::Resources,
+                ) -> Machine<#(#args,)* __O, __C> {
+                    Machine::new(
+                        ::moku::internal::TopNode::new(self.top_state, #name_arg #observer, self.clock),
+                        resources,
+                    )
+                }
+            }
+        });
+    }
+
+    /// Write the TopState impl with associated types to the main module.
+    fn write_top_state_impl(&mut self) {
+        let has_resources = self.declares_type("Resources");
+        let has_input = self.declares_type("Input");
+        let has_output = self.declares_type("Output");
+        if let Some(mut imp) = self.top_state_impl.take() {
+            let event_local = &self.event_local;
+            let machine_mod = self.machine_mod.ident.clone();
+
+            // Add the associated types, `Resources`, `Input`, and `Output` defaulting to `()`
+            imp.items.insert(
+                0,
+                parse_quote! {
+                    type State = #machine_mod::State;
+                },
+            );
+            imp.items.insert(
+                1,
+                parse_quote! {
+                    type Event = #event_local;
+                },
+            );
+            if !has_resources {
+                imp.items.insert(
+                    2,
+                    parse_quote! {
+                        type Resources = ();
+                    },
+                );
+            }
+            if !has_input {
+                imp.items.insert(
+                    2,
+                    parse_quote! {
+                        type Input = ();
+                    },
+                );
+            }
+            if !has_output {
+                imp.items.insert(
+                    2,
+                    parse_quote! {
+                        type Output = ();
+                    },
+                );
+            }
+
+            let main_mod_content = &mut self
+                .main_mod
+                .content
+                .as_mut()
+                .expect("main_mod_content: no content in module")
+                .1;
+
+            main_mod_content.push(Item::Impl(imp));
+        }
+    }
+
+    /// Write the auto-generated elements for each State to their impls and the machine module.
+    fn write_states(&mut self) {
+        let mut items: Vec<Item> = Vec::new();
+        let event = &self.event;
+        let event_local = &self.event_local;
+        let machine_mod = self.machine_mod.ident.clone();
+        let mut substate_impls = Vec::new();
+        let mut statelike_impls = Vec::new();
+        let all_states: Vec<_> = self
+            .all_states()
+            .zip(self.all_state_tys())
+            .zip(self.all_state_cfgs())
+            .map(|((state, ty), cfgs)| (state, ty, cfgs))
+            .collect();
+        let MachineGenerics {
+            params,
+            alias_params,
+            args,
+            predicates,
+        } = self.generics.clone();
+        let top_ty = self.top_state.ty.clone();
+
+        self.top_state.for_each_state(|state, ancestors| {
+            let is_top_state = ancestors.is_empty();
+            let cfgs = state.cfg_attrs();
+
+            let parent_context = |lifetime: TokenStream| match ancestors.last() {
+                None => quote! { ::moku::internal::TopContext<#lifetime, super::#top_ty> },
+                Some(parent) => parent.context_ty(lifetime, &args),
+            };
+
+            // Substate enter, associated types, and StateLike impl
+            if !is_top_state {
+                let mut imp = state.imp.take().unwrap_or_else(|| {
+                    panic!("write_states: missing Substate impl for {}", state.ident)
+                });
+
+                // states in submodules name the items of the state_machine module through `super`
+                let prefix = scope(state.path.len());
+                let machine_mod = quote! { #prefix #machine_mod };
+                let top_ty = quote! { #prefix #top_ty };
+                let scoped_event = |depth: usize| match event_local.to_string().as_str() {
+                    "()" => event_local.clone(),
+                    _ => {
+                        let prefix = scope(depth);
+                        quote! { #prefix #event_local }
+                    }
+                };
+                let is_narrow = |event: &TokenStream, depth: usize| {
+                    event.to_string() != event_local.to_string()
+                        && event.to_string() != scoped_event(depth).to_string()
+                };
+                let event_local = scoped_event(state.path.len());
+
+                // the nearest narrower event type declared by this state or an ancestor, named
+                // through the `Substate` impl of an ancestor in another module
+                let narrow_event = match &state.event {
+                    Some(event) => Some(event.clone()).filter(|event| is_narrow(event, state.path.len())),
+                    None => ancestors
+                        .iter()
+                        .enumerate()
+                        .rev()
+                        .find(|(_, anc)| anc.event.is_some())
+                        .and_then(|(i, anc)| {
+                            let event = anc.event.clone().filter(|event| is_narrow(event, anc.path.len()))?;
+                            if anc.path == state.path {
+                                return Some(event);
+                            }
+
+                            let anc_ty = &anc.ty;
+                            let parent_ty = &ancestors[i - 1].ty;
+                            let top_arg = if params.is_empty() { quote! { () } } else { top_ty.clone() };
+                            Some(quote! {
+                                <#prefix #anc_ty as ::moku::Substate<#prefix #parent_ty, #top_arg>>::Event
+                            })
+                        }),
+                };
+                let state_event = narrow_event.as_ref().unwrap_or(&event_local);
+
+                // states with declared transitions may only target a narrowed enum of those states
+                let state_enum = match &state.transitions {
+                    None => quote! { #machine_mod::State },
+                    Some(transitions) => {
+                        let target = state.target_ident();
+                        let target_cfgs: Vec<_> = transitions
+                            .iter()
+                            .map(|transition| {
+                                all_states
+                                    .iter()
+                                    .find(|(other, _, _)| other == transition)
+                                    .map(|(_, _, cfgs)| cfgs.clone())
+                                    .unwrap_or_default()
+                            })
+                            .collect();
+                        let doc = format!(
+                            "The states that `{}` may transition to, declared with `moku::transitions`.",
+                            state.ident
+                        );
+
+                        items.push(parse_quote! {
+                            #cfgs
+                            #[doc = #doc]
+                            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+                            pub enum #target {
+                                #(#target_cfgs #transitions,)*
+                            }
+                        });
+
+                        items.push(parse_quote! {
+                            #cfgs
+                            impl ::moku::StateEnum for #target {
+                                const ALL: &'static [Self] = &[#(#target_cfgs Self::#transitions,)*];
+                            }
+                        });
+
+                        items.push(parse_quote! {
+                            #cfgs
+                            impl ::core::convert::From<#target> for State {
+                                fn from(target: #target) -> Self {
+                                    match target {
+                                        #(#target_cfgs #target::#transitions => State::#transitions,)*
+                                    }
+                                }
+                            }
+                        });
+
+                        quote! { #machine_mod::#target }
+                    }
+                };
+
+                // Add associated types at the beginning
+                imp.items.insert(
+                    0,
+                    parse_quote! {
+                        type State = #state_enum;
+                    },
+                );
+                imp.items.insert(
+                    1,
+                    parse_quote! {
+                        type Event = #state_event;
+                    },
+                );
+                // contexts borrow the TopState, so its generic parameters must outlive them
+                let context = parent_context(quote! { '__ctx });
+                let context_bound = if params.is_empty() {
+                    TokenStream::new()
+                } else {
+                    quote! { where #top_ty: '__ctx }
+                };
+                imp.items.insert(
+                    2,
+                    parse_quote! {
+                        type Context<'__ctx> = #machine_mod::#context #context_bound;
+                    },
+                );
+                imp.items.insert(
+                    3,
+                    parse_quote! {
+                        type Input = <#top_ty as ::moku::TopState>::Input;
+                    },
+                );
+
+                // Add enter method if autogenerated
+                if state.autogen_enter {
+                    imp.items.push(parse_quote! {
+                        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<::moku::Entry<Self::State, Self>> {
+                            Self {}
+                        }
+                    });
+                }
+
+                // Generate StateLike impl that bridges to Substate
+                // events that can't be converted to a narrower event type are deferred
+                let handle_event = match &narrow_event {
+                    None => quote! {
+                        <Self as ::moku::Substate<_, _>>::handle_event(self, ctx, event).into()
+                    },
+                    Some(narrow_event) => quote! {
+                        match <#narrow_event as ::core::convert::TryFrom<&#event_local>>::try_from(event) {
+                            ::core::result::Result::Ok(event) => {
+                                <Self as ::moku::Substate<_, _>>::handle_event(self, ctx, &event).into()
+                            }
+                            ::core::result::Result::Err(_) => ::moku::Response::Next(::moku::Next::None),
+                        }
+                    },
+                };
+
+                let (impl_generics, _, where_clause) = imp.generics.split_for_impl();
+                let self_ty = &imp.self_ty;
+
+                statelike_impls.push((state.path.clone(), parse_quote! {
+                    #cfgs
+                    impl #impl_generics ::moku::internal::StateLike<#machine_mod::State, #event_local, #top_ty>
+                        for #self_ty
+                    #where_clause
+                    {
+                        type Context<'__ctx> = #machine_mod::#context #context_bound;
+
+                        type Input = <#top_ty as ::moku::TopState>::Input;
+
+                        fn enter(ctx: &mut Self::Context<'_>) -> ::moku::Entry<#machine_mod::State, Self> {
+                            let entry: ::moku::Entry<#state_enum, Self> =
+                                <Self as ::moku::Substate<_, _>>::enter(ctx).into();
+                            ::moku::internal::widen_entry(entry)
+                        }
+
+                        fn init(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<::moku::Next<#machine_mod::State>> {
+                            let next: ::moku::Next<#state_enum> =
+                                <Self as ::moku::Substate<_, _>>::init(self, ctx).into();
+                            ::moku::internal::widen_next(next)
+                        }
+
+                        fn update(
+                            &mut self,
+                            ctx: &mut Self::Context<'_>,
+                            input: &Self::Input,
+                        ) -> impl Into<::moku::Next<#machine_mod::State>> {
+                            let next: ::moku::Next<#state_enum> =
+                                <Self as ::moku::Substate<_, _>>::update(self, ctx, input).into();
+                            ::moku::internal::widen_next(next)
+                        }
+
+                        fn top_down_update(
+                            &mut self,
+                            ctx: &mut Self::Context<'_>,
+                            input: &Self::Input,
+                        ) -> impl Into<::moku::Next<#machine_mod::State>> {
+                            let next: ::moku::Next<#state_enum> =
+                                <Self as ::moku::Substate<_, _>>::top_down_update(self, ctx, input).into();
+                            ::moku::internal::widen_next(next)
+                        }
+
+                        fn exit(self, ctx: &mut Self::Context<'_>) -> impl Into<::moku::Next<#machine_mod::State>> {
+                            let next: ::moku::Next<#state_enum> =
+                                <Self as ::moku::Substate<_, _>>::exit(self, ctx).into();
+                            ::moku::internal::widen_next(next)
+                        }
+
+                        fn handle_event(
+                            &mut self,
+                            ctx: &mut Self::Context<'_>,
+                            event: &#event_local,
+                        ) -> impl Into<::moku::Response<#machine_mod::State>> {
+                            let response: ::moku::Response<#state_enum> = #handle_event;
+                            ::moku::internal::widen_response(response)
+                        }
+                    }
+                }));
+
+                substate_impls.push((state.path.clone(), imp));
+            }
+
+            // Node
+            let state_ident = state.ident.clone();
+            let state_ty = state.ty.clone();
+            let node = state.node_ident();
+            let node_ty = state.node_ty(&args);
+            let substate = state.substate_enum_ident();
+            let substate_ty = state.substate_enum_ty(&args);
+
+            items.push(parse_quote! {
+               #cfgs
+               type #node<#(#alias_params),*> =
+                   ::moku::internal::Node<State, #event, super::#state_ty, #substate_ty, super::#top_ty>;
+            });
+
+            // the bounds on the Nodes of children can't be conditionally compiled, so they name a
+            // placeholder instead
+            if !state.cfgs.is_empty() {
+                let state_cfgs = &state.cfgs;
+                items.push(parse_quote! {
+                    #[cfg(not(all(#(#state_cfgs),*)))]
+                    type #node<#(#alias_params),*> = ::core::marker::PhantomData<fn() -> super::#top_ty>;
+                });
+
+                // likewise for the bounds on the state itself
+                let type_alias = state.type_alias_ident();
+                items.push(parse_quote! {
+                    #cfgs
+                    type #type_alias<#(#alias_params),*> =
+                        <super::#state_ty as ::moku::internal::Identity<fn() -> super::#top_ty>>::Type;
+                });
+                items.push(parse_quote! {
+                    #[cfg(not(all(#(#state_cfgs),*)))]
+                    type #type_alias<#(#alias_params),*> = ::core::marker::PhantomData<fn() -> super::#top_ty>;
+                });
+            }
+
+            // Context
+            let context = state.context_ident();
+            let parent_context = parent_context(quote! { '_ });
+
+            let ancestor_tys = ancestors.iter().map(|anc| &anc.ty);
+            let ancestor_idents_snake: Vec<_> = ancestors
+                .iter()
+                .map(|anc| Ident::new(&anc.ident.to_string().to_case(Case::Snake), Span::call_site()))
+                .collect();
+            let state_ident_snake =
+                Ident::new(&state_ident.to_string().to_case(Case::Snake), Span::call_site());
+
+            items.push(parse_quote! {
+               #cfgs
+               pub struct #context<'__ctx, #(#params),*>
+               where
+                   #(#predicates,)*
+               {
+                   pub resources: &'__ctx mut <super::#top_ty as ::moku::TopState>::Resources,
+                   pub outputs: &'__ctx mut dyn ::moku::OutputQueue<<super::#top_ty as ::moku::TopState>::Output>,
+                   #(pub #ancestor_idents_snake: &'__ctx mut super::#ancestor_tys,)*
+                   pub #state_ident_snake: &'__ctx mut super::#state_ty,
+                   __time: ::moku::internal::ContextTime,
+               }
+            });
+
+            items.push(parse_quote! {
+               #cfgs
+               impl<'__ctx, #(#params),*> #context<'__ctx, #(#args),*>
+               where
+                   #(#predicates,)*
+               {
+                   pub fn new(state: &'__ctx mut super::#state_ty, ctx: &'__ctx mut #parent_context) -> Self {
+                       let time = *::moku::internal::TimedContext::time(ctx);
+                       Self {
+                           resources: ctx.resources,
+                           outputs: ctx.outputs,
+                           #(#ancestor_idents_snake: ctx.#ancestor_idents_snake,)*
+                           #state_ident_snake: state,
+                           __time: time,
+                       }
+                   }
+               }
+            });
+
+            items.push(parse_quote! {
+               #cfgs
+               impl<'__ctx, #(#params),*> ::moku::internal::TimedContext for #context<'__ctx, #(#args),*>
+               where
+                   #(#predicates,)*
+               {
+                   fn time(&self) -> &::moku::internal::ContextTime {
+                       &self.__time
+                   }
+
+                   fn time_mut(&mut self) -> &mut ::moku::internal::ContextTime {
+                       &mut self.__time
+                   }
+               }
+            });
+
+            items.push(parse_quote! {
+               #cfgs
+               impl<'__ctx, #(#params),*> ::moku::StateContext for #context<'__ctx, #(#args),*>
+               where
+                   #(#predicates,)*
+               {
+               }
+            });
+
+            // typed access to the states of the context, e.g. for helpers generic over contexts
+            for (field, ty) in ancestor_idents_snake
+                .iter()
+                .zip(ancestors.iter().map(|anc| &anc.ty))
+                .chain([(&state_ident_snake, &state_ty)])
+            {
+                items.push(parse_quote! {
+                   #cfgs
+                   impl<'__ctx, #(#params),*> ::moku::HasState<super::#ty> for #context<'__ctx, #(#args),*>
+                   where
+                       #(#predicates,)*
+                   {
+                       fn state(&self) -> &super::#ty {
+                           self.#field
+                       }
+
+                       fn state_mut(&mut self) -> &mut super::#ty {
+                           self.#field
+                       }
+                   }
+                });
+            }
+
+            // SubstateEnum
+            let children: Vec<_> = state.children.iter().map(|child| &child.ident).collect();
+            let children_nodes: Vec<_> = state.children.iter().map(|child| child.node_ty(&args)).collect();
+            let child_cfgs: Vec<_> = state.children.iter().map(State::cfg_attrs).collect();
+            let descendents = state.descendents();
+            let is_leaf_state = children.is_empty();
+
+            // a SubstateEnum whose children may all be compiled out must still use the generic
+            // parameters of the machine
+            let conditional_children = state.children.iter().any(|child| child.has_own_cfgs(state));
+            let (phantom_variant, phantom_arm) = if !is_leaf_state
+                && !params.is_empty()
+                && state.children.iter().all(|child| child.has_own_cfgs(state))
+            {
+                (
+                    quote! {
+                        #[allow(dead_code)]
+                        __Phantom(::core::marker::PhantomData<fn() -> super::#top_ty>),
+                    },
+                    quote! { #substate::__Phantom(..) => ::core::unreachable!(), },
+                )
+            } else {
+                (TokenStream::new(), TokenStream::new())
+            };
+            let allow_unused = if conditional_children {
+                quote! { #[allow(unused_variables, unreachable_code)] }
+            } else {
+                TokenStream::new()
+            };
+
+            // leaf SubstateEnums don't use the generic parameters of the machine
+            let (enum_params, enum_predicates) = if is_leaf_state {
+                (Vec::new(), Vec::new())
+            } else {
+                (params.clone(), predicates.clone())
+            };
+
+            items.push(parse_quote! {
+              #cfgs
+              enum #substate<#(#enum_params),*>
+              where
+                  #(#enum_predicates,)*
+              {
+                  None,
+                  #(#child_cfgs #children(#children_nodes),)*
+                  #phantom_variant
+              }
+            });
+
+            let state_list = if cfg!(feature = "std") {
+                quote! {
+                    fn state_list(&self, list: Vec<State>) -> Vec<State> {
+                        match self {
+                            Self::None => list,
+                            #phantom_arm
+                            #(#child_cfgs Self::#children(node) => node.state_list(list),)*
+                        }
+                    }
+                }
+            } else {
+                TokenStream::new()
+            };
+
+            // Clone, PartialEq, Eq, and Hash, bounded on the state data of every descendent
+            //
+            // the bounds are wrapped in a `for<'__ctx>` binder so that they are checked where the
+            // impls are used rather than where they are defined
+            let eq_fallback = if children.is_empty() {
+                TokenStream::new()
+            } else {
+                quote! { _ => false, }
+            };
+
+            items.push(parse_quote! {
+                #cfgs
+                impl<#(#enum_params),*> ::core::clone::Clone for #substate_ty
+                where
+                    #(#enum_predicates,)*
+                    #(for<'__ctx> #children_nodes: ::core::clone::Clone,)*
+                {
+                    fn clone(&self) -> Self {
+                        match self {
+                            Self::None => Self::None,
+                            #phantom_arm
+                            #(#child_cfgs Self::#children(node) => Self::#children(node.clone()),)*
+                        }
+                    }
+                }
+            });
+
+            items.push(parse_quote! {
+                #cfgs
+                impl<#(#enum_params),*> ::core::cmp::PartialEq for #substate_ty
+                where
+                    #(#enum_predicates,)*
+                    #(for<'__ctx> #children_nodes: ::core::cmp::PartialEq,)*
+                {
+                    fn eq(&self, other: &Self) -> bool {
+                        match (self, other) {
+                            (Self::None, Self::None) => true,
+                            #(#child_cfgs (Self::#children(node), Self::#children(other)) => node == other,)*
+                            #eq_fallback
+                        }
+                    }
+                }
+            });
+
+            items.push(parse_quote! {
+                #cfgs
+                impl<#(#enum_params),*> ::core::cmp::Eq for #substate_ty
+                where
+                    #(#enum_predicates,)*
+                    #(for<'__ctx> #children_nodes: ::core::cmp::Eq,)*
+                {
+                }
+            });
+
+            items.push(parse_quote! {
+                #cfgs
+                impl<#(#enum_params),*> ::core::hash::Hash for #substate_ty
+                where
+                    #(#enum_predicates,)*
+                    #(for<'__ctx> #children_nodes: ::core::hash::Hash,)*
+                {
+                    fn hash<__H: ::core::hash::Hasher>(&self, state: &mut __H) {
+                        ::core::hash::Hash::hash(&::core::mem::discriminant(self), state);
+                        match self {
+                            Self::None => (),
+                            #phantom_arm
+                            #(#child_cfgs Self::#children(node) => ::core::hash::Hash::hash(node, state),)*
+                        }
+                    }
+                }
+            });
+
+            if is_leaf_state {
+                items.push(parse_quote! {
+                    #cfgs
+                    #allow_unused
+                    impl<#(#params),*> ::moku::internal::SubstateEnum<State, #event, super::#state_ty, super::#top_ty>
+                        for #substate_ty
+                    where
+                        #(#predicates,)*
+                    {
+                        fn none_variant() -> Self {
+                            Self::None
+                        }
+
+                        fn this_state() -> State {
+                            State::#state_ident
+                        }
+
+                        fn is_state(state: State) -> bool {
+                            matches!(state, State::#state_ident)
+                        }
+
+                        #state_list
+                    }
+                });
+            } else {
+                // the index of the child towards each descendent
+                let (targets, target_children): (Vec<_>, Vec<_>) = state
+                    .children
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, child)| {
+                        [child]
+                            .into_iter()
+                            .chain(child.descendent_states())
+                            .map(move |target| (target, Literal::usize_unsuffixed(i)))
+                    })
+                    .unzip();
+                let target_cfgs = targets.iter().map(|target| target.cfg_attrs());
+                let targets = targets.iter().map(|target| &target.ident);
+                let child_indices = (0..children.len()).map(Literal::usize_unsuffixed);
+
+                let is_ancestor = if is_top_state {
+                    quote! {
+                        fn is_ancestor(state: State) -> bool {
+                            !matches!(state, State::#state_ident)
+                        }
+                    }
+                } else {
+                    let descendent_cfgs = state.descendent_states().into_iter().map(State::cfg_attrs);
+                    quote! {
+                        fn is_ancestor(state: State) -> bool {
+                            match state {
+                                #(#descendent_cfgs State::#descendents => true,)*
+                                _ => false,
+                            }
+                        }
+                    }
+                };
+
+                items.push(parse_quote! {
+                    #cfgs
+                    #allow_unused
+                    impl<#(#params),*> ::moku::internal::SubstateEnum<State, #event, super::#state_ty, super::#top_ty>
+                        for #substate_ty
+                    where
+                        #(#predicates,)*
+                    {
+                        fn none_variant() -> Self {
+                            Self::None
+                        }
+
+                        fn this_state() -> State {
+                            State::#state_ident
+                        }
+
+                        fn is_state(state: State) -> bool {
+                            matches!(state, State::#state_ident)
+                        }
+
+                        fn current_state(&self) -> State {
+                            match self {
+                                Self::None => State::#state_ident,
+                                #phantom_arm
+                                #(#child_cfgs Self::#children(node) => node.current_state(),)*
+                            }
+                        }
+
+                        #is_ancestor
+
+                        fn update<__O: ::moku::Observer<State>>(
+                            &mut self,
+                            state: &mut super::#state_ty,
+                            ctx: &mut <super::#state_ty as ::moku::internal::StateLike<State, #event, super::#top_ty>>::Context<'_>,
+                            input: &<super::#top_ty as ::moku::TopState>::Input,
+                            observer: &mut __O,
+                        ) -> ::moku::Next<State> {
+                            match self {
+                                Self::None => ::moku::Next::None,
+                                #phantom_arm
+                                #(#child_cfgs Self::#children(node) => node.update(&mut #context::new(state, ctx), input, observer),)*
+                            }
+                        }
+
+                        fn update_in_need<__O: ::moku::Observer<State>>(
+                            &mut self,
+                            state: &mut super::#state_ty,
+                            ctx: &mut <super::#state_ty as ::moku::internal::StateLike<State, #event, super::#top_ty>>::Context<'_>,
+                            input: &<super::#top_ty as ::moku::TopState>::Input,
+                            observer: &mut __O,
+                        ) -> ::moku::Next<State> {
+                            match self {
+                                Self::None => ::moku::Next::None,
+                                #phantom_arm
+                                #(#child_cfgs Self::#children(node) => node.update_in_need(&mut #context::new(state, ctx), input, observer),)*
+                            }
+                        }
+
+                        fn top_down_update<__O: ::moku::Observer<State>>(
+                            &mut self,
+                            state: &mut super::#state_ty,
+                            ctx: &mut <super::#state_ty as ::moku::internal::StateLike<State, #event, super::#top_ty>>::Context<'_>,
+                            input: &<super::#top_ty as ::moku::TopState>::Input,
+                            observer: &mut __O,
+                        ) -> ::moku::Next<State> {
+                            match self {
+                                Self::None => ::moku::Next::None,
+                                #phantom_arm
+                                #(#child_cfgs Self::#children(node) => {
+                                    node.top_down_update(&mut #context::new(state, ctx), input, observer)
+                                })*
+                            }
+                        }
+
+                        fn top_down_update_in_need<__O: ::moku::Observer<State>>(
+                            &mut self,
+                            state: &mut super::#state_ty,
+                            ctx: &mut <super::#state_ty as ::moku::internal::StateLike<State, #event, super::#top_ty>>::Context<'_>,
+                            input: &<super::#top_ty as ::moku::TopState>::Input,
+                            observer: &mut __O,
+                        ) -> ::moku::Next<State> {
+                            match self {
+                                Self::None => ::moku::Next::None,
+                                #phantom_arm
+                                #(#child_cfgs Self::#children(node) => {
+                                    node.top_down_update_in_need(&mut #context::new(state, ctx), input, observer)
+                                })*
+                            }
+                        }
+
+                        fn clear_top_down_updated(&mut self) {
+                            match self {
+                                Self::None => (),
+                                #phantom_arm
+                                #(#child_cfgs Self::#children(node) => node.clear_top_down_updated(),)*
+                            }
+                        }
+
+                        fn exit<__O: ::moku::Observer<State>>(
+                            &mut self,
+                            state: &mut super::#state_ty,
+                            ctx: &mut <super::#state_ty as ::moku::internal::StateLike<State, #event, super::#top_ty>>::Context<'_>,
+                            in_update: bool,
+                            observer: &mut __O,
+                        ) -> ::moku::Next<State> {
+                            let old_state = core::mem::replace(self, Self::None);
+                            match old_state {
+                                Self::None => ::moku::Next::None,
+                                #phantom_arm
+                                #(#child_cfgs Self::#children(node) => node.exit(
+                                        &mut #context::new(state, ctx),
+                                        in_update,
+                                        observer,
+                                ),)*
+                            }
+                        }
+
+                        fn transition<__O: ::moku::Observer<State>>(
+                            &mut self,
+                            target: State,
+                            state: &mut super::#state_ty,
+                            ctx: &mut <super::#state_ty as ::moku::internal::StateLike<State, #event, super::#top_ty>>::Context<'_>,
+                            in_update: bool,
+                            exact: bool,
+                            observer: &mut __O,
+                        ) -> ::moku::internal::TransitionResult<State> {
+                            match self {
+                                Self::None => ::moku::internal::TransitionResult::MoveUp,
+                                #phantom_arm
+                                #(#child_cfgs Self::#children(node) => {
+                                    node.transition(target, &mut #context::new(state, ctx), in_update, exact, observer)
+                                })*
+                            }
+                        }
+
+                        fn enter_substate_towards<__O: ::moku::Observer<State>>(
+                            &mut self,
+                            target: State,
+                            state: &mut super::#state_ty,
+                            ctx: &mut <super::#state_ty as ::moku::internal::StateLike<State, #event, super::#top_ty>>::Context<'_>,
+                            in_update: bool,
+                            observer: &mut __O,
+                        ) -> ::moku::Next<State> {
+                            let child = match target {
+                                #(#target_cfgs State::#targets => #target_children,)*
+                                _ => unreachable!(),
+                            };
+
+                            match child {
+                                #(
+                                    #child_cfgs #child_indices => {
+                                        match <#children_nodes>::enter(
+                                            &mut #context::new(state, ctx),
+                                            in_update,
+                                            observer,
+                                        ) {
+                                            ::moku::internal::NodeEntry::Node(node) => {
+                                                *self = Self::#children(node);
+                                                ::moku::Next::None
+                                            }
+                                            ::moku::internal::NodeEntry::Target(new_target) => ::moku::Next::Target(new_target),
+                                            ::moku::internal::NodeEntry::ExactTarget(new_target) => ::moku::Next::ExactTarget(new_target),
+                                        }
+                                    }
+                                )*
+                                _ => unreachable!(),
+                            }
+                        }
+
+                        fn state_matches(&self, state: State) -> bool {
+                            Self::is_state(state)
+                                || match self {
+                                    Self::None => false,
+                                    #phantom_arm
+                                    #(#child_cfgs Self::#children(node) => node.state_matches(state),)*
+                                }
+                        }
+
+                        fn entry_time(&self, state: State) -> Option<::core::time::Duration> {
+                            match self {
+                                Self::None => None,
+                                #phantom_arm
+                                #(#child_cfgs Self::#children(node) => node.entry_time(state),)*
+                            }
+                        }
+
+                        fn handle_event<__O: ::moku::Observer<State>>(
+                            &mut self,
+                            event: &#event,
+                            state: &mut super::#state_ty,
+                            ctx: &mut <super::#state_ty as ::moku::internal::StateLike<State, #event, super::#top_ty>>::Context<'_>,
+                            observer: &mut __O,
+                        ) -> ::moku::EventOutcome<State> {
+                            match self {
+                                Self::None => ::moku::EventOutcome::Unhandled,
+                                #phantom_arm
+                                #(#child_cfgs Self::#children(node) => node.handle_event(
+                                        &mut #context::new(state, ctx),
+                                        event,
+                                        observer,
+                                ),)*
+                            }
+                        }
+
+                        #state_list
+                    }
+                });
+            }
+
+            // StateRef
+            for (other_state, other_ty, other_cfgs) in &all_states {
+                items.push(if *other_state == state_ident {
+                    parse_quote! {
+                        #cfgs
+                        #other_cfgs
+                        impl<#(#params),*> ::moku::StateRef<State, #event, super::#other_ty> for #node_ty
+                        where
+                            #(#predicates,)*
+                        {
+                            fn state_ref(&self) -> Option<&super::#other_ty> {
+                                Some(&self.state)
+                            }
+
+                            fn state_mut(&mut self) -> Option<&mut super::#other_ty> {
+                                Some(&mut self.state)
+                            }
+                        }
+                    }
+                } else if descendents.contains(other_state) {
+                    parse_quote! {
+                        #cfgs
+                        #other_cfgs
+                        impl<#(#params),*> ::moku::StateRef<State, #event, super::#other_ty> for #node_ty
+                        where
+                            #(#predicates,)*
+                        {
+                            fn state_ref(&self) -> Option<&super::#other_ty> {
+                                match &self.substate {
+                                    #substate::None => None,
+                                    #phantom_arm
+                                    #(#child_cfgs #substate::#children(node) => node.state_ref(),)*
+                                }
+                            }
+
+                            fn state_mut(&mut self) -> Option<&mut super::#other_ty> {
+                                match &mut self.substate {
+                                    #substate::None => None,
+                                    #phantom_arm
+                                    #(#child_cfgs #substate::#children(node) => node.state_mut(),)*
+                                }
+                            }
+                        }
+                    }
+                } else {
+                    parse_quote! {
+                        #cfgs
+                        #other_cfgs
+                        impl<#(#params),*> ::moku::StateRef<State, #event, super::#other_ty> for #node_ty
+                        where
+                            #(#predicates,)*
+                        {
+                            fn state_ref(&self) -> Option<&super::#other_ty> {
+                                None
+                            }
+
+                            fn state_mut(&mut self) -> Option<&mut super::#other_ty> {
+                                None
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        let main_mod_content = &mut self
+            .main_mod
+            .content
+            .as_mut()
+            .expect("main_mod_content: no content in module")
+            .1;
+
+        // the impls of states in submodules are put back into their submodules
+        for (path, imp) in substate_impls.into_iter().chain(statelike_impls) {
+            submodule_items(main_mod_content, &path)
+                .expect("write_states: missing submodule")
+                .push(Item::Impl(imp));
+        }
+
+        self.machine_mod_content().extend(items);
+    }
+}
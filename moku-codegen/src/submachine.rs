@@ -0,0 +1,282 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_quote,
+    spanned::Spanned,
+    Expr, FnArg, Ident, ImplItem, ImplItemFn, ItemImpl, Member, Pat, Token,
+};
+
+use crate::util::take_attributes;
+
+/// The arguments of a `moku::submachine` attribute.
+struct Args {
+    field: Member,
+    resources: Option<Expr>,
+    input: Option<Expr>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = Args {
+            field: input.parse()?,
+            resources: None,
+            input: None,
+        };
+
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+
+            let name: Ident = input.parse()?;
+            let arg = if name == "resources" {
+                &mut args.resources
+            } else if name == "input" {
+                &mut args.input
+            } else {
+                return Err(syn::Error::new(
+                    name.span(),
+                    "expected `resources` or `input`, e.g. \
+                    `#[moku::submachine(field, resources = ctx.resources)]`",
+                ));
+            };
+
+            if arg.is_some() {
+                return Err(syn::Error::new(
+                    name.span(),
+                    format!("`{name}` is already given for this `moku::submachine`"),
+                ));
+            }
+
+            input.parse::<Token![=]>()?;
+            *arg = Some(input.parse()?);
+        }
+
+        Ok(args)
+    }
+}
+
+impl Args {
+    /// Bind `ctx` and `input` to the parameters of a hook, for the `resources` and `input`
+    /// arguments to use, and bind `__resources` and `__input` to those arguments.
+    ///
+    /// Without arguments, the submachine is given `()`, spanned by the field so that type errors
+    /// point to it.
+    fn bindings(&self, ctx: &Ident, input: Option<&Ident>) -> TokenStream {
+        let span = self.field.span();
+        let resources = match &self.resources {
+            Some(resources) => resources.to_token_stream(),
+            None => quote_spanned! {span=> &mut () },
+        };
+
+        let input = input.map(|input| {
+            let sub_input = match &self.input {
+                Some(sub_input) => sub_input.to_token_stream(),
+                None => quote_spanned! {span=> &() },
+            };
+            quote! {
+                #[allow(unused_variables)]
+                let input = #input;
+                let __input: &_ = #sub_input;
+            }
+        });
+
+        quote! {
+            #[allow(unused_variables)]
+            let ctx = &mut *#ctx;
+            #input
+            let __resources: &mut _ = #resources;
+        }
+    }
+
+    /// Call a method of the submachine, spanned by the field so that type errors point to it.
+    fn call(&self, method: &str, args: TokenStream) -> TokenStream {
+        let field = &self.field;
+        let method = Ident::new(method, field.span());
+        quote_spanned! {field.span()=>
+            ::moku::StateMachine::#method(&mut self.#field, #args)
+        }
+    }
+}
+
+/// Forward the `update`, `top_down_update`, `exit`, and deferred events of a `Substate` impl
+/// attributed with `moku::submachine` to the nested state machine in the field that it names.
+///
+/// Must be called after `moku::on` handlers are expanded and input parameters are added, so that
+/// every hook to forward from has all of its parameters.
+pub fn expand_submachine(imp: &mut ItemImpl) -> Result<(), syn::Error> {
+    let mut attrs = take_attributes(&mut imp.attrs, "submachine");
+    if attrs.len() > 1 {
+        return Err(syn::Error::new(
+            attrs[1].span(),
+            "multiple `moku::submachine` attributes defined for this impl",
+        ));
+    }
+
+    let args: Args = match attrs.pop() {
+        Some(attr) => attr.parse_args()?,
+        None => return Ok(()),
+    };
+
+    // the submachine updates before the state, so that the state can check it
+    match find_fn(imp, "update") {
+        Some(fun) => {
+            let bindings = args.bindings(&param(fun, 1, "ctx")?, Some(&param(fun, 2, "input")?));
+            let update = args.call("update_with_input", quote! { __resources, __input });
+            fun.block
+                .stmts
+                .insert(0, parse_quote! {{ #bindings #update; }});
+        }
+        None => {
+            let bindings = args.bindings(&parse_quote!(ctx), Some(&parse_quote!(input)));
+            let update = args.call("update_with_input", quote! { __resources, __input });
+            imp.items.push(parse_quote! {
+                fn update(
+                    &mut self,
+                    ctx: &mut Self::Context<'_>,
+                    input: &Self::Input,
+                ) -> impl ::core::convert::Into<::moku::Next<Self::State>> {
+                    #bindings
+                    #update;
+                }
+            });
+        }
+    }
+
+    // the submachine updates after the state from the top down, unless the state transitions
+    match find_fn(imp, "top_down_update") {
+        Some(fun) => {
+            let bindings = args.bindings(&param(fun, 1, "ctx")?, Some(&param(fun, 2, "input")?));
+            let update = args.call(
+                "top_down_update_with_input",
+                quote! { __resources, __input },
+            );
+            let block = &fun.block;
+            fun.block = parse_quote! {{
+                #[allow(clippy::redundant_closure_call)]
+                let next: ::moku::Next<Self::State> = (|| #block)().into();
+                if ::core::matches!(next, ::moku::Next::None) {
+                    #bindings
+                    #update;
+                }
+                next
+            }};
+        }
+        None => {
+            let bindings = args.bindings(&parse_quote!(ctx), Some(&parse_quote!(input)));
+            let update = args.call(
+                "top_down_update_with_input",
+                quote! { __resources, __input },
+            );
+            imp.items.push(parse_quote! {
+                fn top_down_update(
+                    &mut self,
+                    ctx: &mut Self::Context<'_>,
+                    input: &Self::Input,
+                ) -> impl ::core::convert::Into<::moku::Next<Self::State>> {
+                    #bindings
+                    #update;
+                }
+            });
+        }
+    }
+
+    // the submachine is shut down before the state exits
+    match find_fn(imp, "exit") {
+        Some(fun) => {
+            if let Some(FnArg::Receiver(receiver)) = fun.sig.inputs.first_mut() {
+                receiver.mutability = Some(Default::default());
+            }
+            let bindings = args.bindings(&param(fun, 1, "ctx")?, None);
+            let shutdown = args.call("shutdown_with", quote! { __resources });
+            fun.block
+                .stmts
+                .insert(0, parse_quote! {{ #bindings #shutdown; }});
+        }
+        None => {
+            let bindings = args.bindings(&parse_quote!(ctx), None);
+            let shutdown = args.call("shutdown_with", quote! { __resources });
+            imp.items.push(parse_quote! {
+                fn exit(
+                    mut self,
+                    ctx: &mut Self::Context<'_>,
+                ) -> impl ::core::convert::Into<::moku::Next<Self::State>> {
+                    #bindings
+                    #shutdown;
+                }
+            });
+        }
+    }
+
+    // events that the state defers are forwarded to the submachine
+    let field = &args.field;
+    match find_fn(imp, "handle_event") {
+        Some(fun) => {
+            let bindings = args.bindings(&param(fun, 1, "ctx")?, None);
+            let event = param(fun, 2, "event")?;
+            let block = &fun.block;
+            fun.block = parse_quote! {{
+                #[allow(clippy::redundant_closure_call)]
+                let response: ::moku::Response<Self::State> = (|| #block)().into();
+                match response {
+                    ::moku::Response::Next(::moku::Next::None) => {
+                        #bindings
+                        ::moku::internal::forward_event(&mut self.#field, __resources, #event)
+                    }
+                    response => response,
+                }
+            }};
+        }
+        None => {
+            let bindings = args.bindings(&parse_quote!(ctx), None);
+            imp.items.push(parse_quote! {
+                fn handle_event(
+                    &mut self,
+                    ctx: &mut Self::Context<'_>,
+                    event: &Self::Event,
+                ) -> impl ::core::convert::Into<::moku::Response<Self::State>> {
+                    #bindings
+                    let response: ::moku::Response<Self::State> =
+                        ::moku::internal::forward_event(&mut self.#field, __resources, event);
+                    response
+                }
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Find a function of an impl by name.
+fn find_fn<'a>(imp: &'a mut ItemImpl, name: &str) -> Option<&'a mut ImplItemFn> {
+    imp.items.iter_mut().find_map(|item| match item {
+        ImplItem::Fn(fun) if fun.sig.ident == name => Some(fun),
+        _ => None,
+    })
+}
+
+/// Get the name of a parameter of a hook, which must be named for it to be forwarded.
+fn param(fun: &ImplItemFn, index: usize, kind: &str) -> Result<Ident, syn::Error> {
+    match fun.sig.inputs.iter().nth(index) {
+        Some(FnArg::Typed(arg)) => match arg.pat.as_ref() {
+            Pat::Ident(pat) => Ok(pat.ident.clone()),
+            pat => Err(syn::Error::new(
+                pat.span(),
+                format!(
+                    "the `{kind}` parameter of `{}` must be named to be forwarded to a \
+                    `moku::submachine`",
+                    fun.sig.ident
+                ),
+            )),
+        },
+        _ => Err(syn::Error::new(
+            fun.sig.span(),
+            format!(
+                "`{}` must have a `{kind}` parameter to be forwarded to a `moku::submachine`",
+                fun.sig.ident
+            ),
+        )),
+    }
+}
@@ -0,0 +1,1022 @@
+use std::collections::{HashMap, HashSet};
+
+use convert_case::{Case, Casing};
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    spanned::Spanned, Attribute, GenericArgument, GenericParam, ImplItem, Item, ItemImpl, ItemMod,
+    ItemStruct, Meta, PathArguments, Type, TypePath,
+};
+
+use crate::{
+    handlers::expand_handlers,
+    metadata::{MachineGenerics, Metadata, State},
+    submachine::expand_submachine,
+    util::{
+        add_input_params, cfg_predicates, doc_string, expose_item, filter_attributes, find_mention,
+        param_name, path_matches, prepend_params, push_unique, scope, take_description, take_tags,
+        take_transitions, type_ident, type_name,
+    },
+};
+
+/// Collect and validate Metadata about the structure of a `state_machine` module and the usage of attributes.
+pub fn build_metadata(name: Ident, module: ItemMod) -> Result<Metadata, syn::Error> {
+    let mut unpacker = Unpacker::new(name, module);
+    unpacker.unpack()?;
+    unpacker.expose_states();
+    unpacker.check_state_defs();
+    unpacker.validate_associated_types()?;
+    unpacker.validate_state_names()?;
+    unpacker.validate_enter_defs()?;
+    let top_state = unpacker.get_top_state()?;
+    unpacker.validate_superstates(top_state)?;
+    unpacker.validate_transitions(top_state)?;
+    unpacker.build_metadata()
+}
+
+struct UnpackedState {
+    ident: Ident,
+    /// The path of this state from the `state_machine` module, e.g. `foo::Bar<H>`.
+    ty: TokenStream,
+    /// The submodules of the `state_machine` module that contain this state.
+    path: Vec<Ident>,
+    /// The predicates of the `cfg` attributes of this state, its impl, and its submodules.
+    cfgs: Vec<TokenStream>,
+    description: Option<String>,
+    tags: Vec<Ident>,
+    /// The states that this state may transition to, if declared with `moku::transitions`.
+    transitions: Option<Vec<Ident>>,
+    superstate: Ident,
+    imp: ItemImpl,
+    superstate_span: Span,
+    event: Option<TokenStream>,
+    def_found: bool,
+    has_fields: bool,
+    autogen_enter: bool,
+}
+
+/// A struct defined in the `state_machine` module or one of its submodules.
+struct StructDef {
+    has_fields: bool,
+    /// The predicates of the `cfg` attributes of the struct and its submodules.
+    cfgs: Vec<TokenStream>,
+    /// The `moku::describe` attribute or doc comment of the struct.
+    description: Option<String>,
+    tags: Vec<Ident>,
+}
+
+struct Unpacker {
+    name: Ident,
+    main_mod: ItemMod,
+    machine_mod: Option<ItemMod>,
+    events: Vec<Ident>,
+    top_state: Option<Ident>,
+    top_state_ty: Option<TokenStream>,
+    top_state_impl: Option<ItemImpl>,
+    top_state_description: Option<String>,
+    top_state_tags: Vec<Ident>,
+    generics: MachineGenerics,
+    states: Vec<UnpackedState>,
+    state_idents: HashSet<Ident>,
+    structs: HashMap<Ident, StructDef>,
+    /// The predicates of the `cfg` attributes of the submodules being unpacked.
+    module_cfgs: Vec<TokenStream>,
+    handler_impls: Vec<ItemImpl>,
+    error: Option<syn::Error>,
+}
+
+impl Unpacker {
+    fn new(name: Ident, main_mod: ItemMod) -> Self {
+        Self {
+            name,
+            main_mod,
+            machine_mod: None,
+            events: Vec::new(),
+            top_state: None,
+            top_state_ty: None,
+            top_state_impl: None,
+            top_state_description: None,
+            top_state_tags: Vec::new(),
+            generics: MachineGenerics::default(),
+            states: Vec::new(),
+            state_idents: HashSet::new(),
+            structs: HashMap::new(),
+            module_cfgs: Vec::new(),
+            handler_impls: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Build Metadata from the info collected by an Unpacker.
+    fn build_metadata(mut self) -> Result<Metadata, syn::Error> {
+        let machine_mod = self.take_machine_mod()?;
+        let (event, event_local, events) = self.take_events(&machine_mod.ident)?;
+        let mut top_state: State = self.get_top_state()?.into();
+        if let Some(ty) = self.top_state_ty.take() {
+            top_state.set_ty(ty);
+        }
+
+        // the attributes of the impl take precedence over those of the struct
+        let mut description = self.top_state_description.take();
+        let mut tags = Vec::new();
+        if let Some(def) = self.structs.remove(top_state.ident()) {
+            description = description.or(def.description);
+            tags = def.tags;
+        }
+        for tag in self.top_state_tags.drain(..) {
+            push_unique(&mut tags, tag);
+        }
+        top_state.set_description(description);
+        top_state.set_tags(tags);
+
+        let mut metadata = Metadata {
+            event,
+            event_local,
+            events,
+            top_state,
+            generics: self.generics,
+            top_state_impl: self.top_state_impl.take(),
+            machine_mod,
+            name: self.name,
+            states: HashMap::new(),
+            main_mod: self.main_mod,
+        };
+
+        let relations: Vec<_> = self
+            .states
+            .into_iter()
+            .map(|state| {
+                let added = metadata.add_state(
+                    &state.ident,
+                    state.autogen_enter,
+                    state.ty,
+                    state.path,
+                    state.imp,
+                    state.event,
+                );
+                added.set_cfgs(state.cfgs);
+                added.set_description(state.description);
+                added.set_tags(state.tags);
+                added.set_transitions(state.transitions);
+                (state.ident, state.superstate)
+            })
+            .collect();
+
+        for (state, superstate) in relations {
+            metadata.add_relation(&superstate, &state)?;
+        }
+
+        Ok(metadata)
+    }
+
+    /// Unpack each item in the state_machine module's content.
+    fn unpack(&mut self) -> Result<(), syn::Error> {
+        if let Some(mut content) = self.main_mod.content.take() {
+            if !content.1.is_empty() {
+                let items: Vec<_> = content.1.drain(..).collect();
+
+                for item in &items {
+                    // first pass to check for StateMachineEvent type and TopState generics
+                    match item {
+                        Item::Impl(imp) => {
+                            self.find_event(imp);
+                            self.find_generics(imp);
+                        }
+                        Item::Enum(def) => self.find_derived_event(&def.ident, &def.attrs),
+                        Item::Struct(def) => self.find_derived_event(&def.ident, &def.attrs),
+                        _ => (),
+                    }
+
+                    // stop if we encounter an issue
+                    if let Some(error) = self.error.take() {
+                        return Err(error);
+                    }
+                }
+
+                content.1 = self.unpack_items(items, &[]);
+
+                // stop if we encounter an issue
+                if let Some(error) = self.error.take() {
+                    return Err(error);
+                }
+
+                self.main_mod.content = Some(content);
+                return Ok(());
+            }
+        }
+
+        // fallthrough error for above validation
+        let msg = format!(
+            "a `moku::state_machine` module must be inline with its attribute, try
+```
+#[moku::state_machine]
+mod {} {{
+    ...
+}}
+```",
+            self.main_mod.ident
+        );
+        Err(syn::Error::new(self.main_mod.span(), msg))
+    }
+
+    /// Unpack the items of the `state_machine` module or one of its submodules, returning the
+    /// items to keep in the module.
+    ///
+    /// Stops early if an error is encountered.
+    fn unpack_items(&mut self, items: Vec<Item>, path: &[Ident]) -> Vec<Item> {
+        let handler_impls = std::mem::take(&mut self.handler_impls);
+        let mut kept = Vec::new();
+
+        for item in items {
+            if let Some(item) = match item {
+                Item::Struct(def) => self.unpack_struct(def),
+                Item::Mod(module) => self.unpack_mod(module, path),
+                Item::Impl(imp) => self.unpack_impl(imp, path),
+                _ => Some(item),
+            } {
+                // restore the items that we won't need to touch
+                kept.push(item);
+            }
+
+            if self.error.is_some() {
+                break;
+            }
+        }
+
+        // restore the impls of handlers moved out of state impls
+        let handler_impls = std::mem::replace(&mut self.handler_impls, handler_impls);
+        kept.extend(handler_impls.into_iter().map(Item::Impl));
+        kept
+    }
+
+    /// Make the states defined in submodules visible to the machine module.
+    fn expose_states(&mut self) {
+        let items = &mut self
+            .main_mod
+            .content
+            .as_mut()
+            .expect("expose_states: no content in module")
+            .1;
+
+        for state in &self.states {
+            if !state.path.is_empty() {
+                expose_item(items, &state.path, &state.ident);
+            }
+        }
+    }
+
+    /// Check each found State struct definition with its struct definition.
+    fn check_state_defs(&mut self) {
+        for state in &mut self.states {
+            match self.structs.remove(&state.ident) {
+                Some(def) => {
+                    state.def_found = true;
+                    state.has_fields = def.has_fields;
+
+                    // the attributes of the impl take precedence over those of the struct
+                    state.description = state.description.take().or(def.description);
+                    let impl_tags = std::mem::replace(&mut state.tags, def.tags);
+                    for tag in impl_tags {
+                        push_unique(&mut state.tags, tag);
+                    }
+
+                    for cfg in def.cfgs {
+                        if !state
+                            .cfgs
+                            .iter()
+                            .any(|other| other.to_string() == cfg.to_string())
+                        {
+                            state.cfgs.push(cfg);
+                        }
+                    }
+                }
+                None => {
+                    state.def_found = false;
+                }
+            };
+        }
+    }
+
+    /// Validate that associated types are not manually defined for any Substate.
+    fn validate_associated_types(&self) -> Result<(), syn::Error> {
+        for state in &self.states {
+            for item in &state.imp.items {
+                if let ImplItem::Type(ty) = item {
+                    if ty.ident == "Context" || ty.ident == "State" || ty.ident == "Input" {
+                        return Err(syn::Error::new(
+                            ty.span(),
+                            format!(
+                                "the `Substate::{}` associated type must not be manually defined",
+                                ty.ident
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+
+        // Also check TopState impl
+        if let Some(ref imp) = self.top_state_impl {
+            for item in &imp.items {
+                if let ImplItem::Type(ty) = item {
+                    if ty.ident == "State" || ty.ident == "Event" {
+                        return Err(syn::Error::new(
+                            ty.span(),
+                            format!(
+                                "the `TopState::{}` associated type must not be manually defined",
+                                ty.ident
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate that no state name conflicts with the `resources` or `outputs` fields of the
+    /// generated contexts.
+    fn validate_state_names(&self) -> Result<(), syn::Error> {
+        let idents = self
+            .states
+            .iter()
+            .map(|state| &state.ident)
+            .chain(self.top_state.as_ref());
+        for ident in idents {
+            let field = ident.to_string().to_case(Case::Snake);
+            if field == "resources" || (field == "outputs" && cfg!(feature = "std")) {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    format!(
+                        "state name `{ident}` conflicts with the `{field}` field of the \
+                        generated contexts, try another name"
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate that Substate::enter is manually defined or can be autogenerated for each Substate.
+    fn validate_enter_defs(&mut self) -> Result<(), syn::Error> {
+        for state in &mut self.states {
+            let has_enter = state.imp.items.iter().any(|item| match item {
+                ImplItem::Fn(fun) => fun.sig.ident == "enter",
+                _ => false,
+            });
+
+            if has_enter {
+                state.autogen_enter = false;
+            } else if state.def_found {
+                if state.has_fields {
+                    return Err(syn::Error::new(
+                        state.imp.trait_.as_ref().unwrap().1.span(),
+                        "a struct with fields must manually implement the `Substate::enter` function",
+                    ));
+                } else {
+                    state.autogen_enter = true;
+                }
+            } else {
+                return Err(syn::Error::new(
+                    state.imp.trait_.as_ref().unwrap().1.span(),
+                    "a struct that is not defined in this module must manually implement the `Substate::enter` function",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Take the machine_module if found.
+    fn take_machine_mod(&mut self) -> Result<ItemMod, syn::Error> {
+        match self.machine_mod.take() {
+            Some(machine_mod) => Ok(machine_mod),
+            None => Err(syn::Error::new(
+                self.main_mod.span(),
+                "no `moku::machine_module` was defined in this module",
+            )),
+        }
+    }
+
+    /// Take the StateMachineEvent paths if found, else ().
+    ///
+    /// Returns (event_from_machine, event_local, events) tuple, where events is the list of event
+    /// types to be combined into a generated `Event` enum if more than one was found.
+    fn take_events(
+        &mut self,
+        machine_mod: &Ident,
+    ) -> Result<(TokenStream, TokenStream, Vec<Ident>), syn::Error> {
+        let mut events = std::mem::take(&mut self.events);
+        match events.len() {
+            0 => Ok((quote! { () }, quote! { () }, events)),
+            1 => {
+                let ident = events.pop().unwrap();
+                Ok((quote! { super::#ident }, quote! { #ident }, events))
+            }
+            _ => {
+                // each event type gets a `handle_{type}` method on the machine
+                if let Some(ident) = events
+                    .iter()
+                    .find(|ident| ident.to_string().to_case(Case::Snake) == "event")
+                {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "when multiple `moku::StateMachineEvent`s are defined, none may be named \
+                        `Event`, as its `handle_event` method would conflict with \
+                        `moku::StateMachine::handle_event`",
+                    ));
+                }
+
+                Ok((quote! { Event }, quote! { #machine_mod::Event }, events))
+            }
+        }
+    }
+
+    /// Get the Ident of the TopState if found.
+    fn get_top_state(&self) -> Result<&Ident, syn::Error> {
+        match &self.top_state {
+            Some(state) => Ok(state),
+            None => Err(syn::Error::new(
+                self.main_mod.span(),
+                "no `moku::TopState` was defined in this module",
+            )),
+        }
+    }
+
+    /// Validate that each Substate's superstate is another Substate or the TopState.
+    fn validate_superstates(&self, top_state: &Ident) -> Result<(), syn::Error> {
+        // validate that each superstate is another State or TopState
+        for state in &self.states {
+            let valid = state.superstate != state.ident
+                && (state.superstate == *top_state
+                    || self.state_idents.contains(&state.superstate));
+            if !valid {
+                return Err(syn::Error::new(
+                    state.superstate_span,
+                    format!(
+                        "superstate `{}` doesn't match any other known `moku::Substate` or `moku::TopState`",
+                        state.superstate
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate that each state declared with `moku::transitions` is a known state.
+    fn validate_transitions(&self, top_state: &Ident) -> Result<(), syn::Error> {
+        for target in self
+            .states
+            .iter()
+            .flat_map(|state| state.transitions.iter().flatten())
+        {
+            if target != top_state && !self.state_idents.contains(target) {
+                return Err(syn::Error::new(
+                    target.span(),
+                    format!(
+                        "transition target `{target}` doesn't match any known `moku::Substate` or \
+                        `moku::TopState`"
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Unpack an implementation of the `TopState` trait.
+    fn unpack_top_state(&mut self, imp: ItemImpl) {
+        if self.top_state.is_some() {
+            self.error = Some(syn::Error::new(
+                imp.span(),
+                "multiple `moku::TopState`s are defined within this module",
+            ));
+            return;
+        }
+
+        match type_ident(&imp.self_ty) {
+            Some(ident) => {
+                let mut imp = imp;
+                let (description, tags) = match self.take_state_attributes(&mut imp.attrs) {
+                    Some(taken) => taken,
+                    None => return,
+                };
+                self.top_state_description = description;
+                self.top_state_tags = tags;
+
+                if let Some(attr) = filter_attributes(&imp.attrs, "transitions").first() {
+                    self.error = Some(syn::Error::new(
+                        attr.span(),
+                        "`moku::transitions` can't be used on the `moku::TopState`, which may \
+                        transition to any state",
+                    ));
+                    return;
+                }
+
+                if let Some(attr) = filter_attributes(&imp.attrs, "submachine").first() {
+                    self.error = Some(syn::Error::new(
+                        attr.span(),
+                        "`moku::submachine` can't be used on the `moku::TopState`, which is never \
+                        entered or exited",
+                    ));
+                    return;
+                }
+
+                if !self.expand_handlers(&mut imp, true) {
+                    return;
+                }
+                add_input_params(&mut imp, 1);
+
+                self.top_state = Some(ident);
+                self.top_state_impl = Some(imp);
+            }
+            None => {
+                self.error = Some(syn::Error::new(
+                    imp.self_ty.span(),
+                    "`moku::TopState` must be implemented on a plain struct \
+                    \nYou may also use a type alias: `type MyTopState = Option<bool>;`",
+                ));
+            }
+        }
+    }
+
+    /// Check if an implementation is of the `TopState` trait and store its generic parameters if
+    /// applicable, so that they can be carried through to each `Substate`.
+    fn find_generics(&mut self, imp: &ItemImpl) {
+        match &imp.trait_ {
+            Some(tr) if path_matches(&tr.1, "TopState") => (),
+            _ => return,
+        }
+
+        // multiple TopStates are reported by unpack_top_state
+        if self.top_state_ty.is_some() {
+            return;
+        }
+        self.top_state_ty = Some(imp.self_ty.to_token_stream());
+
+        let mut generics = MachineGenerics::default();
+        for param in &imp.generics.params {
+            match param {
+                GenericParam::Type(param) => {
+                    let ident = &param.ident;
+                    generics.alias_params.push(quote! { #ident });
+                    generics.args.push(quote! { #ident });
+                }
+                GenericParam::Const(param) => {
+                    let ident = &param.ident;
+                    let ty = &param.ty;
+                    generics.alias_params.push(quote! { const #ident: #ty });
+                    generics.args.push(quote! { #ident });
+                }
+                GenericParam::Lifetime(param) => {
+                    let lifetime = &param.lifetime;
+                    generics.alias_params.push(quote! { #lifetime });
+                    generics.args.push(quote! { #lifetime });
+                }
+            }
+            generics.params.push(param.to_token_stream());
+        }
+
+        if let Some(where_clause) = &imp.generics.where_clause {
+            generics.predicates = where_clause
+                .predicates
+                .iter()
+                .map(ToTokens::to_token_stream)
+                .collect();
+        }
+
+        self.generics = generics;
+    }
+
+    /// Add the generic parameters of the `TopState` to a `Substate` impl, and name the `TopState`
+    /// in its trait, e.g. `impl<H: Hal> Substate<Foo, Top<H>> for Bar`.
+    ///
+    /// Generic parameters already declared by the impl are left as they are.
+    fn add_generics(&self, imp: &mut ItemImpl, path: &[Ident]) -> Result<(), syn::Error> {
+        if self.generics.is_empty() {
+            return Ok(());
+        }
+
+        let declared: Vec<_> = imp.generics.params.iter().map(param_name).collect();
+
+        let mut params = Vec::new();
+        for (param, arg) in self.generics.params.iter().zip(&self.generics.args) {
+            if !declared.contains(&arg.to_string()) {
+                params.push(syn::parse2(param.clone())?);
+            }
+        }
+        prepend_params(&mut imp.generics, params);
+
+        for predicate in &self.generics.predicates {
+            imp.generics
+                .make_where_clause()
+                .predicates
+                .push(syn::parse2(predicate.clone())?);
+        }
+
+        let scope = scope(path.len());
+        let top_state = self.top_state_ty.clone().unwrap_or_default();
+        let top_state = quote! { #scope #top_state };
+        let trait_path = &mut imp.trait_.as_mut().unwrap().1;
+        if let PathArguments::AngleBracketed(args) =
+            &mut trait_path.segments.last_mut().unwrap().arguments
+        {
+            args.args.push(syn::parse2(top_state)?);
+        }
+
+        Ok(())
+    }
+
+    /// Check if an implementation is of the `StateMachineEvent` trait and store the target type if
+    /// applicable.
+    fn find_event(&mut self, imp: &ItemImpl) {
+        if let Some(tr) = &imp.trait_ {
+            if !path_matches(&tr.1, "StateMachineEvent") {
+                return;
+            }
+        } else {
+            return;
+        }
+
+        let ident = match imp.self_ty.as_ref() {
+            Type::Path(TypePath { path, .. }) => path.get_ident().cloned(),
+            _ => None,
+        };
+
+        match ident {
+            Some(ident) => self.add_event(ident),
+            None => {
+                self.error = Some(syn::Error::new(
+                    imp.self_ty.span(),
+                    "`moku::StateMachineEvent` must be implemented on a plain enum or struct. \
+                    \nYou may also use a type alias: `type Event = Option<bool>;`",
+                ));
+            }
+        }
+    }
+
+    /// Check if an enum or struct derives the `StateMachineEvent` trait and store it if
+    /// applicable.
+    fn find_derived_event(&mut self, ident: &Ident, attrs: &[Attribute]) {
+        for attr in attrs {
+            if !attr.path().is_ident("derive") {
+                continue;
+            }
+
+            let mut derived = false;
+            let result = attr.parse_nested_meta(|meta| {
+                derived |= path_matches(&meta.path, "StateMachineEvent");
+                Ok(())
+            });
+
+            if result.is_ok() && derived {
+                self.add_event(ident.clone());
+                return;
+            }
+        }
+    }
+
+    /// Store an event type if it hasn't been found already.
+    fn add_event(&mut self, ident: Ident) {
+        if !self.events.contains(&ident) {
+            self.events.push(ident);
+        }
+    }
+
+    /// Unpack an implementation of the `Substate` trait found in the submodule at the given path.
+    fn unpack_substate(&mut self, imp: ItemImpl, path: &[Ident]) {
+        // states may only name the generic parameters of the TopState
+        for param in &imp.generics.params {
+            let name = param_name(param);
+            if !self.generics.args.iter().any(|arg| arg.to_string() == name) {
+                self.error = Some(syn::Error::new(
+                    param.span(),
+                    format!(
+                        "generic parameter `{name}` is not a generic parameter of the `moku::TopState`"
+                    ),
+                ));
+                return;
+            }
+        }
+
+        // impls that name the generic parameters of the TopState must declare them
+        let declared: Vec<_> = imp.generics.params.iter().map(param_name).collect();
+        let mut named = imp.trait_.as_ref().unwrap().1.to_token_stream();
+        named.extend(imp.self_ty.to_token_stream());
+        for arg in &self.generics.args {
+            let name = arg.to_string();
+            if declared.contains(&name) {
+                continue;
+            }
+            if let Some(span) = find_mention(named.clone(), &name) {
+                self.error = Some(syn::Error::new(
+                    span,
+                    format!(
+                        "generic parameter `{name}` of the `moku::TopState` must be declared by \
+                        this impl"
+                    ),
+                ));
+                return;
+            }
+        }
+
+        let ident = match type_ident(&imp.self_ty) {
+            Some(ident) => ident,
+            None => {
+                self.error = Some(syn::Error::new(
+                    imp.self_ty.span(),
+                    "`moku::Substate` must be implemented on a plain struct \
+                    \nYou may also use a type alias: `type MyState = Option<bool>;`",
+                ));
+                return;
+            }
+        };
+
+        // Extract the superstate from the trait generic parameter: Substate<Parent>
+        let trait_path = &imp.trait_.as_ref().unwrap().1;
+        let last_segment = trait_path.segments.last().unwrap();
+
+        let superstate = match &last_segment.arguments {
+            PathArguments::AngleBracketed(args) => {
+                if args.args.len() != 1 {
+                    self.error = Some(syn::Error::new(
+                        args.span(),
+                        "`moku::Substate` requires exactly one type parameter for the superstate",
+                    ));
+                    return;
+                }
+                match args.args.first().unwrap() {
+                    // states in submodules may name their superstate by path
+                    GenericArgument::Type(ty) => type_name(ty),
+                    _ => None,
+                }
+            }
+            PathArguments::None => {
+                self.error = Some(syn::Error::new(
+                    last_segment.span(),
+                    "`moku::Substate` requires a superstate type parameter, e.g. `impl Substate<Top> for Foo`",
+                ));
+                return;
+            }
+            PathArguments::Parenthesized(_) => {
+                self.error = Some(syn::Error::new(
+                    last_segment.span(),
+                    "unexpected parenthesized arguments on `moku::Substate`",
+                ));
+                return;
+            }
+        };
+
+        let superstate = match superstate {
+            Some(superstate) => superstate,
+            None => {
+                self.error = Some(syn::Error::new(
+                    last_segment.span(),
+                    "the `moku::Substate` superstate must be only a single state name, e.g. `impl Substate<Top> for Foo`",
+                ));
+                return;
+            }
+        };
+
+        let superstate_span = match &last_segment.arguments {
+            PathArguments::AngleBracketed(args) => args.span(),
+            _ => last_segment.span(),
+        };
+
+        // Check for duplicate Substate impls
+        if !self.state_idents.insert(ident.clone()) {
+            self.error = Some(syn::Error::new(
+                imp.self_ty.span(),
+                format!(
+                    "multiple `Substate` impls found for `{}`; each state must only implement Substate once",
+                    ident
+                ),
+            ));
+            return;
+        }
+
+        let mut imp = imp;
+        if let Err(error) = self.add_generics(&mut imp, path) {
+            self.error = Some(error);
+            return;
+        }
+
+        let (description, tags) = match self.take_state_attributes(&mut imp.attrs) {
+            Some(taken) => taken,
+            None => return,
+        };
+        let transitions = match take_transitions(&mut imp.attrs) {
+            Ok(transitions) => transitions,
+            Err(error) => {
+                self.error = Some(error);
+                return;
+            }
+        };
+
+        if !self.expand_handlers(&mut imp, false) {
+            return;
+        }
+        add_input_params(&mut imp, 2);
+        if let Err(error) = expand_submachine(&mut imp) {
+            self.error = Some(error);
+            return;
+        }
+
+        // a narrower event type for this state and its descendents
+        let mut event = None;
+        imp.items.retain(|item| match item {
+            ImplItem::Type(ty) if ty.ident == "Event" => {
+                event = Some(ty.ty.to_token_stream());
+                false
+            }
+            _ => true,
+        });
+
+        let self_ty = &imp.self_ty;
+        let mut cfgs = self.module_cfgs.clone();
+        cfgs.extend(cfg_predicates(&imp.attrs));
+        self.states.push(UnpackedState {
+            ident,
+            ty: quote! { #(#path::)* #self_ty },
+            path: path.to_vec(),
+            cfgs,
+            description,
+            tags,
+            transitions,
+            superstate,
+            superstate_span,
+            event,
+            imp,
+            def_found: false,
+            has_fields: false,
+            autogen_enter: false,
+        });
+    }
+
+    /// Take the `moku::describe` and `moku::tag` attributes of a state, storing any error.
+    fn take_state_attributes(
+        &mut self,
+        attrs: &mut Vec<Attribute>,
+    ) -> Option<(Option<String>, Vec<Ident>)> {
+        match take_description(attrs).and_then(|description| Ok((description, take_tags(attrs)?))) {
+            Ok(taken) => Some(taken),
+            Err(error) => {
+                self.error = Some(error);
+                None
+            }
+        }
+    }
+
+    /// Expand the `moku::on` handlers of a state impl, storing any error.
+    ///
+    /// Returns false if an error was found.
+    fn expand_handlers(&mut self, imp: &mut ItemImpl, is_top_state: bool) -> bool {
+        match expand_handlers(imp, is_top_state) {
+            Ok(handler_impl) => {
+                self.handler_impls.extend(handler_impl);
+                true
+            }
+            Err(error) => {
+                self.error = Some(error);
+                false
+            }
+        }
+    }
+
+    fn unpack_struct(&mut self, mut def: ItemStruct) -> Option<Item> {
+        let (description, tags) = self.take_state_attributes(&mut def.attrs)?;
+
+        // track what structs have no fields for Substate::enter autogen info
+        let mut cfgs = self.module_cfgs.clone();
+        cfgs.extend(cfg_predicates(&def.attrs));
+        self.structs.insert(
+            def.ident.clone(),
+            StructDef {
+                has_fields: !def.fields.is_empty(),
+                cfgs,
+                description: description.or_else(|| doc_string(&def.attrs)),
+                tags,
+            },
+        );
+
+        Some(Item::Struct(def))
+    }
+
+    fn unpack_mod(&mut self, module: ItemMod, path: &[Ident]) -> Option<Item> {
+        let mut attrs = filter_attributes(&module.attrs, "machine_module");
+
+        match attrs.len() {
+            0 => {
+                // look for states in modules without our attribute
+                return self.unpack_submodule(module, path);
+            }
+            1 if !path.is_empty() => {
+                self.error = Some(syn::Error::new(
+                    module.span(),
+                    "a `moku::machine_module` must be defined directly within the \
+                    `moku::state_machine` module",
+                ));
+                return None;
+            }
+            1 => (),
+            _ => {
+                self.error = Some(syn::Error::new(
+                    module.span(),
+                    "multiple `moku::machine_module` attributes defined for this module",
+                ));
+                return None;
+            }
+        }
+
+        let attr = attrs.pop().unwrap();
+
+        // validate attribute arguments
+        match attr.meta {
+            Meta::Path(_) => (),
+            _ => {
+                self.error = Some(syn::Error::new(
+                    attr.span(),
+                    "`moku::machine_module` accepts no arguments, try `#[moku::machine_module]`",
+                ));
+                return None;
+            }
+        }
+
+        // validate single attribute definition in module
+        if self.machine_mod.is_some() {
+            self.error = Some(syn::Error::new(
+                module.span(),
+                "multiple `moku::machine_module`s are defined within this module",
+            ));
+            return None;
+        }
+
+        // validate this module has some inline content
+        if let Some(content) = &module.content {
+            if content.1.is_empty() {
+                // all is good
+                self.machine_mod = Some(module);
+                return None;
+            }
+        }
+
+        // fallthrough error for above validation
+        let msg = format!(
+            "a `moku::machine_module` must have empty braces, try `mod {} {{}}`",
+            module.ident
+        );
+        self.error = Some(syn::Error::new(module.span(), msg));
+
+        None
+    }
+
+    /// Unpack the states of an inline submodule of the `state_machine` module, found in the
+    /// submodule at the given path.
+    fn unpack_submodule(&mut self, mut module: ItemMod, path: &[Ident]) -> Option<Item> {
+        // leave other state machines alone
+        if !filter_attributes(&module.attrs, "state_machine").is_empty() {
+            return Some(Item::Mod(module));
+        }
+
+        if let Some((_, items)) = &mut module.content {
+            let path: Vec<_> = path.iter().chain([&module.ident]).cloned().collect();
+            let num_cfgs = self.module_cfgs.len();
+            self.module_cfgs.extend(cfg_predicates(&module.attrs));
+            *items = self.unpack_items(std::mem::take(items), &path);
+            self.module_cfgs.truncate(num_cfgs);
+        }
+
+        Some(Item::Mod(module))
+    }
+
+    fn unpack_impl(&mut self, imp: ItemImpl, path: &[Ident]) -> Option<Item> {
+        let tr = match &imp.trait_ {
+            None => return Some(Item::Impl(imp)),
+            Some(tr) => &tr.1,
+        };
+
+        if path_matches(tr, "TopState") {
+            if !path.is_empty() {
+                self.error = Some(syn::Error::new(
+                    imp.span(),
+                    "the `moku::TopState` must be implemented directly within the \
+                    `moku::state_machine` module",
+                ));
+                return None;
+            }
+
+            // TopState now has no generic parameters
+            self.unpack_top_state(imp);
+            None
+        } else if path_matches(tr, "Substate") {
+            // Substate<Parent> - superstate is in the generic parameter
+            self.unpack_substate(imp, path);
+            None
+        } else {
+            Some(Item::Impl(imp))
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use convert_case::{Case, Casing};
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+    parse_quote, punctuated::Punctuated, spanned::Spanned, Attribute, Expr, ExprLit, GenericParam,
+    Generics, Ident, ImplItem, Item, ItemImpl, ItemMod, Lit, LitStr, Meta, MetaNameValue, Path,
+    Token, Type, TypePath, Visibility,
+};
+
+/// Derive the default state machine name from a `state_machine` module's name.
+pub fn default_name(module: &ItemMod) -> Ident {
+    Ident::new(
+        &module.ident.to_string().to_case(Case::UpperCamel),
+        Span::call_site(),
+    )
+}
+
+/// Get the Ident of a type that is a single path segment, ignoring any generic arguments, e.g.
+/// `Top` for `Top<H>`.
+pub fn type_ident(ty: &Type) -> Option<Ident> {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) if path.leading_colon.is_none() => {
+            match path.segments.len() {
+                1 => Some(path.segments[0].ident.clone()),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Get the Ident of the last segment of a type that is a path, ignoring any generic arguments,
+/// e.g. `Bar` for `super::foo::Bar<H>`.
+pub fn type_name(ty: &Type) -> Option<Ident> {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => {
+            path.segments.last().map(|seg| seg.ident.clone())
+        }
+        _ => None,
+    }
+}
+
+/// Get the prefix that names the `state_machine` module from a submodule at the given depth,
+/// e.g. `super::super::` for a depth of 2.
+pub fn scope(depth: usize) -> TokenStream {
+    (0..depth).map(|_| quote! { super:: }).collect()
+}
+
+/// Get the contents of the submodule at the given path within a list of items.
+pub fn submodule_items<'a>(items: &'a mut Vec<Item>, path: &[Ident]) -> Option<&'a mut Vec<Item>> {
+    let Some((first, rest)) = path.split_first() else {
+        return Some(items);
+    };
+
+    items.iter_mut().find_map(|item| match item {
+        Item::Mod(module) if module.ident == *first => module
+            .content
+            .as_mut()
+            .and_then(|(_, items)| submodule_items(items, rest)),
+        _ => None,
+    })
+}
+
+/// Raise the inherited visibility of the struct or type alias named `ident` in the submodule at
+/// the given path, and of the submodules along the way, so that it may be named from the
+/// `state_machine` module and its machine module.
+pub fn expose_item(items: &mut [Item], path: &[Ident], ident: &Ident) {
+    expose_item_acc(items, path, ident, 0);
+}
+
+fn expose_item_acc(items: &mut [Item], path: &[Ident], ident: &Ident, depth: usize) {
+    let expose = |vis: &mut Visibility| {
+        if depth > 0 && matches!(vis, Visibility::Inherited) {
+            let supers = (0..depth).map(|_| quote! { super });
+            *vis = parse_quote! { pub(in #(#supers)::*) };
+        }
+    };
+
+    for item in items {
+        match (item, path.split_first()) {
+            (Item::Mod(module), Some((first, rest))) if module.ident == *first => {
+                expose(&mut module.vis);
+                if let Some((_, items)) = &mut module.content {
+                    expose_item_acc(items, rest, ident, depth + 1);
+                }
+            }
+            (Item::Struct(def), None) if def.ident == *ident => expose(&mut def.vis),
+            (Item::Type(def), None) if def.ident == *ident => expose(&mut def.vis),
+            _ => (),
+        }
+    }
+}
+
+/// Get the name of a generic parameter as it would be used as an argument, e.g. `H` or `'a`.
+pub fn param_name(param: &GenericParam) -> String {
+    match param {
+        GenericParam::Type(param) => param.ident.to_string(),
+        GenericParam::Const(param) => param.ident.to_string(),
+        GenericParam::Lifetime(param) => param.lifetime.to_token_stream().to_string(),
+    }
+}
+
+/// Find the first mention of a generic parameter by name, e.g. `H` or `'a`, within some tokens.
+pub fn find_mention(tokens: TokenStream, name: &str) -> Option<Span> {
+    let mut lifetime = false;
+    for token in tokens {
+        let quote = matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '\'');
+        match token {
+            TokenTree::Group(group) => {
+                if let Some(span) = find_mention(group.stream(), name) {
+                    return Some(span);
+                }
+            }
+            TokenTree::Ident(ident) => {
+                let found = match lifetime {
+                    true => name.strip_prefix('\'') == Some(ident.to_string().as_str()),
+                    false => ident == name,
+                };
+                if found {
+                    return Some(ident.span());
+                }
+            }
+            _ => (),
+        }
+        lifetime = quote;
+    }
+    None
+}
+
+/// Add generic parameters before the existing parameters of the same kind, keeping lifetimes
+/// first.
+pub fn prepend_params(generics: &mut Generics, params: Vec<GenericParam>) {
+    let is_lifetime = |param: &GenericParam| matches!(param, GenericParam::Lifetime(_));
+    let existing: Vec<_> = std::mem::take(&mut generics.params).into_iter().collect();
+    let (new_lifetimes, new_others): (Vec<_>, Vec<_>) = params.into_iter().partition(is_lifetime);
+    let (lifetimes, others): (Vec<_>, Vec<_>) = existing.into_iter().partition(is_lifetime);
+
+    generics.params = new_lifetimes
+        .into_iter()
+        .chain(lifetimes)
+        .chain(new_others)
+        .chain(others)
+        .collect::<Punctuated<_, _>>();
+}
+
+/// Add the `input` parameter to the `update` and `top_down_update` hooks of a state impl that
+/// omit it, i.e. those that have only the given number of parameters.
+pub fn add_input_params(imp: &mut ItemImpl, num_params: usize) {
+    for item in &mut imp.items {
+        if let ImplItem::Fn(fun) = item {
+            let is_update = fun.sig.ident == "update" || fun.sig.ident == "top_down_update";
+            if is_update && fun.sig.inputs.len() == num_params {
+                fun.sig.inputs.push(parse_quote! { _input: &Self::Input });
+            }
+        }
+    }
+}
+
+/// Get the predicates of the `cfg` attributes in a list of attributes.
+pub fn cfg_predicates(attrs: &[Attribute]) -> Vec<TokenStream> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::List(list) => Some(list.tokens.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Check if the first segment of a Path matches `{name}` or `moku::{name}`.
+pub fn path_matches(path: &Path, name: &str) -> bool {
+    let seg = match path.segments.len() {
+        1 => path.segments.first().unwrap(),
+        2 => {
+            let first_seg = path.segments.first().unwrap();
+            if first_seg.ident != "moku" {
+                return false;
+            }
+
+            path.segments.last().unwrap()
+        }
+        _ => return false,
+    };
+
+    seg.ident == name
+}
+
+/// Take the attributes matching `{name}` or `moku::{name}` out of a list of attributes.
+pub fn take_attributes(attrs: &mut Vec<Attribute>, name: &str) -> Vec<Attribute> {
+    let (taken, kept) = std::mem::take(attrs)
+        .into_iter()
+        .partition(|attr| path_matches(attr.path(), name));
+    *attrs = kept;
+    taken
+}
+
+/// Take the description of a state out of its `moku::describe` attribute, if any.
+pub fn take_description(attrs: &mut Vec<Attribute>) -> Result<Option<String>, syn::Error> {
+    let mut taken = take_attributes(attrs, "describe");
+    if taken.len() > 1 {
+        return Err(syn::Error::new(
+            taken[1].span(),
+            "multiple `moku::describe` attributes defined for this state",
+        ));
+    }
+
+    taken
+        .pop()
+        .map(|attr| Ok(attr.parse_args::<LitStr>()?.value()))
+        .transpose()
+}
+
+/// Take the tags of a state out of its `moku::tag` attributes.
+pub fn take_tags(attrs: &mut Vec<Attribute>) -> Result<Vec<Ident>, syn::Error> {
+    let mut tags = Vec::new();
+    for attr in take_attributes(attrs, "tag") {
+        let idents =
+            attr.parse_args_with(Punctuated::<Ident, Token![,]>::parse_separated_nonempty)?;
+        tags.extend(idents);
+    }
+
+    Ok(tags)
+}
+
+/// Take the allowed transitions of a state out of its `moku::transitions` attribute, if any.
+pub fn take_transitions(attrs: &mut Vec<Attribute>) -> Result<Option<Vec<Ident>>, syn::Error> {
+    let taken = take_attributes(attrs, "transitions");
+    if taken.len() > 1 {
+        return Err(syn::Error::new(
+            taken[1].span(),
+            "multiple `moku::transitions` attributes defined for this state",
+        ));
+    }
+
+    let Some(attr) = taken.first() else {
+        return Ok(None);
+    };
+
+    let mut transitions = Vec::new();
+    for ident in attr.parse_args_with(Punctuated::<Ident, Token![,]>::parse_terminated)? {
+        push_unique(&mut transitions, ident);
+    }
+
+    Ok(Some(transitions))
+}
+
+/// Get the text of the doc comments in a list of attributes, if any.
+pub fn doc_string(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<_> = attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(MetaNameValue {
+                path,
+                value:
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(doc), ..
+                    }),
+                ..
+            }) if path.is_ident("doc") => Some(doc.value().trim().to_owned()),
+            _ => None,
+        })
+        .collect();
+
+    let doc = lines.join("\n").trim().to_owned();
+    (!doc.is_empty()).then_some(doc)
+}
+
+/// Add an Ident to a list if it isn't in the list already.
+pub fn push_unique(idents: &mut Vec<Ident>, ident: Ident) {
+    if !idents.contains(&ident) {
+        idents.push(ident);
+    }
+}
+
+/// Filter a list of attributes down to those matching `{name}` or `moku::{name}`.
+pub fn filter_attributes<'a>(attrs: &'a [Attribute], name: &str) -> Vec<&'a Attribute> {
+    attrs
+        .iter()
+        .filter(move |attr| {
+            let path = attr.meta.path();
+            let seg = match path.segments.len() {
+                1 => path.segments.first().unwrap(),
+                2 => {
+                    let first_seg = path.segments.first().unwrap();
+                    if first_seg.ident != "moku" {
+                        return false;
+                    }
+
+                    path.segments.last().unwrap()
+                }
+                _ => return false,
+            };
+
+            seg.ident == name
+        })
+        .collect()
+}
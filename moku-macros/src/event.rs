@@ -0,0 +1,43 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput};
+
+/// Generate an implementation of `StateMachineEvent` that names each variant of an enum, or the
+/// struct itself.
+pub fn derive_event(input: DeriveInput) -> Result<TokenStream, syn::Error> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let name = match &input.data {
+        Data::Enum(data) => {
+            let arms = data.variants.iter().map(|variant| {
+                let variant_ident = &variant.ident;
+                let name = variant_ident.to_string();
+                quote! { Self::#variant_ident { .. } => #name }
+            });
+            quote! {
+                match *self {
+                    #(#arms,)*
+                }
+            }
+        }
+        Data::Struct(_) => {
+            let name = ident.to_string();
+            quote! { #name }
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "`moku::StateMachineEvent` can only be derived for an enum or struct",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::moku::StateMachineEvent for #ident #ty_generics #where_clause {
+            fn name(&self) -> &str {
+                #name
+            }
+        }
+    })
+}
@@ -1,17 +1,13 @@
 #![allow(unused)]
 
-use convert_case::{Case, Casing};
+use moku_codegen::{build_metadata, default_name};
 use proc_macro::TokenStream;
-use proc_macro2::{Ident, Span};
+use proc_macro2::Ident;
 use quote::ToTokens;
-use syn::{parse, parse_macro_input, spanned::Spanned, ItemImpl, ItemMod};
-use unpacker::build_metadata;
+use syn::{parse, parse_macro_input, spanned::Spanned, DeriveInput, ItemImpl, ItemMod};
 
-use crate::util::path_matches;
-
-mod metadata;
-mod unpacker;
-mod util;
+mod event;
+mod scxml;
 
 /// Append a compile error to a TokenStream.
 ///
@@ -37,7 +33,7 @@ pub fn machine_module(_args: TokenStream, input: TokenStream) -> TokenStream {
 #[proc_macro_attribute]
 pub fn state_machine(args: TokenStream, input: TokenStream) -> TokenStream {
     // validate that this attribute is attached to a module
-    let main_mod = match parse::<ItemMod>(input.clone()) {
+    let mut main_mod = match parse::<ItemMod>(input.clone()) {
         Ok(main_mod) => main_mod,
         Err(error) => {
             return token_stream_with_error(input, error);
@@ -53,12 +49,12 @@ pub fn state_machine(args: TokenStream, input: TokenStream) -> TokenStream {
     //
     // This will stop rust-analyzer completions from working while the error persists.
 
+    if let Err(error) = scxml::expand_include(&mut main_mod) {
+        return error.into_compile_error().into();
+    }
+
     let name = if args.is_empty() {
-        // derive state machine name from module name by default
-        Ident::new(
-            &main_mod.ident.to_string().to_case(Case::UpperCamel),
-            Span::call_site(),
-        )
+        default_name(&main_mod)
     } else {
         parse_macro_input!(args as Ident)
     };
@@ -69,6 +65,105 @@ pub fn state_machine(args: TokenStream, input: TokenStream) -> TokenStream {
     }
 }
 
+#[proc_macro_attribute]
+pub fn on(_args: TokenStream, input: TokenStream) -> TokenStream {
+    // handlers are consumed by `state_machine`, so this is only reached if misplaced
+    token_stream_with_error(
+        input,
+        syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "`moku::on` must be used on a function in a `moku::Substate` or `moku::TopState` impl \
+            within a `moku::state_machine` module",
+        ),
+    )
+}
+
+#[proc_macro_attribute]
+pub fn unmatched(_args: TokenStream, input: TokenStream) -> TokenStream {
+    // consumed by `state_machine`, so this is only reached if misplaced
+    token_stream_with_error(
+        input,
+        syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "`moku::unmatched` must be used on a `moku::Substate` or `moku::TopState` impl \
+            within a `moku::state_machine` module",
+        ),
+    )
+}
+
+#[proc_macro_attribute]
+pub fn submachine(_args: TokenStream, input: TokenStream) -> TokenStream {
+    // consumed by `state_machine`, so this is only reached if misplaced
+    token_stream_with_error(
+        input,
+        syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "`moku::submachine` must be used on a `moku::Substate` impl within a \
+            `moku::state_machine` module",
+        ),
+    )
+}
+
+#[proc_macro_attribute]
+pub fn describe(_args: TokenStream, input: TokenStream) -> TokenStream {
+    // consumed by `state_machine`, so this is only reached if misplaced
+    token_stream_with_error(
+        input,
+        syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "`moku::describe` must be used on a state or its `moku::Substate` or `moku::TopState` \
+            impl within a `moku::state_machine` module",
+        ),
+    )
+}
+
+#[proc_macro_attribute]
+pub fn tag(_args: TokenStream, input: TokenStream) -> TokenStream {
+    // consumed by `state_machine`, so this is only reached if misplaced
+    token_stream_with_error(
+        input,
+        syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "`moku::tag` must be used on a state or its `moku::Substate` or `moku::TopState` \
+            impl within a `moku::state_machine` module",
+        ),
+    )
+}
+
+#[proc_macro_attribute]
+pub fn transitions(_args: TokenStream, input: TokenStream) -> TokenStream {
+    // consumed by `state_machine`, so this is only reached if misplaced
+    token_stream_with_error(
+        input,
+        syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "`moku::transitions` must be used on a `moku::Substate` impl within a \
+            `moku::state_machine` module",
+        ),
+    )
+}
+
+#[proc_macro]
+pub fn include_scxml(_input: TokenStream) -> TokenStream {
+    // consumed by `state_machine`, so this is only reached if misplaced
+    syn::Error::new(
+        proc_macro2::Span::call_site(),
+        "`moku::include_scxml` must be used within a `moku::state_machine` module",
+    )
+    .into_compile_error()
+    .into()
+}
+
+#[proc_macro_derive(StateMachineEvent)]
+pub fn state_machine_event(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match event::derive_event(input) {
+        Ok(output) => output.into(),
+        Err(error) => error.into_compile_error().into(),
+    }
+}
+
 fn generate_state_machine(name: Ident, main_mod: ItemMod) -> Result<ItemMod, syn::Error> {
     let metadata = build_metadata(name, main_mod)?;
     Ok(metadata.write_state_machine())
@@ -1,914 +0,0 @@
-use std::collections::HashMap;
-
-use convert_case::{Case, Casing};
-use proc_macro2::{Span, TokenStream};
-use quote::{format_ident, quote, ToTokens};
-use syn::{parse_quote, Ident, Item, ItemImpl, ItemMod};
-
-pub struct State {
-    ident: Ident,
-    children: Vec<State>,
-    autogen_enter: bool,
-    imp: Option<ItemImpl>,
-}
-
-impl From<&Ident> for State {
-    fn from(ident: &Ident) -> Self {
-        Self {
-            ident: ident.clone(),
-            children: Vec::new(),
-            autogen_enter: false,
-            imp: None,
-        }
-    }
-}
-
-impl State {
-    /// Get the Ident for this State's Node.
-    fn node_ident(&self) -> Ident {
-        format_ident!("{}Node", self.ident)
-    }
-
-    /// Get the Ident for this State's SubstateEnum.
-    fn substate_enum_ident(&self) -> Ident {
-        format_ident!("{}Substate", self.ident)
-    }
-
-    /// Get the Ident for this State's Context.
-    fn context_ident(&self) -> Ident {
-        format_ident!("{}Context", self.ident)
-    }
-
-    /// Add a child to this State or one of its descendents. Returns the child if no parent is
-    /// found.
-    fn add_child(&mut self, mut child: State, parent: &Ident) -> Option<State> {
-        if self.ident == *parent {
-            self.children.push(child);
-            None
-        } else {
-            for state in &mut self.children {
-                if let Some(reject) = state.add_child(child, parent) {
-                    child = reject;
-                } else {
-                    return None;
-                }
-            }
-
-            Some(child)
-        }
-    }
-
-    /// Generate a simple text state chart of this State and its children.
-    fn state_chart(&self) -> String {
-        format!("{}", self.ident) + &self.state_chart_children(&mut Vec::new())
-    }
-
-    /// Helper function for formatting the children of a `state_chart`.
-    fn state_chart_children(&self, levels: &mut Vec<bool>) -> String {
-        if let Some((last, firsts)) = self.children.split_last() {
-            let mut acc = String::new();
-
-            for child in firsts {
-                acc += &child.state_chart_acc(levels, false);
-            }
-
-            acc + &last.state_chart_acc(levels, true)
-        } else {
-            String::new()
-        }
-    }
-
-    /// Helper function for recursively formatting `state_chart`.
-    fn state_chart_acc(&self, levels: &mut Vec<bool>, last: bool) -> String {
-        let mut pad = String::new();
-        for bar in levels.iter() {
-            if *bar {
-                pad += "\u{02502}  ";
-            } else {
-                pad += "   ";
-            }
-        }
-
-        let vert = if last { '\u{02514}' } else { '\u{0251C}' };
-
-        levels.push(!last);
-        let ret =
-            format!("\n{pad}{vert}\u{02500} {}", self.ident) + &self.state_chart_children(levels);
-        levels.pop();
-
-        ret
-    }
-
-    /// Create a copy that does not include an ItemImpl or children.
-    fn shallow_copy(&self) -> Self {
-        Self {
-            ident: self.ident.clone(),
-            children: Vec::new(),
-            autogen_enter: self.autogen_enter,
-            imp: None,
-        }
-    }
-
-    /// Execute a function for this state and each substate, with a list of shallow-copy
-    /// ancestors.
-    fn for_each_state<F: FnMut(&mut State, &Vec<State>)>(&mut self, mut fun: F) {
-        self.for_each_state_acc(&mut fun, &mut Vec::new());
-    }
-
-    /// Helper to accumulate ancestors for `for_each_state`.
-    fn for_each_state_acc<F: FnMut(&mut State, &Vec<State>)>(
-        &mut self,
-        fun: &mut F,
-        ancestors: &mut Vec<State>,
-    ) {
-        fun(self, ancestors);
-        ancestors.push(self.shallow_copy());
-        for child in &mut self.children {
-            child.for_each_state_acc(fun, ancestors);
-        }
-        ancestors.pop();
-    }
-
-    /// Get all descendents of this State.
-    fn descendents(&self) -> Vec<Ident> {
-        let mut res = Vec::new();
-        for child in &self.children {
-            child.descendents_acc(&mut res);
-        }
-        res
-    }
-
-    /// Helper to accumulate descendent list for `descendents`.
-    fn descendents_acc(&self, acc: &mut Vec<Ident>) {
-        acc.push(self.ident.clone());
-        for child in &self.children {
-            child.descendents_acc(acc);
-        }
-    }
-}
-
-pub struct Metadata {
-    pub name: Ident,
-    /// Event type path for use in machine module (e.g., `super::Event`)
-    pub event: TokenStream,
-    /// Event type path for use in main module (e.g., `Event`)
-    pub event_local: TokenStream,
-    pub top_state: State,
-    pub top_state_impl: Option<ItemImpl>,
-    pub states: HashMap<Ident, State>,
-    pub machine_mod: ItemMod,
-    pub main_mod: ItemMod,
-}
-
-impl Metadata {
-    /// Add a state machine state.
-    pub fn add_state(&mut self, ident: &Ident, autogen_enter: bool, imp: ItemImpl) {
-        let mut state: State = ident.into();
-        state.autogen_enter = autogen_enter;
-        state.imp = Some(imp);
-        self.states.insert(ident.clone(), state);
-    }
-
-    /// Add the Substate impl item to a state.
-    pub fn add_state_impl(&mut self, ident: &Ident, imp: ItemImpl) {
-        self.states
-            .get_mut(ident)
-            .unwrap_or_else(|| panic!("add_state_impl: could not find state {}", ident))
-            .imp = Some(imp);
-    }
-
-    /// Add a parent-child relation to the state graph, while detecting state graph cycles.
-    pub fn add_relation(&mut self, parent: &Ident, child: &Ident) -> Result<(), syn::Error> {
-        let mut child = self
-            .states
-            .remove(child)
-            .unwrap_or_else(|| panic!("add_relation: could not find child {}", child));
-
-        for state in self.states.values_mut().chain([&mut self.top_state]) {
-            if let Some(reject) = state.add_child(child, parent) {
-                child = reject;
-            } else {
-                return Ok(());
-            }
-        }
-
-        // we didn't find the parent, this is a cycle
-        Err(syn::Error::new(
-            parent.span(),
-            format!(
-                "state graph cycle detected in children of {parent}:\n{parent}{}",
-                child.state_chart_acc(&mut Vec::new(), true),
-            ),
-        ))
-    }
-
-    /// Write the state machine and return the complete main module.
-    pub fn write_state_machine(mut self) -> ItemMod {
-        self.write_state_chart();
-        self.write_state_enum();
-        self.write_machine();
-        self.write_builder();
-        self.write_states();
-        self.write_top_state_impl();
-
-        // put the machine_mod back into the main module
-        let main_mod_content = &mut self
-            .main_mod
-            .content
-            .as_mut()
-            .expect("main_mod_content: no content in module")
-            .1;
-
-        main_mod_content.push(Item::Mod(self.machine_mod));
-
-        self.main_mod
-    }
-
-    /// Get a mutable reference to the contents of the machine module.
-    fn machine_mod_content(&mut self) -> &mut Vec<Item> {
-        &mut self
-            .machine_mod
-            .content
-            .as_mut()
-            .expect("machine_mod_content: no content in module")
-            .1
-    }
-
-    /// Push an Item into the contents of the machine module.
-    fn push_to_machine_mod(&mut self, item: Item) {
-        self.machine_mod_content().push(item)
-    }
-
-    /// Get an Iterator of all states in this machine.
-    fn all_states(&self) -> impl Iterator<Item = Ident> {
-        [self.top_state.ident.clone()]
-            .into_iter()
-            .chain(self.top_state.descendents())
-    }
-
-    /// Write the state chart to the machine module.
-    fn write_state_chart(&mut self) {
-        let chart = self.top_state.state_chart();
-
-        self.push_to_machine_mod(parse_quote! {
-            pub const STATE_CHART: &str = #chart;
-        });
-    }
-
-    /// Write the StateEnum to the machine module.
-    fn write_state_enum(&mut self) {
-        let states = self.all_states();
-
-        self.push_to_machine_mod(parse_quote! {
-          #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-          pub enum State {
-              #(#states,)*
-          }
-        });
-
-        self.push_to_machine_mod(parse_quote! {
-          impl ::moku::StateEnum for State {}
-        });
-    }
-
-    /// Write the StateMachine to the machine module.
-    fn write_machine(&mut self) {
-        let event = self.event.clone();
-        let top_state = self.top_state.ident.clone();
-        let top_substate = self.top_state.substate_enum_ident();
-
-        self.push_to_machine_mod(parse_quote! {
-            pub struct Machine {
-                top_node: ::moku::internal::TopNode<State, #event, super::#top_state, #top_substate>,
-            }
-        });
-
-        self.push_to_machine_mod(parse_quote! {
-            impl Machine {
-                fn new(top_node: ::moku::internal::TopNode<State, #event, super::#top_state, #top_substate>) -> Self {
-                    let mut new = Self { top_node };
-                    new.top_node.init();
-                    new
-                }
-            }
-        });
-
-        let set_name = if cfg!(feature = "std") {
-            quote! {
-                fn set_name(&mut self, name: String) {
-                    self.top_node.set_name(name)
-                }
-            }
-        } else {
-            TokenStream::new()
-        };
-
-        let state_list = if cfg!(feature = "std") {
-            quote! {
-                fn state_list(&self) -> Vec<State> {
-                    self.top_node.node.state_list(Vec::new())
-                }
-            }
-        } else {
-            TokenStream::new()
-        };
-
-        self.push_to_machine_mod(parse_quote! {
-            impl ::moku::StateMachine<State, #event, super::#top_state> for Machine {
-                fn update(&mut self) {
-                    self.top_node.update()
-                }
-
-                fn top_down_update(&mut self) {
-                    self.top_node.top_down_update()
-                }
-
-                fn transition(&mut self, target: State) {
-                    self.top_node.transition(target, false, false);
-                }
-
-                fn exact_transition(&mut self, target: State) {
-                    self.top_node.transition(target, false, true);
-                }
-
-                fn state(&self) -> State {
-                    self.top_node.state()
-                }
-
-                fn name(&self) -> &str {
-                    self.top_node.name()
-                }
-
-                #set_name
-
-                fn state_matches(&self, state: State) -> bool {
-                    self.top_node.state_matches(state)
-                }
-
-                fn top_ref(&self) -> &super::#top_state {
-                    &self.top_node.node.state
-                }
-
-                fn top_mut(&mut self) -> &mut super::#top_state {
-                    &mut self.top_node.node.state
-                }
-
-                fn handle_event(&mut self, event: &#event) {
-                    self.top_node.handle_event(event)
-                }
-
-                #state_list
-            }
-        });
-
-        for state in self.all_states() {
-            let event = &self.event;
-
-            self.push_to_machine_mod(parse_quote! {
-                impl ::moku::StateRef<State, #event, super::#state> for Machine {
-                    fn state_ref(&self) -> Option<&super::#state> {
-                        self.top_node.node.state_ref()
-                    }
-
-                    fn state_mut(&mut self) -> Option<&mut super::#state> {
-                        self.top_node.node.state_mut()
-                    }
-                }
-            });
-        }
-    }
-
-    /// Write the Builder to the machine module.
-    fn write_builder(&mut self) {
-        let top_state = &self.top_state.ident;
-
-        let name_field = if cfg!(feature = "std") {
-            quote! {
-                name: Option<String>,
-            }
-        } else {
-            TokenStream::new()
-        };
-
-        self.push_to_machine_mod(parse_quote! {
-            pub struct Builder {
-                top_state: super::#top_state,
-                #name_field
-            }
-        });
-
-        let name_field = if cfg!(feature = "std") {
-            quote! {
-                name: None,
-            }
-        } else {
-            TokenStream::new()
-        };
-
-        let name_setter = if cfg!(feature = "std") {
-            quote! {
-                fn name(mut self, name: String) -> Self {
-                    self.name = Some(name);
-                    self
-                }
-            }
-        } else {
-            TokenStream::new()
-        };
-
-        let name = self.name.to_string();
-
-        let name_arg = if cfg!(feature = "std") {
-            quote! {
-                self.name.unwrap_or_else(|| String::from(#name)),
-            }
-        } else {
-            quote! {
-                #name
-            }
-        };
-
-        let event = &self.event;
-        let top_state = &self.top_state.ident;
-
-        self.push_to_machine_mod(parse_quote! {
-            impl ::moku::StateMachineBuilder<State, #event, super::#top_state, Machine> for Builder {
-                fn new(top_state: super::#top_state) -> Self {
-                    Self {
-                        top_state,
-                        #name_field
-                    }
-                }
-
-                #name_setter
-
-                fn build(self) -> Machine {
-                    Machine::new(::moku::internal::TopNode::new(
-                        self.top_state,
-                        #name_arg
-                    ))
-                }
-            }
-        });
-    }
-
-    /// Write the TopState impl with associated types to the main module.
-    fn write_top_state_impl(&mut self) {
-        if let Some(mut imp) = self.top_state_impl.take() {
-            let event_local = &self.event_local;
-            let machine_mod = self.machine_mod.ident.clone();
-
-            // Add the associated types
-            imp.items.insert(
-                0,
-                parse_quote! {
-                    type State = #machine_mod::State;
-                },
-            );
-            imp.items.insert(
-                1,
-                parse_quote! {
-                    type Event = #event_local;
-                },
-            );
-
-            let main_mod_content = &mut self
-                .main_mod
-                .content
-                .as_mut()
-                .expect("main_mod_content: no content in module")
-                .1;
-
-            main_mod_content.push(Item::Impl(imp));
-        }
-    }
-
-    /// Write the auto-generated elements for each State to their impls and the machine module.
-    fn write_states(&mut self) {
-        let mut items: Vec<Item> = Vec::new();
-        let event = &self.event;
-        let event_local = &self.event_local;
-        let machine_mod = self.machine_mod.ident.clone();
-        let mut substate_impls = Vec::new();
-        let mut statelike_impls = Vec::new();
-        let all_states: Vec<_> = self.all_states().collect();
-
-        self.top_state.for_each_state(|state, ancestors| {
-            let is_top_state = ancestors.is_empty();
-
-            let parent_context = match ancestors.last() {
-                None => quote! { ::moku::internal::TopContext },
-                Some(parent) => parent.context_ident().into_token_stream(),
-            };
-
-            // Substate enter, associated types, and StateLike impl
-            if !is_top_state {
-                let mut imp = state.imp.take().unwrap_or_else(|| {
-                    panic!("write_states: missing Substate impl for {}", state.ident)
-                });
-
-                // Add associated types at the beginning
-                imp.items.insert(
-                    0,
-                    parse_quote! {
-                        type State = #machine_mod::State;
-                    },
-                );
-                imp.items.insert(
-                    1,
-                    parse_quote! {
-                        type Event = #event_local;
-                    },
-                );
-                imp.items.insert(
-                    2,
-                    parse_quote! {
-                        type Context<'a> = #machine_mod::#parent_context<'a>;
-                    },
-                );
-
-                // Add enter method if autogenerated
-                if state.autogen_enter {
-                    imp.items.push(parse_quote! {
-                        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<::moku::Entry<Self::State, Self>> {
-                            Self {}
-                        }
-                    });
-                }
-
-                substate_impls.push(imp);
-
-                // Generate StateLike impl that bridges to Substate
-                let state_ident = &state.ident;
-                let context = state.context_ident();
-
-                statelike_impls.push(parse_quote! {
-                    impl ::moku::internal::StateLike<#machine_mod::State, #event_local> for #state_ident {
-                        type Context<'a> = #machine_mod::#parent_context<'a>;
-
-                        fn enter(ctx: &mut Self::Context<'_>) -> ::moku::Entry<#machine_mod::State, Self> {
-                            <Self as ::moku::Substate<_>>::enter(ctx).into()
-                        }
-
-                        fn init(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<::moku::Next<#machine_mod::State>> {
-                            <Self as ::moku::Substate<_>>::init(self, ctx)
-                        }
-
-                        fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<::moku::Next<#machine_mod::State>> {
-                            <Self as ::moku::Substate<_>>::update(self, ctx)
-                        }
-
-                        fn top_down_update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<::moku::Next<#machine_mod::State>> {
-                            <Self as ::moku::Substate<_>>::top_down_update(self, ctx)
-                        }
-
-                        fn exit(self, ctx: &mut Self::Context<'_>) -> impl Into<::moku::Next<#machine_mod::State>> {
-                            <Self as ::moku::Substate<_>>::exit(self, ctx)
-                        }
-
-                        fn handle_event(
-                            &mut self,
-                            ctx: &mut Self::Context<'_>,
-                            event: &#event_local,
-                        ) -> impl Into<::moku::Response<#machine_mod::State>> {
-                            <Self as ::moku::Substate<_>>::handle_event(self, ctx, event)
-                        }
-                    }
-                });
-            }
-
-            // Node
-            let state_ident = state.ident.clone();
-            let node = state.node_ident();
-            let substate = state.substate_enum_ident();
-
-            items.push(parse_quote! {
-               type #node = ::moku::internal::Node<State, #event, super::#state_ident, #substate>;
-            });
-
-            // Context
-            let context = state.context_ident();
-
-            let ancestor_idents = ancestors.iter().map(|anc| &anc.ident);
-            let ancestor_idents_snake: Vec<_> = ancestors
-                .iter()
-                .map(|anc| Ident::new(&anc.ident.to_string().to_case(Case::Snake), Span::call_site()))
-                .collect();
-            let state_ident_snake =
-                Ident::new(&state_ident.to_string().to_case(Case::Snake), Span::call_site());
-
-            items.push(parse_quote! {
-               pub struct #context<'a> {
-                   #(pub #ancestor_idents_snake: &'a mut super::#ancestor_idents,)*
-                   pub #state_ident_snake: &'a mut super::#state_ident,
-               }
-            });
-
-            items.push(parse_quote! {
-               impl<'a> #context<'a> {
-                   pub fn new(state: &'a mut super::#state_ident, ctx: &'a mut #parent_context) -> Self {
-                       Self {
-                           #(#ancestor_idents_snake: ctx.#ancestor_idents_snake,)*
-                           #state_ident_snake: state,
-                       }
-                   }
-               }
-            });
-
-            // SubstateEnum
-            let children: Vec<_> = state.children.iter().map(|child| &child.ident).collect();
-            let children_nodes: Vec<_> = state.children.iter().map(|child| child.node_ident()).collect();
-            let descendents = state.descendents();
-
-            items.push(parse_quote! {
-              enum #substate {
-                  None,
-                  #(#children(#children_nodes),)*
-              }
-            });
-
-            let state_list = if cfg!(feature = "std") {
-                quote! {
-                    fn state_list(&self, list: Vec<State>) -> Vec<State> {
-                        match self {
-                            Self::None => list,
-                            #(Self::#children(node) => node.state_list(list),)*
-                        }
-                    }
-                }
-            } else {
-                TokenStream::new()
-            };
-
-            let is_leaf_state = children.is_empty();
-
-            if is_leaf_state {
-                items.push(parse_quote! {
-                    impl ::moku::internal::SubstateEnum<State, #event, super::#state_ident> for #substate {
-                        fn none_variant() -> Self {
-                            Self::None
-                        }
-
-                        fn this_state() -> State {
-                            State::#state_ident
-                        }
-
-                        fn is_state(state: State) -> bool {
-                            matches!(state, State::#state_ident)
-                        }
-
-                        #state_list
-                    }
-                });
-            } else {
-                let children_and_descendents = state.children.iter().map(|child| {
-                    let mut res = child.descendents();
-                    res.push(child.ident.clone());
-                    res
-                });
-
-                let is_ancestor = if is_top_state {
-                    quote! {
-                        fn is_ancestor(state: State) -> bool {
-                            !matches!(state, State::#state_ident)
-                        }
-                    }
-                } else {
-                    quote! {
-                        fn is_ancestor(state: State) -> bool {
-                            matches!(state, #(State::#descendents)|*)
-                        }
-                    }
-                };
-
-                items.push(parse_quote! {
-                    impl ::moku::internal::SubstateEnum<State, #event, super::#state_ident> for #substate {
-                        fn none_variant() -> Self {
-                            Self::None
-                        }
-
-                        fn this_state() -> State {
-                            State::#state_ident
-                        }
-
-                        fn is_state(state: State) -> bool {
-                            matches!(state, State::#state_ident)
-                        }
-
-                        fn current_state(&self) -> State {
-                            match self {
-                                Self::None => State::#state_ident,
-                                #(Self::#children(node) => node.current_state(),)*
-                            }
-                        }
-
-                        #is_ancestor
-
-                        fn update(
-                            &mut self,
-                            state: &mut super::#state_ident,
-                            ctx: &mut <super::#state_ident as ::moku::internal::StateLike<State, #event>>::Context<'_>,
-                        ) -> ::moku::Next<State> {
-                            match self {
-                                Self::None => ::moku::Next::None,
-                                #(Self::#children(node) => node.update(&mut #context::new(state, ctx)),)*
-                            }
-                        }
-
-                        fn update_in_need(
-                            &mut self,
-                            state: &mut super::#state_ident,
-                            ctx: &mut <super::#state_ident as ::moku::internal::StateLike<State, #event>>::Context<'_>,
-                        ) -> ::moku::Next<State> {
-                            match self {
-                                Self::None => ::moku::Next::None,
-                                #(Self::#children(node) => node.update_in_need(&mut #context::new(state, ctx)),)*
-                            }
-                        }
-
-                        fn top_down_update(
-                            &mut self,
-                            state: &mut super::#state_ident,
-                            ctx: &mut <super::#state_ident as ::moku::internal::StateLike<State, #event>>::Context<'_>,
-                        ) -> ::moku::Next<State> {
-                            match self {
-                                Self::None => ::moku::Next::None,
-                                #(Self::#children(node) => {
-                                    node.top_down_update(&mut #context::new(state, ctx))
-                                })*
-                            }
-                        }
-
-                        fn top_down_update_in_need(
-                            &mut self,
-                            state: &mut super::#state_ident,
-                            ctx: &mut <super::#state_ident as ::moku::internal::StateLike<State, #event>>::Context<'_>,
-                        ) -> ::moku::Next<State> {
-                            match self {
-                                Self::None => ::moku::Next::None,
-                                #(Self::#children(node) => {
-                                    node.top_down_update_in_need(&mut #context::new(state, ctx))
-                                })*
-                            }
-                        }
-
-                        fn clear_top_down_updated(&mut self) {
-                            match self {
-                                Self::None => (),
-                                #(Self::#children(node) => node.clear_top_down_updated(),)*
-                            }
-                        }
-
-                        fn exit(
-                            &mut self,
-                            state: &mut super::#state_ident,
-                            ctx: &mut <super::#state_ident as ::moku::internal::StateLike<State, #event>>::Context<'_>,
-                            in_update: bool,
-                        ) -> ::moku::Next<State> {
-                            let old_state = core::mem::replace(self, Self::None);
-                            match old_state {
-                                Self::None => ::moku::Next::None,
-                                #(Self::#children(node) => node.exit(
-                                        &mut #context::new(state, ctx),
-                                        in_update,
-                                ),)*
-                            }
-                        }
-
-                        fn transition(
-                            &mut self,
-                            target: State,
-                            state: &mut super::#state_ident,
-                            ctx: &mut <super::#state_ident as ::moku::internal::StateLike<State, #event>>::Context<'_>,
-                            in_update: bool,
-                            exact: bool,
-                        ) -> ::moku::internal::TransitionResult<State> {
-                            match self {
-                                Self::None => ::moku::internal::TransitionResult::MoveUp,
-                                #(Self::#children(node) => {
-                                    node.transition(target, &mut #context::new(state, ctx), in_update, exact)
-                                })*
-                            }
-                        }
-
-                        fn enter_substate_towards(
-                            &mut self,
-                            target: State,
-                            state: &mut super::#state_ident,
-                            ctx: &mut <super::#state_ident as ::moku::internal::StateLike<State, #event>>::Context<'_>,
-                            in_update: bool,
-                        ) -> ::moku::Next<State> {
-                            match target {
-                                #(
-                                    #(State::#children_and_descendents)|* => {
-                                        match #children_nodes::enter(
-                                            &mut #context::new(state, ctx),
-                                            in_update,
-                                        ) {
-                                            ::moku::internal::NodeEntry::Node(node) => {
-                                                *self = Self::#children(node);
-                                                ::moku::Next::None
-                                            }
-                                            ::moku::internal::NodeEntry::Target(new_target) => ::moku::Next::Target(new_target),
-                                            ::moku::internal::NodeEntry::ExactTarget(new_target) => ::moku::Next::ExactTarget(new_target),
-                                        }
-                                    }
-                                )*
-                                _ => unreachable!(),
-                            }
-                        }
-
-                        fn state_matches(&self, state: State) -> bool {
-                            Self::is_state(state)
-                                || match self {
-                                    Self::None => false,
-                                    #(Self::#children(node) => node.state_matches(state),)*
-                                }
-                        }
-
-                        fn handle_event(
-                            &mut self,
-                            event: &#event,
-                            state: &mut super::#state_ident,
-                            ctx: &mut <super::#state_ident as ::moku::internal::StateLike<State, #event>>::Context<'_>,
-                        ) -> ::moku::Response<State> {
-                            match self {
-                                Self::None => ::moku::Response::Next(::moku::Next::None),
-                                #(Self::#children(node) => node.handle_event(
-                                        &mut #context::new(state, ctx),
-                                        event,
-                                ),)*
-                            }
-                        }
-
-                        #state_list
-                    }
-                });
-            }
-
-            // StateRef
-            for other_state in &all_states {
-                items.push(if *other_state == state_ident {
-                    parse_quote! {
-                        impl ::moku::StateRef<State, #event, super::#other_state> for #node {
-                            fn state_ref(&self) -> Option<&super::#other_state> {
-                                Some(&self.state)
-                            }
-
-                            fn state_mut(&mut self) -> Option<&mut super::#other_state> {
-                                Some(&mut self.state)
-                            }
-                        }
-                    }
-                } else if descendents.contains(other_state) {
-                    parse_quote! {
-                        impl ::moku::StateRef<State, #event, super::#other_state> for #node {
-                            fn state_ref(&self) -> Option<&super::#other_state> {
-                                match &self.substate {
-                                    #substate::None => None,
-                                    #(#substate::#children(node) => node.state_ref(),)*
-                                }
-                            }
-
-                            fn state_mut(&mut self) -> Option<&mut super::#other_state> {
-                                match &mut self.substate {
-                                    #substate::None => None,
-                                    #(#substate::#children(node) => node.state_mut(),)*
-                                }
-                            }
-                        }
-                    }
-                } else {
-                    parse_quote! {
-                        impl ::moku::StateRef<State, #event, super::#other_state> for #node {
-                            fn state_ref(&self) -> Option<&super::#other_state> {
-                                None
-                            }
-
-                            fn state_mut(&mut self) -> Option<&mut super::#other_state> {
-                                None
-                            }
-                        }
-                    }
-                });
-            }
-        });
-
-        let main_mod_content = &mut self
-            .main_mod
-            .content
-            .as_mut()
-            .expect("main_mod_content: no content in module")
-            .1;
-
-        for imp in substate_impls {
-            main_mod_content.push(Item::Impl(imp));
-        }
-
-        for imp in statelike_impls {
-            main_mod_content.push(Item::Impl(imp));
-        }
-
-        self.machine_mod_content().extend(items);
-    }
-}
@@ -0,0 +1,849 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use convert_case::{Case, Casing};
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, ToTokens};
+use roxmltree::{Document, Node};
+use syn::{
+    parse_quote, spanned::Spanned, GenericArgument, Ident, ImplItem, Item, ItemImpl, ItemMod,
+    LitStr, Path, PathArguments, Type,
+};
+
+/// Merge the chart of the `include_scxml!` invocation within a `state_machine` module, if any,
+/// into the module.
+pub fn expand_include(main_mod: &mut ItemMod) -> Result<(), syn::Error> {
+    let Some((_, items)) = &mut main_mod.content else {
+        return Ok(());
+    };
+
+    let mut includes = items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| match item {
+            Item::Macro(item) if path_ends_with(&item.mac.path, "include_scxml") => Some(index),
+            _ => None,
+        });
+
+    let Some(index) = includes.next() else {
+        return Ok(());
+    };
+
+    if let Some(second) = includes.next() {
+        return Err(syn::Error::new_spanned(
+            &items[second],
+            "multiple `moku::include_scxml` invocations in this module",
+        ));
+    }
+
+    let Item::Macro(include) = items.remove(index) else {
+        unreachable!()
+    };
+
+    let path: LitStr = include.mac.parse_body()?;
+    let span = path.span();
+    let name = path.value();
+    let file = resolve_path(&name, span);
+    let text = std::fs::read_to_string(&file).map_err(|error| {
+        syn::Error::new(span, format!("failed to read SCXML file `{name}`: {error}"))
+    })?;
+
+    let doc = Document::parse(&text).map_err(|error| {
+        syn::Error::new(
+            span,
+            format!("failed to parse SCXML file `{name}`: {error}"),
+        )
+    })?;
+
+    let chart = ChartParser::new(&name, &doc, span).parse()?;
+    chart.validate(span)?;
+    chart.merge(items, &file)
+}
+
+/// Check if the last segment of a path is the given identifier.
+fn path_ends_with(path: &Path, ident: &str) -> bool {
+    path.segments
+        .last()
+        .is_some_and(|segment| segment.ident == ident)
+}
+
+/// Get the identifier that a type is named by, e.g. `Foo` for `super::Foo<T>`.
+fn type_ident(ty: &Type) -> Option<&Ident> {
+    match ty {
+        Type::Path(ty) => ty.path.segments.last().map(|segment| &segment.ident),
+        _ => None,
+    }
+}
+
+/// Get the identifier that an item is named by, if any.
+fn item_ident(item: &Item) -> Option<&Ident> {
+    match item {
+        Item::Struct(item) => Some(&item.ident),
+        Item::Enum(item) => Some(&item.ident),
+        Item::Type(item) => Some(&item.ident),
+        _ => None,
+    }
+}
+
+/// Find the impl of a trait, such as `Substate`, for the type named by an identifier.
+fn find_impl<'a>(
+    items: &'a mut [Item],
+    trait_ident: &str,
+    ident: &Ident,
+) -> Option<&'a mut ItemImpl> {
+    items.iter_mut().find_map(|item| match item {
+        Item::Impl(imp)
+            if imp
+                .trait_
+                .as_ref()
+                .is_some_and(|(_, path, _)| path_ends_with(path, trait_ident))
+                && type_ident(&imp.self_ty) == Some(ident) =>
+        {
+            Some(imp)
+        }
+        _ => None,
+    })
+}
+
+/// Check if an impl defines a function.
+fn has_fn(imp: &ItemImpl, name: &str) -> bool {
+    imp.items
+        .iter()
+        .any(|item| matches!(item, ImplItem::Fn(fun) if fun.sig.ident == name))
+}
+
+/// Resolve a relative path like `include_str!`, against the directory of the invoking source
+/// file, falling back to the `CARGO_MANIFEST_DIR` of the crate being compiled.
+fn resolve_path(path: &str, span: Span) -> PathBuf {
+    let path = PathBuf::from(path);
+    if path.is_absolute() {
+        return path;
+    }
+
+    let source_dir = span
+        .unwrap()
+        .local_file()
+        .and_then(|file| file.parent().map(std::path::Path::to_path_buf));
+
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from);
+
+    [source_dir, manifest_dir]
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join(&path))
+        .find(|file| file.is_file())
+        .and_then(|file| file.canonicalize().ok())
+        .unwrap_or(path)
+}
+
+/// Convert an SCXML id or event name to an `UpperCamel` Rust identifier.
+fn to_ident(name: &str) -> Option<Ident> {
+    let camel = name.replace('.', "_").to_case(Case::UpperCamel);
+    syn::parse_str::<Ident>(&camel).ok()
+}
+
+/// A reference to a state by its SCXML id, along with the identifier that the id maps to.
+struct StateRef {
+    id: String,
+    ident: Ident,
+}
+
+struct ChartState {
+    id: String,
+    ident: Ident,
+    initial: Option<StateRef>,
+    children: Vec<ChartState>,
+    transitions: Vec<ChartTransition>,
+}
+
+struct ChartTransition {
+    /// An empty event list denotes an eventless transition.
+    events: Vec<EventDescriptor>,
+    target: Option<StateRef>,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum EventDescriptor {
+    Named(Ident),
+    Any,
+}
+
+struct Chart {
+    initial: Option<StateRef>,
+    states: Vec<ChartState>,
+    events: Vec<Ident>,
+}
+
+/// Turns an SCXML document into a [`Chart`], reporting errors with their file position.
+struct ChartParser<'a> {
+    file: &'a str,
+    doc: &'a Document<'a>,
+    span: Span,
+    events: Vec<Ident>,
+}
+
+impl<'a> ChartParser<'a> {
+    fn new(file: &'a str, doc: &'a Document<'a>, span: Span) -> Self {
+        Self {
+            file,
+            doc,
+            span,
+            events: Vec::new(),
+        }
+    }
+
+    /// Make an error pointing at the invocation with the location of an SCXML node.
+    fn error(&self, node: Node, msg: impl AsRef<str>) -> syn::Error {
+        let pos = self.doc.text_pos_at(node.range().start);
+        syn::Error::new(
+            self.span,
+            format!("{}:{}:{}: {}", self.file, pos.row, pos.col, msg.as_ref()),
+        )
+    }
+
+    fn parse(mut self) -> Result<Chart, syn::Error> {
+        let root = self.doc.root_element();
+        if root.tag_name().name() != "scxml" {
+            return Err(self.error(root, "the root element must be `<scxml>`"));
+        }
+
+        let initial = self.initial_attr(root)?;
+        let states = self.parse_children(root)?;
+
+        if states.is_empty() {
+            return Err(self.error(root, "an SCXML chart must contain at least one `<state>`"));
+        }
+
+        Ok(Chart {
+            initial,
+            states,
+            events: self.events,
+        })
+    }
+
+    /// Parse the `initial` attribute or `<initial>` child of a state or the root element.
+    fn initial_attr(&self, node: Node) -> Result<Option<StateRef>, syn::Error> {
+        let target = match node.attribute("initial") {
+            Some(initial) => Some((node, initial)),
+            None => node
+                .children()
+                .find(|child| child.tag_name().name() == "initial")
+                .map(|initial| {
+                    initial
+                        .children()
+                        .find(|child| child.tag_name().name() == "transition")
+                        .and_then(|transition| transition.attribute("target"))
+                        .map(|target| (initial, target))
+                        .ok_or_else(|| {
+                            self.error(
+                                initial,
+                                "an `<initial>` must contain a `<transition>` with a `target`",
+                            )
+                        })
+                })
+                .transpose()?,
+        };
+
+        target
+            .map(|(node, target)| self.state_ref(node, target))
+            .transpose()
+    }
+
+    /// Parse a reference to a single state id.
+    fn state_ref(&self, node: Node, ids: &str) -> Result<StateRef, syn::Error> {
+        let mut ids = ids.split_whitespace();
+        match (ids.next(), ids.next()) {
+            (Some(id), None) => to_ident(id)
+                .map(|ident| StateRef {
+                    id: id.to_owned(),
+                    ident,
+                })
+                .ok_or_else(|| {
+                    self.error(
+                        node,
+                        format!("the state id `{id}` cannot be converted to a Rust identifier"),
+                    )
+                }),
+            _ => Err(self.error(node, "exactly one target state must be given")),
+        }
+    }
+
+    fn parse_children(&mut self, node: Node) -> Result<Vec<ChartState>, syn::Error> {
+        let mut states = Vec::new();
+
+        for child in node.children().filter(Node::is_element) {
+            match child.tag_name().name() {
+                "state" | "final" => states.push(self.parse_state(child)?),
+                "parallel" => {
+                    return Err(self.error(child, "`<parallel>` states are not supported by moku"))
+                }
+                "history" => {
+                    return Err(self.error(child, "`<history>` states are not supported by moku"))
+                }
+                // executable content and data are left for the user to implement
+                _ => (),
+            }
+        }
+
+        Ok(states)
+    }
+
+    fn parse_state(&mut self, node: Node) -> Result<ChartState, syn::Error> {
+        let id = node.attribute("id").ok_or_else(|| {
+            self.error(
+                node,
+                format!("every `<{}>` must have an `id`", node.tag_name().name()),
+            )
+        })?;
+
+        let ident = to_ident(id).ok_or_else(|| {
+            self.error(
+                node,
+                format!("the state id `{id}` cannot be converted to a Rust identifier"),
+            )
+        })?;
+
+        if ident == "Top" || ident == "Event" {
+            return Err(self.error(
+                node,
+                format!("the state id `{id}` conflicts with the generated `{ident}` type"),
+            ));
+        }
+
+        let initial = self.initial_attr(node)?;
+
+        let mut transitions = Vec::new();
+        for transition in node
+            .children()
+            .filter(|child| child.tag_name().name() == "transition")
+        {
+            transitions.push(self.parse_transition(transition)?);
+        }
+
+        let children = self.parse_children(node)?;
+
+        Ok(ChartState {
+            id: id.to_owned(),
+            ident,
+            initial,
+            children,
+            transitions,
+        })
+    }
+
+    fn parse_transition(&mut self, node: Node) -> Result<ChartTransition, syn::Error> {
+        if node.attribute("cond").is_some() {
+            return Err(self.error(
+                node,
+                "transition conditions (`cond`) are not supported, implement them in the generated hooks instead",
+            ));
+        }
+
+        let mut events = Vec::new();
+        for event in node
+            .attribute("event")
+            .unwrap_or_default()
+            .split_whitespace()
+        {
+            let descriptor = if event == "*" {
+                EventDescriptor::Any
+            } else if event.contains('*') {
+                return Err(self.error(
+                    node,
+                    format!("the event descriptor `{event}` is not supported, only `*` may be used as a wildcard"),
+                ));
+            } else {
+                let ident = to_ident(event).ok_or_else(|| {
+                    self.error(
+                        node,
+                        format!("the event `{event}` cannot be converted to a Rust identifier"),
+                    )
+                })?;
+
+                if !self.events.contains(&ident) {
+                    self.events.push(ident.clone());
+                }
+
+                EventDescriptor::Named(ident)
+            };
+
+            if !events.contains(&descriptor) {
+                events.push(descriptor);
+            }
+        }
+
+        let target = node
+            .attribute("target")
+            .map(|target| self.state_ref(node, target))
+            .transpose()?;
+
+        Ok(ChartTransition { events, target })
+    }
+}
+
+impl ChartState {
+    /// Execute a function for this state and each descendent, along with a list of its
+    /// ancestors.
+    fn for_each<'a, F: FnMut(&'a ChartState, &[&'a Ident])>(
+        &'a self,
+        ancestors: &mut Vec<&'a Ident>,
+        fun: &mut F,
+    ) {
+        fun(self, ancestors);
+        ancestors.push(&self.ident);
+        for child in &self.children {
+            child.for_each(ancestors, fun);
+        }
+        ancestors.pop();
+    }
+
+    /// Does this state or any of its descendents have the given SCXML id?
+    fn contains(&self, id: &str) -> bool {
+        self.id == id || self.children.iter().any(|child| child.contains(id))
+    }
+
+    /// The target of the initial transition upon entering this state directly, if any.
+    fn initial_target(&self) -> Option<&Ident> {
+        self.initial
+            .as_ref()
+            .map(|initial| &initial.ident)
+            .or_else(|| self.children.first().map(|child| &child.ident))
+    }
+}
+
+/// Write the `Next` for a transition from a state to a target.
+///
+/// SCXML transitions to the source state or one of its ancestors exit and re-enter the target,
+/// which corresponds to a moku exact transition.
+fn write_next(
+    machine: &Ident,
+    target: &Ident,
+    source: &ChartState,
+    ancestors: &[&Ident],
+) -> TokenStream {
+    if *target == source.ident || ancestors.contains(&target) {
+        quote! { ::moku::Next::ExactTarget(#machine::State::#target) }
+    } else {
+        quote! { ::moku::Next::Target(#machine::State::#target) }
+    }
+}
+
+impl Chart {
+    fn for_each_state<'a, F: FnMut(&'a ChartState, &[&'a Ident])>(&'a self, mut fun: F) {
+        for state in &self.states {
+            state.for_each(&mut Vec::new(), &mut fun);
+        }
+    }
+
+    /// Validate state ids and transition targets.
+    ///
+    /// SCXML ids are case-sensitive, so references must match a state id exactly rather than
+    /// only after conversion to a Rust identifier.
+    ///
+    /// Structural validation of the resulting machine is left to the `state_machine` unpacker.
+    fn validate(&self, span: Span) -> Result<(), syn::Error> {
+        let mut ids = HashSet::new();
+        let mut idents = HashSet::new();
+        let mut error = None;
+
+        self.for_each_state(|state, _| {
+            if error.is_some() {
+                return;
+            }
+
+            if !ids.insert(state.id.as_str()) {
+                error = Some(syn::Error::new(
+                    span,
+                    format!("multiple SCXML states have the id `{}`", state.id),
+                ));
+            } else if !idents.insert(state.ident.clone()) {
+                error = Some(syn::Error::new(
+                    span,
+                    format!(
+                        "multiple SCXML states map to the state name `{}`",
+                        state.ident
+                    ),
+                ));
+            }
+        });
+
+        self.for_each_state(|state, _| {
+            if error.is_some() {
+                return;
+            }
+
+            if let Some(initial) = &state.initial {
+                if initial.id == state.id || !state.contains(&initial.id) {
+                    error = Some(syn::Error::new(
+                        span,
+                        format!(
+                            "the initial state `{}` of `{}` must be one of its descendents",
+                            initial.id, state.id
+                        ),
+                    ));
+                    return;
+                }
+            }
+
+            for target in state.transitions.iter().filter_map(|t| t.target.as_ref()) {
+                if !ids.contains(target.id.as_str()) {
+                    error = Some(syn::Error::new(
+                        span,
+                        format!(
+                            "the transition target `{}` of `{}` doesn't match any SCXML state",
+                            target.id, state.id
+                        ),
+                    ));
+                    return;
+                }
+            }
+        });
+
+        if let Some(initial) = &self.initial {
+            if error.is_none() && !ids.contains(initial.id.as_str()) {
+                error = Some(syn::Error::new(
+                    span,
+                    format!(
+                        "the initial state `{}` doesn't match any SCXML state",
+                        initial.id
+                    ),
+                ));
+            }
+        }
+
+        // eventless transitions are taken as soon as their states are entered, so a loop of them
+        // would never settle
+        if error.is_none() {
+            error = self.find_eventless_loop().map(|id| {
+                syn::Error::new(
+                    span,
+                    format!("the eventless transitions taken upon entering `{id}` loop forever"),
+                )
+            });
+        }
+
+        match error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Write a `moku::on` handler for each transition of a state that is triggered by events.
+    ///
+    /// Handlers are matched in order, so those written by the user take precedence over these,
+    /// which are matched in document order like SCXML transitions.
+    fn write_handlers(
+        &self,
+        machine: &Ident,
+        state: &ChartState,
+        ancestors: &[&Ident],
+    ) -> Vec<ImplItem> {
+        let mut handled: Vec<&Ident> = Vec::new();
+        let mut handlers = Vec::new();
+
+        for transition in &state.transitions {
+            let response = match &transition.target {
+                Some(target) => {
+                    let next = write_next(machine, &target.ident, state, ancestors);
+                    quote! { ::moku::Response::Next(#next) }
+                }
+                None => quote! { ::moku::Response::Drop },
+            };
+
+            let mut patterns = Vec::new();
+            for descriptor in &transition.events {
+                match descriptor {
+                    EventDescriptor::Named(event) => {
+                        if !handled.contains(&event) {
+                            handled.push(event);
+                            patterns.push(quote! { Event::#event });
+                        }
+                    }
+                    EventDescriptor::Any => {
+                        // SCXML selects the first matching transition in document order
+                        let rest = self.events.iter().filter(|event| !handled.contains(event));
+                        patterns.extend(rest.map(|event| quote! { Event::#event }));
+                        handled = self.events.iter().collect();
+                    }
+                }
+            }
+
+            if !patterns.is_empty() {
+                let ident = format_ident!("__scxml_transition_{}", handlers.len());
+                handlers.push(parse_quote! {
+                    #[::moku::on(#(#patterns)|*)]
+                    fn #ident() -> ::moku::Response<Self::State> {
+                        #response
+                    }
+                });
+            }
+        }
+
+        handlers
+    }
+
+    /// Write the target of the `init` hook of a state, if any.
+    ///
+    /// Compound states take their initial transition, and atomic states take their
+    /// [eventless target](Chart::eventless_target), so that it is taken as soon as the state is
+    /// entered, as in SCXML.
+    fn write_init(
+        &self,
+        machine: &Ident,
+        state: &ChartState,
+        ancestors: &[&Ident],
+    ) -> Option<TokenStream> {
+        match state.initial_target() {
+            Some(initial) => Some(quote! { #machine::State::#initial }),
+            None => self
+                .eventless_target(state, ancestors)
+                .map(|target| write_next(machine, target, state, ancestors)),
+        }
+    }
+
+    /// The target of the first eventless transition of an atomic state or its ancestors, nearest
+    /// first.
+    ///
+    /// Without a `cond`, an eventless transition is always enabled, so it is taken as soon as any
+    /// state within its source is entered.
+    fn eventless_target<'a>(
+        &'a self,
+        state: &'a ChartState,
+        ancestors: &[&'a Ident],
+    ) -> Option<&'a Ident> {
+        let states = self.states_by_ident();
+        std::iter::once(state)
+            .chain(ancestors.iter().rev().map(|ancestor| states[*ancestor]))
+            .find_map(|state| {
+                state
+                    .transitions
+                    .iter()
+                    .find(|transition| transition.events.is_empty())
+                    .map(|transition| transition.target.as_ref().map(|target| &target.ident))
+            })
+            .flatten()
+    }
+
+    /// Find an atomic state whose eventless transitions lead back to a state that they already
+    /// entered, and so would be taken forever.
+    fn find_eventless_loop(&self) -> Option<&str> {
+        let states = self.states_by_ident();
+        let mut ancestors_of = HashMap::new();
+        self.for_each_state(|state, ancestors| {
+            ancestors_of.insert(&state.ident, ancestors.to_vec());
+        });
+
+        let mut found = None;
+        self.for_each_state(|state, _| {
+            if found.is_some() || !state.children.is_empty() {
+                return;
+            }
+
+            let mut entered = vec![&state.ident];
+            let mut current = state;
+            while let Some(target) = self.eventless_target(current, &ancestors_of[&current.ident]) {
+                // entering a state settles in the atomic state at the end of its initial
+                // transitions
+                current = states[target];
+                while let Some(initial) = current.initial_target() {
+                    current = states[initial];
+                }
+
+                if entered.contains(&&current.ident) {
+                    found = Some(state.id.as_str());
+                    return;
+                }
+                entered.push(&current.ident);
+            }
+        });
+        found
+    }
+
+    /// Map the identifier of every state to the state.
+    fn states_by_ident(&self) -> HashMap<&Ident, &ChartState> {
+        let mut states = HashMap::new();
+        self.for_each_state(|state, _| {
+            states.insert(&state.ident, state);
+        });
+        states
+    }
+
+    /// Merge the chart into the items of a `state_machine` module.
+    ///
+    /// States, the top state, and the `Event` enum that the module doesn't define are generated.
+    /// The transitions of the chart are added to the `Substate` and `TopState` impls as `init`
+    /// hooks and `moku::on` handlers, unless the impl defines `init` or `handle_event` itself.
+    fn merge(&self, items: &mut Vec<Item>, file: &std::path::Path) -> Result<(), syn::Error> {
+        let file = file.to_string_lossy().into_owned();
+
+        let machine = items
+            .iter()
+            .find_map(|item| match item {
+                Item::Mod(module)
+                    if module
+                        .attrs
+                        .iter()
+                        .any(|attr| path_ends_with(attr.path(), "machine_module")) =>
+                {
+                    Some(module.ident.clone())
+                }
+                _ => None,
+            })
+            .unwrap_or_else(|| format_ident!("machine"));
+
+        let is_defined = |items: &[Item], ident: &Ident| {
+            items.iter().any(|item| item_ident(item) == Some(ident))
+        };
+
+        // rebuild the machine when the chart changes
+        items.push(parse_quote! {
+            const _: &[u8] = include_bytes!(#file);
+        });
+
+        if !self.events.is_empty() && !is_defined(items, &format_ident!("Event")) {
+            let events = &self.events;
+            items.push(parse_quote! {
+                #[derive(Debug, Clone, Copy, PartialEq, Eq, ::moku::StateMachineEvent)]
+                pub enum Event {
+                    #(#events,)*
+                }
+            });
+        }
+
+        let top = items.iter().find_map(|item| match item {
+            Item::Impl(imp)
+                if imp
+                    .trait_
+                    .as_ref()
+                    .is_some_and(|(_, path, _)| path_ends_with(path, "TopState")) =>
+            {
+                Some(imp.self_ty.to_token_stream())
+            }
+            _ => None,
+        });
+
+        let top = match top {
+            Some(top) => top,
+            None => {
+                let top = format_ident!("Top");
+                if !is_defined(items, &top) {
+                    items.push(parse_quote! { pub struct Top; });
+                }
+                items.push(parse_quote! { impl ::moku::TopState for Top {} });
+                quote! { Top }
+            }
+        };
+        let top_ident = syn::parse2::<Type>(top.clone())
+            .ok()
+            .and_then(|ty| type_ident(&ty).cloned())
+            .unwrap_or_else(|| format_ident!("Top"));
+
+        let top_init = self
+            .initial
+            .as_ref()
+            .map(|initial| &initial.ident)
+            .or_else(|| self.states.first().map(|state| &state.ident));
+        if let (Some(initial), Some(imp)) = (top_init, find_impl(items, "TopState", &top_ident)) {
+            if !has_fn(imp, "init") {
+                imp.items.push(parse_quote! {
+                    fn init(&mut self) -> impl Into<::moku::Next<Self::State>> {
+                        #machine::State::#initial
+                    }
+                });
+            }
+        }
+
+        let mut result = Ok(());
+        self.for_each_state(|state, ancestors| {
+            if result.is_err() {
+                return;
+            }
+
+            let ident = &state.ident;
+            let parent = ancestors.last().copied().unwrap_or(&top_ident);
+
+            if find_impl(items, "Substate", ident).is_none() {
+                if !is_defined(items, ident) {
+                    items.push(parse_quote! { pub struct #ident; });
+                }
+                let parent = match ancestors.last() {
+                    Some(parent) => quote! { #parent },
+                    None => top.clone(),
+                };
+                items.push(parse_quote! { impl ::moku::Substate<#parent> for #ident {} });
+            }
+            let imp = find_impl(items, "Substate", ident).unwrap();
+
+            // the superstate must match the chart for its transitions to make sense
+            let superstate = imp.trait_.as_ref().and_then(|(_, path, _)| {
+                match &path.segments.last()?.arguments {
+                    PathArguments::AngleBracketed(args) => match args.args.first()? {
+                        GenericArgument::Type(ty) => Some(ty),
+                        _ => None,
+                    },
+                    _ => None,
+                }
+            });
+            if let Some(superstate) = superstate {
+                if type_ident(superstate) != Some(parent) {
+                    result = Err(syn::Error::new(
+                        superstate.span(),
+                        format!("`{ident}` is a substate of `{parent}` in the SCXML chart"),
+                    ));
+                    return;
+                }
+            }
+
+            if !has_fn(imp, "init") {
+                if let Some(next) = self.write_init(&machine, state, ancestors) {
+                    imp.items.push(parse_quote! {
+                        fn init(
+                            &mut self,
+                            _ctx: &mut Self::Context<'_>,
+                        ) -> impl Into<::moku::Next<Self::State>> {
+                            #next
+                        }
+                    });
+                }
+            }
+
+            if !has_fn(imp, "handle_event") {
+                let handlers = self.write_handlers(&machine, state, ancestors);
+                imp.items.extend(handlers);
+            }
+        });
+        result?;
+
+        // the states are ordered as in the chart, wherever they are defined
+        let mut order = Vec::new();
+        self.for_each_state(|state, _| order.push(&state.ident));
+        let position = |item: &Item| match item {
+            Item::Impl(imp)
+                if imp
+                    .trait_
+                    .as_ref()
+                    .is_some_and(|(_, path, _)| path_ends_with(path, "Substate")) =>
+            {
+                type_ident(&imp.self_ty)
+                    .and_then(|ident| order.iter().position(|state| *state == ident))
+            }
+            _ => None,
+        };
+
+        let slots: Vec<_> = (0..items.len())
+            .filter(|index| position(&items[*index]).is_some())
+            .collect();
+        let mut impls: Vec<_> = slots
+            .iter()
+            .map(|index| std::mem::replace(&mut items[*index], Item::Verbatim(TokenStream::new())))
+            .collect();
+        impls.sort_by_key(|imp| position(imp));
+        for (index, imp) in slots.into_iter().zip(impls) {
+            items[index] = imp;
+        }
+
+        Ok(())
+    }
+}
@@ -10,6 +10,10 @@
 /// - exactly one implementation of [`TopState`]
 /// - any number of implementations of [`Substate`]
 ///
+/// Implementations of [`Substate`] may also be placed in inline submodules of the attributed
+/// module, at any depth. Such states are made visible to the attributed module, and the
+/// generated items they use must be imported, e.g. with `use super::*;`.
+///
 /// An optional name can be provided as an argument to set the default name of the
 /// [`StateMachine`] for use in logging. If not provided, the name defaults to the
 /// module name in `UpperCamel` case. This is useful for `no_std` environments
@@ -68,112 +72,1420 @@ pub use moku_macros::state_machine;
 /// ```
 pub use moku_macros::machine_module;
 
-/// Represents either no action or some type of transition to new state.
-///
-/// Return type of multiple [`Substate`] methods.
-pub enum Next<T: StateEnum> {
-    /// No transition should be taken, stay in the current active state.
-    None,
+/// Handle events matching a pattern with a function of a [`Substate`] or [`TopState`] impl.
+///
+/// Attributed functions are moved to an inherent impl of the state, and a `handle_event`
+/// function is generated that calls the first function whose pattern matches each event.
+/// A function may have multiple `on` attributes, and patterns may bind fields of the event.
+///
+/// Each parameter of the function is passed by name, ignoring any leading underscores:
+/// - `ctx` is the state's [`Substate::Context`]
+/// - `event` is the event being handled
+/// - any other name is a binding of the pattern
+///
+/// Functions must return something that implements `Into<Response<State>>`
+/// (or `Into<Next<State>>` for the [`TopState`]), just like `handle_event`. Functions of a
+/// [`Substate`] that return nothing handle the event without a transition, as if they returned
+/// [`Response::Handled`]. Those of the [`TopState`] leave the event unhandled, as it has no
+/// superstate to defer it to.
+///
+/// Events that match no pattern are deferred to the superstate, unless the impl is attributed
+/// with [`unmatched`]. `handle_event` must not be manually defined alongside `on` functions.
+///
+/// ## Example
+/// ```
+/// #[moku::state_machine]
+/// mod hunter {
+///     use moku::*;
+///
+///     #[machine_module]
+///     pub mod machine {}
+///     use machine::State;
+///
+///     pub enum Event {
+///         PreyCaught { size: u32 },
+///         PreyEscaped,
+///         Nightfall,
+///     }
+///     impl StateMachineEvent for Event {}
+///
+///     pub struct Top {
+///         pub food: u32,
+///     }
+///
+///     impl TopState for Top {
+///         fn init(&mut self) -> impl Into<Next<Self::State>> {
+///             State::Hunting
+///         }
+///     }
+///
+///     struct Hunting;
+///
+///     #[unmatched(drop)]
+///     impl Substate<Top> for Hunting {
+///         #[on(Event::PreyCaught { size })]
+///         fn caught(&mut self, ctx: &mut Self::Context<'_>, size: &u32) -> Next<Self::State> {
+///             ctx.top.food += size;
+///             State::Resting.into()
+///         }
+///
+///         #[on(Event::Nightfall)]
+///         fn nightfall(&mut self) -> Next<Self::State> {
+///             State::Resting.into()
+///         }
+///     }
+///
+///     struct Resting;
+///     impl Substate<Top> for Resting {}
+/// }
+///
+/// use moku::*;
+/// use hunter::{machine::{Builder, State}, Event, Top};
+///
+/// let mut machine = Builder::new(Top { food: 0 }).build();
+///
+/// let outcome = machine.handle_event(&Event::PreyEscaped);
+/// assert_eq!(outcome, EventOutcome::Dropped { state: State::Hunting });
+///
+/// machine.handle_event(&Event::PreyCaught { size: 3 });
+/// assert!(matches!(machine.state(), State::Resting));
+/// assert_eq!(machine.top_ref().food, 3);
+/// ```
+pub use moku_macros::on;
+
+/// Set what happens to events that match no [`on`] function of a [`Substate`] impl.
+///
+/// Takes either `defer`, the default, to defer unmatched events to the superstate, or `drop`
+/// to drop them. Events are never dropped by the [`TopState`], so only `defer` is accepted on
+/// its impl.
+///
+/// A `handle_event` function is generated for the state even if it has no [`on`] functions, so
+/// `#[moku::unmatched(drop)]` alone drops every event.
+///
+/// ## Example
+/// ```
+/// #[moku::state_machine]
+/// mod blinky {
+///     use moku::*;
+///
+///     #[machine_module]
+///     mod machine {}
+///
+///     pub struct Event;
+///     impl StateMachineEvent for Event {}
+///
+///     struct Top;
+///     impl TopState for Top {}
+///
+///     struct Disabled;
+///
+///     #[unmatched(drop)]
+///     impl Substate<Top> for Disabled {}
+/// }
+/// ```
+pub use moku_macros::unmatched;
+
+/// Set the description of a state, returned by the `description` function of the generated
+/// `State` enum.
+///
+/// May be placed on the struct of a state or on its [`Substate`] or [`TopState`] impl, taking
+/// precedence over any doc comment on the struct, which is used as the description otherwise.
+/// The description is also carried onto the state's variant of the `State` enum as a doc comment.
+///
+/// ## Example
+/// ```
+/// #[moku::state_machine]
+/// mod blinky {
+///     use moku::*;
+///
+///     #[machine_module]
+///     pub mod machine {}
+///
+///     struct Top;
+///     impl TopState for Top {}
+///
+///     /// The LED is dark.
+///     struct Off;
+///     impl Substate<Top> for Off {}
+///
+///     struct On;
+///
+///     #[describe("The LED is lit.")]
+///     impl Substate<Top> for On {}
+/// }
+///
+/// use blinky::machine::State;
+///
+/// assert_eq!(State::Off.description(), Some("The LED is dark."));
+/// assert_eq!(State::On.description(), Some("The LED is lit."));
+/// assert_eq!(State::Top.description(), None);
+/// ```
+pub use moku_macros::describe;
+
+/// Add tags to a state, grouping states independently of the shape of the state tree.
+///
+/// May be placed on the struct of a state or on its [`Substate`] or [`TopState`] impl, any
+/// number of times. A `Tag` enum with a variant for every tag is generated in the
+/// [`machine_module`], along with:
+/// - `State::tags`, returning the tags of a state
+/// - `Machine::in_tag`, checking if any active state of the machine has a tag
+///
+/// ## Example
+/// ```
+/// #[moku::state_machine]
+/// mod blinky {
+///     use moku::*;
+///
+///     #[machine_module]
+///     pub mod machine {}
+///     use machine::State;
+///
+///     pub struct Top;
+///     impl TopState for Top {
+///         fn init(&mut self) -> impl Into<Next<Self::State>> {
+///             State::Blinking
+///         }
+///     }
+///
+///     #[tag(UserVisible)]
+///     struct Blinking;
+///     impl Substate<Top> for Blinking {}
+///
+///     #[tag(Safety, UserVisible)]
+///     struct Fault;
+///     impl Substate<Top> for Fault {}
+/// }
+///
+/// use blinky::machine::{Builder, State, Tag};
+/// use moku::*;
+///
+/// assert_eq!(State::Fault.tags(), [Tag::Safety, Tag::UserVisible]);
+///
+/// let mut machine = Builder::new(blinky::Top).build();
+/// assert!(machine.in_tag(Tag::UserVisible));
+/// assert!(!machine.in_tag(Tag::Safety));
+///
+/// machine.transition(State::Fault);
+/// assert!(machine.in_tag(Tag::Safety));
+/// ```
+pub use moku_macros::tag;
+
+/// Declare the states that a [`Substate`] may transition to.
+///
+/// The [`Substate::State`] of the attributed impl becomes a narrowed enum in the
+/// [`machine_module`], named after the state with a `Target` suffix, e.g. `LedOnTarget`, with a
+/// variant for each declared state. Returning an undeclared state from a hook or [`on`] function
+/// is then a compile error. `#[moku::transitions()]` declares that a state never transitions.
+///
+/// This includes `init`, so a state that enters an initial substate must declare it as well.
+///
+/// The declared transitions are also returned by the `transitions` function of the generated
+/// `State` enum, and are drawn by `moku-cli`.
+///
+/// ## Example
+/// ```
+/// #[moku::state_machine]
+/// mod blinky {
+///     use moku::*;
+///
+///     #[machine_module]
+///     pub mod machine {}
+///
+///     #[derive(StateMachineEvent)]
+///     pub enum Event {
+///         Toggle,
+///     }
+///
+///     struct Top;
+///     impl TopState for Top {}
+///
+///     struct LedOn;
+///
+///     #[transitions(LedOff)]
+///     impl Substate<Top> for LedOn {
+///         #[on(Event::Toggle)]
+///         fn toggle() -> Self::State {
+///             // `Self::State::Disabled` would not compile
+///             Self::State::LedOff
+///         }
+///     }
+///
+///     struct LedOff;
+///     impl Substate<Top> for LedOff {}
+///
+///     struct Disabled;
+///     impl Substate<Top> for Disabled {}
+/// }
+///
+/// use blinky::machine::State;
+///
+/// assert_eq!(State::LedOn.transitions(), Some([State::LedOff].as_slice()));
+/// assert_eq!(State::LedOff.transitions(), None);
+/// ```
+pub use moku_macros::transitions;
+
+/// Nest a [`StateMachine`], stored in a field of a [`Substate`], within that state.
+///
+/// Takes the name of the field. The attributed state then:
+/// - updates the submachine before its own `update`, which can check the submachine's state or
+///   drain its outputs to trigger a transition when it completes,
+/// - updates the submachine from the top down after its own `top_down_update`, unless that
+///   triggers a transition,
+/// - shuts the submachine down with [`StateMachine::shutdown`] before its own `exit`,
+/// - forwards the events that it defers to the submachine, converted with `TryFrom<&Event>`.
+///   Events that the submachine handles are reported as [`Response::Handled`], and those that
+///   can't be converted or that it doesn't handle are deferred to the superstate.
+///
+/// The submachine is built by the state's `enter`. Its [`TopState::Resources`] and
+/// [`TopState::Input`] are `()` unless given as expressions of the state's `ctx` and `input`,
+/// e.g. `#[submachine(inner, resources = &mut ctx.top.log, input = input)]`.
+///
+/// ## Example
+/// ```
+/// #[moku::state_machine]
+/// mod blink {
+///     use moku::*;
+///
+///     #[machine_module]
+///     pub mod machine {}
+///     use machine::State;
+///
+///     #[derive(StateMachineEvent)]
+///     pub enum Event {
+///         Toggle,
+///     }
+///
+///     pub struct Top;
+///     impl TopState for Top {
+///         fn init(&mut self) -> impl Into<Next<Self::State>> {
+///             State::Off
+///         }
+///     }
+///
+///     struct Off;
+///     impl Substate<Top> for Off {
+///         #[on(Event::Toggle)]
+///         fn toggle() -> State {
+///             State::On
+///         }
+///     }
+///
+///     pub struct On;
+///     impl Substate<Top> for On {}
+/// }
+///
+/// #[moku::state_machine]
+/// mod device {
+///     use moku::*;
+///
+///     use super::blink;
+///
+///     #[machine_module]
+///     pub mod machine {}
+///     use machine::State;
+///
+///     #[derive(StateMachineEvent)]
+///     pub enum Event {
+///         Toggle,
+///         Reset,
+///     }
+///
+///     impl TryFrom<&Event> for blink::Event {
+///         type Error = ();
+///
+///         fn try_from(event: &Event) -> Result<Self, ()> {
+///             match event {
+///                 Event::Toggle => Ok(blink::Event::Toggle),
+///                 Event::Reset => Err(()),
+///             }
+///         }
+///     }
+///
+///     pub struct Top;
+///     impl TopState for Top {
+///         fn init(&mut self) -> impl Into<Next<Self::State>> {
+///             State::Blinking
+///         }
+///     }
+///
+///     struct Blinking {
+///         blink: blink::machine::Machine,
+///     }
+///
+///     #[submachine(blink)]
+///     impl Substate<Top> for Blinking {
+///         fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+///             Self {
+///                 blink: blink::machine::Builder::new(blink::Top).build(),
+///             }
+///         }
+///
+///         fn update(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+///             (self.blink.state() == blink::machine::State::On).then_some(State::Done)
+///         }
+///     }
+///
+///     struct Done;
+///     impl Substate<Top> for Done {}
+/// }
+///
+/// fn main() {
+///     use device::{machine::{Builder, State}, Event, Top};
+///     use moku::*;
+///
+///     let mut machine = Builder::new(Top).build();
+///     assert_eq!(machine.handle_event(&Event::Reset), EventOutcome::Unhandled);
+///     assert_eq!(
+///         machine.handle_event(&Event::Toggle),
+///         EventOutcome::Handled { state: State::Blinking }
+///     );
+///
+///     machine.update();
+///     assert_eq!(machine.state(), State::Done);
+/// }
+/// ```
+pub use moku_macros::submachine;
+
+/// Adds the states, events, and transitions of an [SCXML](https://www.w3.org/TR/scxml/) chart to
+/// a [`state_machine`] module.
+///
+/// Must be invoked as an item within the module, at most once. Like [`include_str`], a relative
+/// path is resolved against the directory of the file in which the macro is invoked. If no file
+/// is found there, the path is resolved against the directory containing the invoking crate's
+/// `Cargo.toml`.
+///
+/// The chart owns the shape of the machine, while the module owns its behavior. States, hooks,
+/// and data are written in the module as usual, and the chart fills in the rest:
+/// - a unit struct and [`Substate`] impl for every `<state>` and `<final>` element that the
+///   module doesn't define
+/// - a `Top` struct and [`TopState`] impl, acting as the `<scxml>` element, if the module doesn't
+///   implement [`TopState`]
+/// - an `Event` enum implementing [`StateMachineEvent`] with a variant for every event name, if
+///   the module doesn't define `Event`
+///
+/// State ids and event names are converted to `UpperCamel` case, with `.` treated as a word
+/// separator. As in SCXML, ids are case-sensitive, so every transition target and initial state
+/// must match a state id exactly. Each [`Substate`] impl must name the superstate that the state
+/// has in the chart. The chart's transitions are added to the impls of the states:
+/// - `initial` attributes and `<initial>` elements become [`Substate::init`] (or
+///   [`TopState::init`]), defaulting to the first child state
+/// - transitions with an `event` become [`on`] handlers, matched after those of the impl, where a
+///   transition without a `target` drops the event
+/// - the first transition without an `event` of an atomic state, or else of its nearest ancestor
+///   with one, becomes [`Substate::init`] of the atomic state, so that it is taken as soon as the
+///   state is entered
+///
+/// An impl that defines `init` or `handle_event` itself takes no transitions from the chart for
+/// that hook. Transitions to the source state or one of its ancestors are made as exact
+/// transitions. `cond` attributes, `<parallel>` and `<history>` elements, and eventless
+/// transitions that loop forever are not supported. Executable content is ignored.
+///
+/// ## Example
+/// For some `door.scxml`:
+/// ```xml
+/// <scxml xmlns="http://www.w3.org/2005/07/scxml" version="1.0" name="door" initial="Closed">
+///   <state id="Opened">
+///     <transition event="close" target="Closed"/>
+///   </state>
+///   <state id="Closed" initial="Unlocked">
+///     <transition event="open" target="Opened"/>
+///     <state id="Unlocked">
+///       <transition event="lock" target="Locked"/>
+///     </state>
+///     <state id="Locked">
+///       <transition event="unlock" target="Unlocked"/>
+///       <transition event="open"/>
+///     </state>
+///   </state>
+/// </scxml>
+/// ```
+/// ```
+/// #[moku::state_machine]
+/// mod door {
+///     use moku::*;
+///
+///     #[machine_module]
+///     pub mod machine {}
+///
+///     include_scxml!("examples/door.scxml");
+///
+///     pub struct Locked {
+///         pub attempts: u8,
+///     }
+///
+///     impl Substate<Closed> for Locked {
+///         fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+///             Self { attempts: 0 }
+///         }
+///
+///         #[on(Event::Open)]
+///         fn rattle(&mut self) {
+///             self.attempts += 1;
+///         }
+///     }
+/// }
+///
+/// use moku::*;
+/// use door::{machine::{Builder, State, STATE_CHART}, Event, Locked, Top};
+///
+/// assert_eq!(
+///     STATE_CHART,
+///     "Top
+/// ├─ Opened
+/// └─ Closed
+///    ├─ Unlocked
+///    └─ Locked"
+/// );
+///
+/// let mut machine = Builder::new(Top).build();
+/// assert!(matches!(machine.state(), State::Unlocked));
+///
+/// machine.handle_event(&Event::Lock);
+/// machine.handle_event(&Event::Open);
+/// assert!(matches!(machine.state(), State::Locked));
+///
+/// let locked: &Locked = machine.state_ref().unwrap();
+/// assert_eq!(locked.attempts, 1);
+/// ```
+pub use moku_macros::include_scxml;
+
+/// Re-export of the [`arbitrary`](https://docs.rs/arbitrary) crate.
+///
+/// With the `arbitrary` feature enabled, every autogenerated `State` implements
+/// [`arbitrary::Arbitrary`], as does [`testing::Step`] for states and events that implement it.
+/// This allows sequences of steps to be generated by `cargo fuzz` and checked with
+/// [`testing::Explorer::replay`].
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+#[cfg(feature = "arbitrary")]
+pub use arbitrary;
+
+/// Represents either no action or some type of transition to new state.
+///
+/// Return type of multiple [`Substate`] methods.
+pub enum Next<T: StateEnum> {
+    /// No transition should be taken, stay in the current active state.
+    None,
+
+    /// A transition should be taken to the target state.
+    /// See [`StateMachine::transition`] for transition semantics.
+    Target(T),
+
+    /// A transition should be taken to exactly the target state.
+    /// See [`StateMachine::exact_transition`] for exact transition semantics.
+    ExactTarget(T),
+}
+
+impl<T: StateEnum> From<T> for Next<T> {
+    fn from(value: T) -> Self {
+        Next::Target(value)
+    }
+}
+
+impl<T: StateEnum> From<Option<T>> for Next<T> {
+    fn from(value: Option<T>) -> Self {
+        match value {
+            None => Next::None,
+            Some(target) => Next::Target(target),
+        }
+    }
+}
+
+impl<T: StateEnum> From<()> for Next<T> {
+    fn from(_: ()) -> Self {
+        Next::None
+    }
+}
+
+/// A state machine event.
+///
+/// Optionally implement or [derive](macro@StateMachineEvent) this trait for a type in a
+/// [`state_machine`] module to mark it as the event type to be used by the state machine and its
+/// states.
+///
+/// If this trait is implemented for multiple types in the module, they are combined into an
+/// autogenerated `Event` enum in the [`machine_module`], with a variant named after each type.
+/// The enum implements `From` for each type, and each type implements `TryFrom<&Event>` if it
+/// implements `Clone`. The autogenerated `Machine` then has a `handle_{type}` method for each
+/// type, e.g. `handle_uart_frame` for a `UartFrame` type. None of the types may be named `Event`.
+pub trait StateMachineEvent {
+    /// Get the name of the event, used to identify it in logs.
+    ///
+    /// Defaults to the name of the event type. When derived, the name of each variant of an enum
+    /// is used instead.
+    fn name(&self) -> &str {
+        core::any::type_name::<Self>()
+    }
+}
+
+impl StateMachineEvent for () {}
+
+/// Derive [`StateMachineEvent`](trait@StateMachineEvent) for an enum or struct.
+///
+/// [`StateMachineEvent::name`] returns the name of each variant of an enum, or the name of a
+/// struct, so that events are identified by name in logs.
+///
+/// ## Example
+/// ```
+/// #[moku::state_machine]
+/// mod blinky {
+///     use moku::*;
+///
+///     #[machine_module]
+///     mod machine {}
+///
+///     #[derive(StateMachineEvent)]
+///     pub enum Event {
+///         Toggle,
+///         SetPeriod(u32),
+///     }
+///
+///     struct Top;
+///     impl TopState for Top {}
+/// }
+///
+/// use moku::StateMachineEvent;
+///
+/// assert_eq!(blinky::Event::Toggle.name(), "Toggle");
+/// assert_eq!(blinky::Event::SetPeriod(500).name(), "SetPeriod");
+/// ```
+pub use moku_macros::StateMachineEvent;
+
+/// The result of a single state handling an event.
+///
+/// Implements [`From`] for implementors of `StateEnum` and `Option<StateEnum>`
+pub enum Response<T: StateEnum> {
+    /// The transition triggered by the event.
+    /// A value of Next::None defers the handling of the event to the superstate.
+    Next(Next<T>),
+
+    /// Drop the event and stop handling it immediately.
+    Drop,
+
+    /// Handle the event without a transition and stop handling it immediately.
+    ///
+    /// Unlike [`Response::Drop`], the event is reported as handled rather than dropped, e.g. by
+    /// [`EventOutcome::into_response`] for events handled by a nested [`StateMachine`].
+    Handled,
+}
+
+impl<T: StateEnum> From<T> for Response<T> {
+    fn from(value: T) -> Self {
+        Response::Next(value.into())
+    }
+}
+
+impl<T: StateEnum> From<Option<T>> for Response<T> {
+    fn from(value: Option<T>) -> Self {
+        Response::Next(value.into())
+    }
+}
+
+impl<T: StateEnum> From<()> for Response<T> {
+    fn from(_: ()) -> Self {
+        Response::Next(Next::None)
+    }
+}
+
+impl<T: StateEnum> From<Next<T>> for Response<T> {
+    fn from(value: Next<T>) -> Self {
+        Response::Next(value)
+    }
+}
+
+/// The outcome of a state machine handling an event.
+///
+/// Returned by [`StateMachine::handle_event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventOutcome<T: StateEnum> {
+    /// The event triggered a transition.
+    Transition {
+        /// The state that triggered the transition.
+        state: T,
+
+        /// The state that the machine ended in after the transition.
+        ///
+        /// This is the requested target unless the target was already active, in which case the
+        /// machine stays in its current state, or the transition was redirected by a short circuit
+        /// or by the `init` of the target.
+        target: T,
+    },
+
+    /// The event triggered an exact transition.
+    ExactTransition {
+        /// The state that triggered the transition.
+        state: T,
+
+        /// The state that the machine ended in after the transition.
+        ///
+        /// This is the requested target unless the transition was redirected by a short circuit or
+        /// by the `init` of the target.
+        target: T,
+    },
+
+    /// The event was dropped.
+    Dropped {
+        /// The state that dropped the event.
+        state: T,
+    },
+
+    /// The event was handled without a transition.
+    Handled {
+        /// The state that handled the event.
+        state: T,
+    },
+
+    /// No state handled the event, including the [`TopState`].
+    Unhandled,
+}
+
+impl<T: StateEnum> EventOutcome<T> {
+    /// Check if any state handled the event, by triggering a transition, dropping it, or handling
+    /// it without a transition.
+    pub fn is_handled(&self) -> bool {
+        !matches!(self, Self::Unhandled)
+    }
+
+    /// Get the state that handled the event, if any.
+    pub fn state(&self) -> Option<T> {
+        match self {
+            Self::Transition { state, .. }
+            | Self::ExactTransition { state, .. }
+            | Self::Dropped { state }
+            | Self::Handled { state } => Some(*state),
+            Self::Unhandled => None,
+        }
+    }
+
+    /// Get the state that the machine ended in after the transition triggered by the event, if
+    /// any.
+    pub fn target(&self) -> Option<T> {
+        match self {
+            Self::Transition { target, .. } | Self::ExactTransition { target, .. } => Some(*target),
+            Self::Dropped { .. } | Self::Handled { .. } | Self::Unhandled => None,
+        }
+    }
+
+    /// Convert the outcome of forwarding an event to a nested [`StateMachine`] into the
+    /// [`Response`] of the state that forwarded it.
+    ///
+    /// Events handled by the nested machine are reported as [`Response::Handled`], and unhandled
+    /// events are deferred to the superstate.
+    pub fn into_response<S: StateEnum>(self) -> Response<S> {
+        if self.is_handled() {
+            Response::Handled
+        } else {
+            Response::Next(Next::None)
+        }
+    }
+}
+
+/// A flat list of all states in a state machine.
+///
+/// Autogenerated by moku in the [`machine_module`] as `State`.
+///
+/// # Example
+/// For some machine:
+/// ```text
+/// Top
+/// ├─ Foo
+/// │  └─ Bar
+/// └─ Fizz
+///    └─ Buzz
+/// ```
+/// The [`StateEnum`] would be:
+/// ```
+/// #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// pub enum State {
+///     Top,
+///     Foo,
+///     Bar,
+///     Fizz,
+///     Buzz,
+/// }
+/// ```
+pub trait StateEnum: core::fmt::Debug + Clone + Copy + PartialEq + Eq + 'static {
+    /// Every state in the state machine, starting with the [`TopState`] and in the same
+    /// depth-first order as the state chart.
+    const ALL: &'static [Self];
+
+    /// Get the position of this state in [`StateEnum::ALL`].
+    ///
+    /// The autogenerated `State` overrides this with its discriminant.
+    fn index(self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|state| *state == self)
+            .expect("state is missing from StateEnum::ALL")
+    }
+}
+
+/// An action taken by a state machine on one of its states.
+///
+/// Reported to the [`Observer`] of a state machine in the order that the actions are taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action<T: StateEnum> {
+    /// The state is being entered.
+    Enter(T),
+
+    /// The state is being exited.
+    Exit(T),
+
+    /// The state is being initialized, either after entry or as the target of a transition.
+    Init(T),
+
+    /// The state is being updated.
+    Update(T),
+
+    /// The state is being top-down updated.
+    TopDownUpdate(T),
+
+    /// The state is handling an event.
+    HandleEvent(T),
+}
+
+/// An action taken by a state machine as a whole, rather than on one of its states.
+///
+/// Reported to the [`Observer`] of a state machine, and kept by a [`History`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineAction<T: StateEnum> {
+    /// An update was started in the state.
+    Update(T),
+
+    /// A top-down update was started in the state.
+    TopDownUpdate(T),
+
+    /// A transition was started, e.g. by [`StateMachine::transition`], an event, or an update.
+    Transition {
+        /// The state that the machine was in.
+        source: T,
+
+        /// The target of the transition.
+        target: T,
+
+        /// Whether the transition is exact.
+        exact: bool,
+    },
+
+    /// A state triggered a short circuit transition while being entered or exited.
+    ShortCircuit {
+        /// The state that triggered the transition.
+        state: T,
+
+        /// The target of the transition.
+        target: T,
+
+        /// Whether the transition is exact.
+        exact: bool,
+    },
+
+    /// An event was handled by the state machine, including any transition that it triggered.
+    Event(EventOutcome<T>),
+}
+
+impl<T: StateEnum> core::fmt::Display for MachineAction<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Self::Update(state) => write!(f, "Update in {state:?}"),
+            Self::TopDownUpdate(state) => write!(f, "Top-down update in {state:?}"),
+            Self::Transition {
+                source,
+                target,
+                exact,
+            } => write!(
+                f,
+                "{} from {source:?} to {target:?}",
+                if exact {
+                    "Exact transition"
+                } else {
+                    "Transition"
+                }
+            ),
+            Self::ShortCircuit {
+                state,
+                target,
+                exact,
+            } => write!(
+                f,
+                "{state:?} triggered short circuit {}transition to {target:?}",
+                if exact { "exact " } else { "" }
+            ),
+            Self::Event(EventOutcome::Transition { state, target }) => {
+                write!(f, "{state:?} handled event with transition to {target:?}")
+            }
+            Self::Event(EventOutcome::ExactTransition { state, target }) => {
+                write!(
+                    f,
+                    "{state:?} handled event with exact transition to {target:?}"
+                )
+            }
+            Self::Event(EventOutcome::Dropped { state }) => write!(f, "{state:?} dropped event"),
+            Self::Event(EventOutcome::Handled { state }) => write!(f, "{state:?} handled event"),
+            Self::Event(EventOutcome::Unhandled) => f.write_str("Event unhandled"),
+        }
+    }
+}
+
+/// An observer of the [`Action`]s and [`MachineAction`]s taken by a state machine.
+///
+/// Set with the `observer` method of the autogenerated `Builder`, after which it can be accessed
+/// with the `observer` and `observer_mut` methods of the autogenerated `Machine`.
+///
+/// Implemented by `()`, which ignores all actions and is the default observer of every machine,
+/// and by [`History`].
+///
+/// # Example
+/// ```
+/// #[moku::state_machine]
+/// mod example {
+///     #[moku::machine_module]
+///     pub mod machine {}
+///
+///     use machine::State;
+///
+///     pub struct Top;
+///     impl moku::TopState for Top {}
+///
+///     pub struct Foo;
+///     impl moku::Substate<Top> for Foo {}
+/// }
+///
+/// use moku::*;
+/// use example::machine::{Builder, State};
+///
+/// #[derive(Default)]
+/// struct EnterCounter(usize);
+///
+/// impl Observer<State> for EnterCounter {
+///     fn on_action(&mut self, action: Action<State>) {
+///         if let Action::Enter(_) = action {
+///             self.0 += 1;
+///         }
+///     }
+/// }
+///
+/// let mut machine = Builder::new(example::Top)
+///     .observer(EnterCounter::default())
+///     .build();
+///
+/// machine.transition(State::Foo);
+/// assert_eq!(machine.observer().0, 1);
+/// ```
+pub trait Observer<T: StateEnum> {
+    /// Called immediately before the state machine takes an action.
+    #[allow(unused_variables)]
+    fn on_action(&mut self, action: Action<T>) {}
+
+    /// Called immediately after the state machine takes an action.
+    #[allow(unused_variables)]
+    fn on_action_complete(&mut self, action: Action<T>) {}
+
+    /// Called after the state machine handles an event, including any transition that it
+    /// triggered.
+    #[allow(unused_variables)]
+    fn on_event_outcome(&mut self, outcome: EventOutcome<T>) {}
+
+    /// Called when the state machine as a whole takes an action, e.g. starting a transition.
+    ///
+    /// Handled events are reported here as [`MachineAction::Event`], after
+    /// [`Observer::on_event_outcome`].
+    #[allow(unused_variables)]
+    fn on_machine_action(&mut self, action: MachineAction<T>) {}
+}
+
+impl<T: StateEnum> Observer<T> for () {}
+
+/// A monotonic clock used by a state machine to measure time, e.g. for
+/// [`StateContext::time_in_state`].
+///
+/// Wraps a function that returns the time elapsed since some fixed but arbitrary epoch. Set with
+/// the `clock` method of the autogenerated `Builder`.
+///
+/// With the `std` feature, the default clock is based on [`std::time::Instant`]. Otherwise, the
+/// default clock always reads zero, so a clock must be set for time to be measured.
+///
+/// # Example
+/// ```
+/// use core::sync::atomic::{AtomicU64, Ordering};
+/// use core::time::Duration;
+///
+/// static TICKS: AtomicU64 = AtomicU64::new(0);
+///
+/// let clock = moku::Clock::new(|| Duration::from_millis(TICKS.load(Ordering::Relaxed)));
+///
+/// TICKS.store(5, Ordering::Relaxed);
+/// assert_eq!(clock.now(), Duration::from_millis(5));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Clock(fn() -> core::time::Duration);
+
+impl Clock {
+    /// Make a new [`Clock`] from a function that returns the time elapsed since some fixed but
+    /// arbitrary epoch.
+    pub const fn new(now: fn() -> core::time::Duration) -> Self {
+        Self(now)
+    }
+
+    /// Read the time elapsed since the epoch of this clock.
+    pub fn now(&self) -> core::time::Duration {
+        (self.0)()
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        #[cfg(feature = "std")]
+        return Self(|| {
+            static EPOCH: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+            EPOCH.get_or_init(std::time::Instant::now).elapsed()
+        });
+
+        #[cfg(not(feature = "std"))]
+        return Self(|| core::time::Duration::ZERO);
+    }
+}
+
+/// The number of calls to one of the hooks of a state, e.g. [`Substate::enter`], and the total
+/// time spent in them.
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HookMetrics {
+    /// The number of calls to the hook.
+    pub calls: u64,
+
+    /// The total time spent in the hook, as measured by the [`Clock`] of the state machine.
+    pub time: core::time::Duration,
+}
+
+/// The counters and hook timings of a single state.
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateMetrics {
+    /// Calls to `enter`, i.e. entries into the state.
+    pub enter: HookMetrics,
+
+    /// Calls to `init`.
+    pub init: HookMetrics,
+
+    /// Calls to `update`.
+    pub update: HookMetrics,
+
+    /// Calls to `top_down_update`.
+    pub top_down_update: HookMetrics,
+
+    /// Calls to `exit`, i.e. exits from the state.
+    pub exit: HookMetrics,
+
+    /// Calls to `handle_event`, including those that deferred the event to the superstate.
+    pub handle_event: HookMetrics,
+
+    /// The number of events handled by the state, by triggering a transition, dropping them, or
+    /// handling them without a transition with [`Response::Handled`].
+    pub events_handled: u64,
+
+    /// The number of events dropped by the state, which are also counted in
+    /// [`StateMetrics::events_handled`].
+    pub events_dropped: u64,
+}
+
+#[cfg(feature = "metrics")]
+impl StateMetrics {
+    /// Get the total time spent in all of the hooks of the state.
+    pub fn total_time(&self) -> core::time::Duration {
+        self.enter.time
+            + self.init.time
+            + self.update.time
+            + self.top_down_update.time
+            + self.exit.time
+            + self.handle_event.time
+    }
+}
+
+/// The per-state metrics collected by a state machine with the `metrics` feature.
+///
+/// Stored in a fixed-size array indexed by [`StateEnum::index`], so no allocation is needed.
+/// Autogenerated by moku in the [`machine_module`] as `Metrics`, and accessed with the `metrics`
+/// method of the autogenerated `Machine`.
+///
+/// # Example
+/// ```
+/// #[moku::state_machine]
+/// mod example {
+///     #[moku::machine_module]
+///     pub mod machine {}
+///
+///     use machine::State;
+///
+///     pub struct Top;
+///     impl moku::TopState for Top {}
+///
+///     pub struct Foo;
+///     impl moku::Substate<Top> for Foo {}
+/// }
+///
+/// use moku::*;
+/// use example::machine::{Builder, State};
+///
+/// let mut machine = Builder::new(example::Top).build();
+/// machine.transition(State::Foo);
+/// machine.update();
+///
+/// let metrics = machine.metrics();
+/// assert_eq!(metrics.get(State::Foo).enter.calls, 1);
+/// assert_eq!(metrics.get(State::Top).update.calls, 1);
+///
+/// // find the state that spent the most time in its hooks
+/// let (hottest, _) = metrics
+///     .iter()
+///     .max_by_key(|(_, metrics)| metrics.total_time())
+///     .unwrap();
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metrics<T: StateEnum, const N: usize> {
+    states: [StateMetrics; N],
+    phantom: core::marker::PhantomData<T>,
+}
+
+#[cfg(feature = "metrics")]
+impl<T: StateEnum, const N: usize> Metrics<T, N> {
+    /// Get the metrics of a state.
+    pub fn get(&self, state: T) -> &StateMetrics {
+        &self.states[state.index()]
+    }
+
+    /// Get the metrics of a state mutably.
+    pub fn get_mut(&mut self, state: T) -> &mut StateMetrics {
+        &mut self.states[state.index()]
+    }
+
+    /// Iterate over every state and its metrics, in the order of [`StateEnum::ALL`].
+    pub fn iter(&self) -> impl Iterator<Item = (T, &StateMetrics)> + '_ {
+        T::ALL.iter().copied().zip(self.states.iter())
+    }
+
+    /// Reset the metrics of every state.
+    pub fn reset(&mut self) {
+        self.states = [StateMetrics::default(); N];
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl<T: StateEnum, const N: usize> Default for Metrics<T, N> {
+    fn default() -> Self {
+        Self {
+            states: [StateMetrics::default(); N],
+            phantom: core::marker::PhantomData,
+        }
+    }
+}
+
+/// An [`Observer`] that keeps the last `N` [`MachineAction`]s taken by a state machine, for
+/// finding out how the machine got to its current state after the fact.
+///
+/// Actions are kept in a fixed-size ring buffer, so no allocation is needed and the oldest
+/// actions are overwritten once it is full. Set as the observer with the `observer` method of the
+/// autogenerated `Builder`, after which it can be accessed with the `history` method of the
+/// autogenerated `Machine`.
+///
+/// A [`History`] implements `Copy` so that it can be stashed away, e.g. for a panic handler, and
+/// implements `Display` to print one action per line, oldest first.
+///
+/// # Example
+/// ```
+/// #[moku::state_machine]
+/// mod example {
+///     #[moku::machine_module]
+///     pub mod machine {}
+///
+///     use machine::State;
+///
+///     pub struct Top;
+///     impl moku::TopState for Top {}
+///
+///     pub struct Foo;
+///     impl moku::Substate<Top> for Foo {}
+///
+///     pub struct Bar;
+///     impl moku::Substate<Top> for Bar {}
+/// }
+///
+/// use moku::*;
+/// use example::machine::{Builder, State};
+///
+/// let mut machine = Builder::new(example::Top)
+///     .observer(History::<_, 2>::default())
+///     .build();
+///
+/// machine.transition(State::Foo);
+/// machine.update();
+/// machine.transition(State::Bar);
+///
+/// assert_eq!(
+///     machine.history().to_string(),
+///     "Update in Foo\nTransition from Foo to Bar\n"
+/// );
+/// assert_eq!(machine.history().total(), 3);
+/// ```
+#[derive(Clone, Copy)]
+pub struct History<T: StateEnum, const N: usize = 32> {
+    actions: [Option<MachineAction<T>>; N],
+    next: usize,
+    total: u64,
+}
+
+impl<T: StateEnum> History<T> {
+    /// Make a new, empty [`History`] with the default capacity of 32 actions.
+    ///
+    /// Use [`History::default`] to make a [`History`] with a different capacity, e.g.
+    /// `History::<_, 256>::default()`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T: StateEnum, const N: usize> History<T, N> {
+    /// Remove all kept actions.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
 
-    /// A transition should be taken to the target state.
-    /// See [`StateMachine::transition`] for transition semantics.
-    Target(T),
+    /// Get the number of kept actions, which is at most `N`.
+    pub fn len(&self) -> usize {
+        self.actions.iter().flatten().count()
+    }
 
-    /// A transition should be taken to exactly the target state.
-    /// See [`StateMachine::exact_transition`] for exact transition semantics.
-    ExactTarget(T),
+    /// Check if no actions are kept.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the total number of actions taken since the [`History`] was made or cleared,
+    /// including those that have been overwritten.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Get the most recent action, if any.
+    pub fn last(&self) -> Option<MachineAction<T>> {
+        self.next
+            .checked_sub(1)
+            .or_else(|| N.checked_sub(1))
+            .and_then(|index| self.actions[index])
+    }
+
+    /// Iterate over the kept actions, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = MachineAction<T>> + '_ {
+        let (newer, older) = self.actions.split_at(self.next);
+        older.iter().chain(newer).flatten().copied()
+    }
 }
 
-impl<T: StateEnum> From<T> for Next<T> {
-    fn from(value: T) -> Self {
-        Next::Target(value)
+impl<T: StateEnum, const N: usize> Default for History<T, N> {
+    fn default() -> Self {
+        Self {
+            actions: [None; N],
+            next: 0,
+            total: 0,
+        }
     }
 }
 
-impl<T: StateEnum> From<Option<T>> for Next<T> {
-    fn from(value: Option<T>) -> Self {
-        match value {
-            None => Next::None,
-            Some(target) => Next::Target(target),
+impl<T: StateEnum, const N: usize> Observer<T> for History<T, N> {
+    fn on_machine_action(&mut self, action: MachineAction<T>) {
+        if N > 0 {
+            self.actions[self.next] = Some(action);
+            self.next = (self.next + 1) % N;
         }
+        self.total += 1;
     }
 }
 
-impl<T: StateEnum> From<()> for Next<T> {
-    fn from(_: ()) -> Self {
-        Next::None
+impl<T: StateEnum, const N: usize> core::fmt::Debug for History<T, N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
     }
 }
 
-/// A state machine event.
+impl<T: StateEnum, const N: usize> core::fmt::Display for History<T, N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.iter().try_for_each(|action| writeln!(f, "{action}"))
+    }
+}
+
+/// A queue that states push their [`TopState::Output`]s to through `ctx.outputs`, and that the
+/// caller drains them from with [`StateMachine::drain_outputs`].
 ///
-/// Optionally implement this trait for a single type in a [`state_machine`] module
-/// to mark it as the event type to be used by the state machine and its states.
-pub trait StateMachineEvent {}
+/// Implemented by [`Outputs`], which every state machine keeps its outputs in. States see the
+/// queue as `dyn OutputQueue`, so that their contexts don't depend on its capacity.
+pub trait OutputQueue<T> {
+    /// Push an output to the back of the queue, or give it back if the queue is full.
+    fn push(&mut self, output: T) -> Result<(), T>;
 
-impl StateMachineEvent for () {}
+    /// Remove the oldest output from the front of the queue, if any.
+    fn pop(&mut self) -> Option<T>;
 
-/// The result of a single state handling an event.
-///
-/// Implements [`From`] for implementors of `StateEnum` and `Option<StateEnum>`
-pub enum Response<T: StateEnum> {
-    /// The transition triggered by the event.
-    /// A value of Next::None defers the handling of the event to the superstate.
-    Next(Next<T>),
+    /// Get the output at an index, counting from the oldest, if any.
+    fn get(&self, index: usize) -> Option<&T>;
 
-    /// Drop the event and stop handling it immediately.
-    Drop,
+    /// Get the number of outputs in the queue.
+    fn len(&self) -> usize;
+
+    /// Get the maximum number of outputs that the queue can hold.
+    fn capacity(&self) -> usize;
+
+    /// Check if the queue holds no outputs.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Check if the queue can't hold any more outputs.
+    fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
 }
 
-impl<T: StateEnum> From<T> for Response<T> {
-    fn from(value: T) -> Self {
-        Response::Next(value.into())
+impl<T> dyn OutputQueue<T> + '_ {
+    /// Iterate over the outputs in the queue, oldest first.
+    pub fn iter(&self) -> OutputIter<'_, T> {
+        OutputIter {
+            queue: self,
+            index: 0,
+        }
+    }
+
+    /// Remove and iterate over the outputs in the queue, oldest first.
+    ///
+    /// Outputs that aren't iterated over are removed when the iterator is dropped.
+    pub fn drain(&mut self) -> DrainOutputs<'_, T> {
+        DrainOutputs { queue: self }
     }
 }
 
-impl<T: StateEnum> From<Option<T>> for Response<T> {
-    fn from(value: Option<T>) -> Self {
-        Response::Next(value.into())
+/// Iterator over the outputs in an [`OutputQueue`], oldest first.
+///
+/// See [`OutputQueue::iter`](trait.OutputQueue.html#method.iter).
+pub struct OutputIter<'a, T> {
+    queue: &'a dyn OutputQueue<T>,
+    index: usize,
+}
+
+impl<'a, T> Iterator for OutputIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let output = self.queue.get(self.index)?;
+        self.index += 1;
+        Some(output)
     }
 }
 
-impl<T: StateEnum> From<()> for Response<T> {
-    fn from(_: ()) -> Self {
-        Response::Next(Next::None)
+/// Iterator that removes the outputs from an [`OutputQueue`], oldest first.
+///
+/// See [`StateMachine::drain_outputs`].
+pub struct DrainOutputs<'a, T> {
+    queue: &'a mut dyn OutputQueue<T>,
+}
+
+impl<T> Iterator for DrainOutputs<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.queue.pop()
     }
 }
 
-impl<T: StateEnum> From<Next<T>> for Response<T> {
-    fn from(value: Next<T>) -> Self {
-        Response::Next(value)
+impl<T> Drop for DrainOutputs<'_, T> {
+    fn drop(&mut self) {
+        while self.queue.pop().is_some() {}
     }
 }
 
-/// A flat list of all states in a state machine.
+/// An [`OutputQueue`] that holds up to `N` outputs.
 ///
-/// Autogenerated by moku in the [`machine_module`] as `State`.
+/// Outputs are kept in a fixed-size ring buffer, so no allocation is needed, and outputs pushed
+/// while it is full are given back rather than overwriting older ones. Every state machine keeps
+/// its outputs in one, with the capacity set by the `output_capacity` method of the autogenerated
+/// `Builder`.
 ///
 /// # Example
-/// For some machine:
-/// ```text
-/// Top
-/// ├─ Foo
-/// │  └─ Bar
-/// └─ Fizz
-///    └─ Buzz
-/// ```
-/// The [`StateEnum`] would be:
 /// ```
-/// #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-/// pub enum State {
-///     Top,
-///     Foo,
-///     Bar,
-///     Fizz,
-///     Buzz,
-/// }
+/// use moku::{OutputQueue, Outputs};
+///
+/// let mut outputs = Outputs::<_, 2>::new();
+/// assert_eq!(outputs.push(1), Ok(()));
+/// assert_eq!(outputs.push(2), Ok(()));
+/// assert_eq!(outputs.push(3), Err(3));
+/// assert_eq!(outputs.pop(), Some(1));
+/// assert_eq!(outputs.len(), 1);
 /// ```
-pub trait StateEnum: core::fmt::Debug + Clone + Copy + PartialEq + Eq {}
+#[derive(Clone)]
+pub struct Outputs<T, const N: usize = 16> {
+    outputs: [Option<T>; N],
+    front: usize,
+    len: usize,
+}
+
+impl<T, const N: usize> Outputs<T, N> {
+    /// Make a new, empty [`Outputs`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T, const N: usize> Default for Outputs<T, N> {
+    fn default() -> Self {
+        Self {
+            outputs: core::array::from_fn(|_| None),
+            front: 0,
+            len: 0,
+        }
+    }
+}
+
+impl<T, const N: usize> OutputQueue<T> for Outputs<T, N> {
+    fn push(&mut self, output: T) -> Result<(), T> {
+        if self.len == N {
+            return Err(output);
+        }
+        self.outputs[(self.front + self.len) % N] = Some(output);
+        self.len += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        let output = self.outputs.get_mut(self.front)?.take()?;
+        self.front = (self.front + 1) % N;
+        self.len -= 1;
+        Some(output)
+    }
+
+    fn get(&self, index: usize) -> Option<&T> {
+        if index < self.len {
+            self.outputs[(self.front + index) % N].as_ref()
+        } else {
+            None
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<T: core::fmt::Debug, const N: usize> core::fmt::Debug for Outputs<T, N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list()
+            .entries((0..self.len).filter_map(|index| self.get(index)))
+            .finish()
+    }
+}
 
 /// A state machine.
 pub trait StateMachine<T, U, V>
@@ -247,7 +1559,38 @@ where
     /// ```
     /// `Top` being the nearest common ancestor of the starting state, `Bar`, and the new state,
     /// `Buzz`, so the update continues from `Top`.
-    fn update(&mut self);
+    fn update(&mut self)
+    where
+        V: TopState<Resources = (), Input = ()>,
+    {
+        self.update_with_input(&mut (), &())
+    }
+
+    /// Update the state machine, passing [`TopState::Resources`] to the contexts of its states.
+    ///
+    /// See [`StateMachine::update`].
+    fn update_with(&mut self, resources: &mut V::Resources)
+    where
+        V: TopState<Input = ()>,
+    {
+        self.update_with_input(resources, &())
+    }
+
+    /// Update the state machine, passing [`TopState::Input`] to the `update` hook of each state.
+    ///
+    /// See [`StateMachine::update`].
+    fn update_input(&mut self, input: &V::Input)
+    where
+        V: TopState<Resources = ()>,
+    {
+        self.update_with_input(&mut (), input)
+    }
+
+    /// Update the state machine, passing [`TopState::Resources`] to the contexts of its states
+    /// and [`TopState::Input`] to the `update` hook of each state.
+    ///
+    /// See [`StateMachine::update`].
+    fn update_with_input(&mut self, resources: &mut V::Resources, input: &V::Input);
 
     /// Top-down update the state machine.
     ///
@@ -320,7 +1663,40 @@ where
     /// ```
     /// `Top` being the nearest common ancestor of the starting state, `Foo`, and the new state,
     /// `Fizz`, so the top-down update continues from the first active descendent of `Top`: `Fizz`.
-    fn top_down_update(&mut self);
+    fn top_down_update(&mut self)
+    where
+        V: TopState<Resources = (), Input = ()>,
+    {
+        self.top_down_update_with_input(&mut (), &())
+    }
+
+    /// Top-down update the state machine, passing [`TopState::Resources`] to the contexts of its
+    /// states.
+    ///
+    /// See [`StateMachine::top_down_update`].
+    fn top_down_update_with(&mut self, resources: &mut V::Resources)
+    where
+        V: TopState<Input = ()>,
+    {
+        self.top_down_update_with_input(resources, &())
+    }
+
+    /// Top-down update the state machine, passing [`TopState::Input`] to the `top_down_update`
+    /// hook of each state.
+    ///
+    /// See [`StateMachine::top_down_update`].
+    fn top_down_update_input(&mut self, input: &V::Input)
+    where
+        V: TopState<Resources = ()>,
+    {
+        self.top_down_update_with_input(&mut (), input)
+    }
+
+    /// Top-down update the state machine, passing [`TopState::Resources`] to the contexts of its
+    /// states and [`TopState::Input`] to the `top_down_update` hook of each state.
+    ///
+    /// See [`StateMachine::top_down_update`].
+    fn top_down_update_with_input(&mut self, resources: &mut V::Resources, input: &V::Input);
 
     /// Attempt to transition the [`StateMachine`] to the target state.
     ///
@@ -360,7 +1736,18 @@ where
     /// machine.transition(State::Top);
     /// assert!(matches!(machine.state(), State::Bar));
     /// ```
-    fn transition(&mut self, target: T);
+    fn transition(&mut self, target: T)
+    where
+        V: TopState<Resources = ()>,
+    {
+        self.transition_with(&mut (), target)
+    }
+
+    /// Attempt to transition the [`StateMachine`] to the target state, passing
+    /// [`TopState::Resources`] to the contexts of its states.
+    ///
+    /// See [`StateMachine::transition`].
+    fn transition_with(&mut self, resources: &mut V::Resources, target: T);
 
     /// Attempt to transition the [`StateMachine`] to the target state regardless of currently
     /// active states.
@@ -403,7 +1790,70 @@ where
     /// machine.exact_transition(State::Top);
     /// assert!(matches!(machine.state(), State::Top));
     /// ```
-    fn exact_transition(&mut self, target: T);
+    fn exact_transition(&mut self, target: T)
+    where
+        V: TopState<Resources = ()>,
+    {
+        self.exact_transition_with(&mut (), target)
+    }
+
+    /// Attempt to transition the [`StateMachine`] to the target state regardless of currently
+    /// active states, passing [`TopState::Resources`] to the contexts of its states.
+    ///
+    /// See [`StateMachine::exact_transition`].
+    fn exact_transition_with(&mut self, resources: &mut V::Resources, target: T);
+
+    /// Exit every active state below the [`TopState`], deepest first, leaving the
+    /// [`StateMachine`] in its [`TopState`].
+    ///
+    /// Unlike an exact transition to the [`TopState`], [`TopState::init`] is not called, and short
+    /// circuit transitions from [`Substate::exit`] are ignored. Useful for shutting down a
+    /// [`StateMachine`] nested within a state of another when that state exits.
+    ///
+    /// # Example
+    /// ```
+    /// # #[moku::state_machine]
+    /// # mod example {
+    /// #    use moku::*;
+    /// #
+    /// #    #[machine_module]
+    /// #    pub mod machine {}
+    /// #    use machine::State;
+    /// #
+    /// #    pub struct Top;
+    /// #    impl TopState for Top {
+    /// #        fn init(&mut self) -> impl Into<Next<Self::State>> {
+    /// #            State::Bar
+    /// #        }
+    /// #    }
+    /// #
+    /// #    struct Foo;
+    /// #    impl Substate<Top> for Foo {}
+    /// #
+    /// #    struct Bar;
+    /// #    impl Substate<Foo> for Bar {}
+    /// # }
+    /// # use moku::*;
+    /// # use example::machine::{Builder, State};
+    /// # let mut machine = Builder::new(example::Top).build();
+    /// // where Top initially transitions to Bar, a substate of Foo:
+    /// assert!(matches!(machine.state(), State::Bar));
+    ///
+    /// machine.shutdown();
+    /// assert!(matches!(machine.state(), State::Top));
+    /// ```
+    fn shutdown(&mut self)
+    where
+        V: TopState<Resources = ()>,
+    {
+        self.shutdown_with(&mut ())
+    }
+
+    /// Exit every active state below the [`TopState`], passing [`TopState::Resources`] to the
+    /// contexts of the exited states.
+    ///
+    /// See [`StateMachine::shutdown`].
+    fn shutdown_with(&mut self, resources: &mut V::Resources);
 
     /// Get the current state of the [`StateMachine`].
     ///
@@ -604,8 +2054,12 @@ where
     /// If any state returns [`Response::Next`] with something other than [`Next::None`],
     /// the given transition will be completed and no further `handle_event` functions are called.
     ///
-    /// If any state returns [`Response::Drop`], event handling stops immediately and no further
-    /// `handle_event` functions are called.
+    /// If any state returns [`Response::Drop`] or [`Response::Handled`], event handling stops
+    /// immediately and no further `handle_event` functions are called.
+    ///
+    /// If no state handles the event, [`TopState::on_unhandled`] is called.
+    ///
+    /// Returns an [`EventOutcome`] describing which state handled the event and how.
     ///
     /// # Example
     /// ```
@@ -628,9 +2082,20 @@ where
     /// # use moku::*;
     /// # use example::machine::Builder;
     /// # let mut machine = Builder::new(example::Top).build();
-    /// machine.handle_event(&example::Event::A);
+    /// let outcome = machine.handle_event(&example::Event::A);
+    /// assert_eq!(outcome, EventOutcome::Unhandled);
     /// ```
-    fn handle_event(&mut self, event: &U);
+    fn handle_event(&mut self, event: &U) -> EventOutcome<T>
+    where
+        V: TopState<Resources = ()>,
+    {
+        self.handle_event_with(&mut (), event)
+    }
+
+    /// Handle an event, passing [`TopState::Resources`] to the contexts of its states.
+    ///
+    /// See [`StateMachine::handle_event`].
+    fn handle_event_with(&mut self, resources: &mut V::Resources, event: &U) -> EventOutcome<T>;
 
     /// Get a list of currently active states, starting from the [TopState].
     ///
@@ -680,6 +2145,91 @@ where
     #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
     #[cfg(feature = "std")]
     fn state_list(&self) -> Vec<T>;
+
+    /// Get the queue of [`TopState::Output`]s emitted by states that have not yet been drained,
+    /// oldest first.
+    ///
+    /// The queue holds up to as many outputs as the capacity set with the `output_capacity` method
+    /// of the autogenerated `Builder`, 16 by default.
+    ///
+    /// # Example
+    /// ```
+    /// # #[moku::state_machine]
+    /// # mod example {
+    /// #    use moku::*;
+    /// #
+    /// #    #[machine_module]
+    /// #    pub mod machine {}
+    /// #    use machine::State;
+    /// #
+    /// // ...
+    ///     pub struct Top;
+    ///     impl TopState for Top {
+    ///         type Output = &'static str;
+    ///     }
+    ///
+    ///     struct Foo;
+    ///     impl Substate<Top> for Foo {
+    ///         fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+    ///             ctx.outputs.push("hello").unwrap();
+    ///             Self
+    ///         }
+    ///     }
+    /// // ...
+    /// # }
+    /// # use moku::*;
+    /// # use example::machine::{Builder, State};
+    /// # let mut machine = Builder::new(example::Top).build();
+    /// machine.transition(State::Foo);
+    /// assert_eq!(machine.outputs().len(), 1);
+    /// assert_eq!(machine.outputs().get(0), Some(&"hello"));
+    /// ```
+    fn outputs(&self) -> &dyn OutputQueue<V::Output>;
+
+    /// Get the queue of [`TopState::Output`]s emitted by states mutably, e.g. to push outputs
+    /// from outside of the machine.
+    ///
+    /// See [`StateMachine::outputs`].
+    fn outputs_mut(&mut self) -> &mut dyn OutputQueue<V::Output>;
+
+    /// Remove and iterate over the [`TopState::Output`]s emitted by states, oldest first.
+    ///
+    /// # Example
+    /// ```
+    /// # #[moku::state_machine]
+    /// # mod example {
+    /// #    use moku::*;
+    /// #
+    /// #    #[machine_module]
+    /// #    pub mod machine {}
+    /// #    use machine::State;
+    /// #
+    /// // ...
+    ///     pub struct Top;
+    ///     impl TopState for Top {
+    ///         type Output = u8;
+    ///     }
+    ///
+    ///     struct Foo;
+    ///     impl Substate<Top> for Foo {
+    ///         fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+    ///             ctx.outputs.push(1).unwrap();
+    ///         }
+    ///     }
+    /// // ...
+    /// # }
+    /// # use moku::*;
+    /// # use example::machine::{Builder, State};
+    /// # let mut machine = Builder::new(example::Top).build();
+    /// machine.transition(State::Foo);
+    /// machine.update();
+    /// machine.update();
+    /// assert_eq!(machine.drain_outputs().collect::<Vec<_>>(), [1, 1]);
+    /// assert!(machine.outputs().is_empty());
+    /// ```
+    fn drain_outputs(&mut self) -> DrainOutputs<'_, V::Output> {
+        self.outputs_mut().drain()
+    }
 }
 
 /// Trait for getting references to active states.
@@ -755,6 +2305,182 @@ where
     fn state_mut(&mut self) -> Option<&mut V>;
 }
 
+/// Typed access to the states of a generated context, e.g. `FooContext`.
+///
+/// Implemented by every context, so that the states it contains can be named by type with
+/// [`StateContext::get`] and [`StateContext::get_mut`]. Also gives the time that the state whose
+/// hook is being called has been active with [`StateContext::time_in_state`].
+pub trait StateContext: internal::TimedContext {
+    /// Get a reference to a state of this context by its type.
+    ///
+    /// # Example
+    /// ```
+    /// # #[moku::state_machine]
+    /// # mod example {
+    /// #    use moku::*;
+    /// #
+    /// #    #[machine_module]
+    /// #    pub mod machine {}
+    /// #
+    /// // ...
+    ///     pub struct Top {
+    ///         pub count: u8,
+    ///     }
+    ///     impl TopState for Top {}
+    ///
+    ///     struct Foo;
+    ///     impl Substate<Top> for Foo {
+    ///         fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+    ///             assert_eq!(ctx.get::<Top>().count, ctx.top.count);
+    ///         }
+    ///     }
+    /// // ...
+    /// # }
+    /// ```
+    fn get<S>(&self) -> &S
+    where
+        Self: HasState<S>,
+    {
+        self.state()
+    }
+
+    /// Get a mutable reference to a state of this context by its type.
+    ///
+    /// # Example
+    /// ```
+    /// # #[moku::state_machine]
+    /// # mod example {
+    /// #    use moku::*;
+    /// #
+    /// #    #[machine_module]
+    /// #    pub mod machine {}
+    /// #
+    /// // ...
+    ///     pub struct Top {
+    ///         pub count: u8,
+    ///     }
+    ///     impl TopState for Top {}
+    ///
+    ///     struct Foo;
+    ///     impl Substate<Top> for Foo {
+    ///         fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+    ///             ctx.get_mut::<Top>().count += 1;
+    ///         }
+    ///     }
+    /// // ...
+    /// # }
+    /// ```
+    fn get_mut<S>(&mut self) -> &mut S
+    where
+        Self: HasState<S>,
+    {
+        self.state_mut()
+    }
+
+    /// Get the time at which the state whose hook is being called was entered, as measured by the
+    /// [`Clock`] of the state machine.
+    ///
+    /// Within [`Substate::enter`], this is the time at which the state is being entered.
+    fn entry_time(&self) -> core::time::Duration {
+        self.time().entered
+    }
+
+    /// Get the time that the state whose hook is being called has been active, as measured by the
+    /// [`Clock`] of the state machine.
+    ///
+    /// # Example
+    /// ```
+    /// # #[moku::state_machine]
+    /// # mod example {
+    /// #    use moku::*;
+    /// #
+    /// #    #[machine_module]
+    /// #    pub mod machine {}
+    /// #    use machine::State;
+    /// #
+    /// // ...
+    ///     pub struct Top {
+    ///         pub blink_period: std::time::Duration,
+    ///     }
+    ///     impl TopState for Top {}
+    ///
+    ///     struct LedOn;
+    ///     impl Substate<Top> for LedOn {
+    ///         fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+    ///             if ctx.time_in_state() >= ctx.top.blink_period {
+    ///                 Next::Target(State::LedOff)
+    ///             } else {
+    ///                 Next::None
+    ///             }
+    ///         }
+    ///     }
+    ///
+    ///     struct LedOff;
+    ///     impl Substate<Top> for LedOff {}
+    /// // ...
+    /// # }
+    /// ```
+    fn time_in_state(&self) -> core::time::Duration {
+        let time = self.time();
+        time.clock.now().saturating_sub(time.entered)
+    }
+}
+
+/// Trait for getting references to a state contained in a generated context.
+///
+/// Will be implemented by the context of a state for the state itself and each of its
+/// ancestors, so that helper functions can be written generically over any context containing a
+/// state.
+///
+/// # Example
+/// ```
+/// #[moku::state_machine]
+/// mod example {
+///     use moku::*;
+///
+///     #[machine_module]
+///     pub mod machine {}
+///
+///     pub struct Top {
+///         pub count: u8,
+///     }
+///     impl TopState for Top {}
+///
+///     fn count(ctx: &mut impl HasState<Top>) {
+///         ctx.get_mut::<Top>().count += 1;
+///     }
+///
+///     struct Foo;
+///     impl Substate<Top> for Foo {
+///         fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+///             count(ctx);
+///         }
+///     }
+///
+///     struct Bar;
+///     impl Substate<Foo> for Bar {
+///         fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+///             count(ctx);
+///         }
+///     }
+/// }
+///
+/// use moku::*;
+/// use example::machine::{Builder, State};
+///
+/// let mut machine = Builder::new(example::Top { count: 0 }).build();
+/// machine.transition(State::Bar);
+/// machine.update();
+/// assert_eq!(machine.top_ref().count, 2);
+/// ```
+pub trait HasState<S>: StateContext {
+    /// Get a reference to the state.
+    fn state(&self) -> &S;
+
+    /// Get a mutable reference to the state.
+    fn state_mut(&mut self) -> &mut S;
+}
+
 /// Builder for a [`StateMachine`].
 pub trait StateMachineBuilder<T, U, V, W>
 where
@@ -837,7 +2563,19 @@ where
     /// let builder = Builder::new(example::Top);
     /// let machine = builder.build();
     /// ```
-    fn build(self) -> W;
+    fn build(self) -> W
+    where
+        Self: Sized,
+        V: TopState<Resources = ()>,
+    {
+        self.build_with(&mut ())
+    }
+
+    /// Build the [`StateMachine`], passing [`TopState::Resources`] to the contexts of the states
+    /// entered during its initial transition.
+    ///
+    /// See [`StateMachineBuilder::build`].
+    fn build_with(self, resources: &mut V::Resources) -> W;
 }
 
 /// Return type of [`Substate::enter`].
@@ -867,6 +2605,9 @@ impl<T: StateEnum, U> From<U> for Entry<T, U> {
 /// Implement this trait for each non-top state in a [`state_machine`] module.
 /// The generic parameter specifies the superstate (parent state).
 ///
+/// The second generic parameter is the [`TopState`] of a state machine with generic parameters.
+/// It is filled in by the [`state_machine`] macro and should not be specified manually.
+///
 /// ## Example
 /// ```
 /// #[moku::state_machine]
@@ -886,16 +2627,25 @@ impl<T: StateEnum, U> From<U> for Entry<T, U> {
 ///     impl moku::Substate<Foo> for Bar {}
 /// }
 /// ```
-pub trait Substate<Parent>: Sized {
+pub trait Substate<Parent, Top = ()>: Sized {
     /// The state enum type. Auto-filled by the [`state_machine`] macro.
     type State: StateEnum;
 
     /// The event type. Auto-filled by the [`state_machine`] macro.
-    type Event: StateMachineEvent;
+    ///
+    /// May be defined to handle a narrower event type in this state and its descendents, which
+    /// must implement `TryFrom<&E>` for the state machine's event type `E`. Events that can't be
+    /// converted are deferred to the superstate, which handles the original event.
+    type Event;
 
     /// Context providing mutable access to all active superstates.
     /// Auto-filled by the [`state_machine`] macro.
-    type Context<'a>;
+    type Context<'a>
+    where
+        Top: 'a;
+
+    /// The [`TopState::Input`] type. Auto-filled by the [`state_machine`] macro.
+    type Input: ?Sized;
 
     /// Called when a [`StateMachine`] enters this state.
     ///
@@ -950,6 +2700,9 @@ pub trait Substate<Parent>: Sized {
     /// Returning a value of `Next::None` results in the [`StateMachine`] remaining in this state after
     /// transition.
     ///
+    /// If the impl is attributed with [`transitions`], `Self::State` is narrowed for `init` as
+    /// well, so the initial substates that it returns must be declared as transitions.
+    ///
     /// # Example
     /// ```
     /// # #[moku::state_machine]
@@ -984,6 +2737,9 @@ pub trait Substate<Parent>: Sized {
     ///
     /// This method may return a target state to transition to as a result of updating.
     ///
+    /// The `input` parameter receives the [`TopState::Input`] of the update, and may be omitted
+    /// from implementations, in which case it is added by the [`state_machine`] macro.
+    ///
     /// # Example
     /// ```
     /// # #[moku::state_machine]
@@ -1012,12 +2768,20 @@ pub trait Substate<Parent>: Sized {
     /// #    pub struct Bar;
     /// #    impl Substate<Foo> for Bar {}
     /// # }
-    fn update(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {}
+    fn update(
+        &mut self,
+        _ctx: &mut Self::Context<'_>,
+        _input: &Self::Input,
+    ) -> impl Into<Next<Self::State>> {
+    }
 
     /// Called when [`StateMachine::top_down_update`] is called.
     ///
     /// This method may return a target state to transition to as a result of updating.
     ///
+    /// The `input` parameter receives the [`TopState::Input`] of the update, and may be omitted
+    /// from implementations, in which case it is added by the [`state_machine`] macro.
+    ///
     /// # Example
     /// ```
     /// # #[moku::state_machine]
@@ -1046,7 +2810,12 @@ pub trait Substate<Parent>: Sized {
     /// #    pub struct Bar;
     /// #    impl Substate<Foo> for Bar {}
     /// # }
-    fn top_down_update(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {}
+    fn top_down_update(
+        &mut self,
+        _ctx: &mut Self::Context<'_>,
+        _input: &Self::Input,
+    ) -> impl Into<Next<Self::State>> {
+    }
 
     /// Called when a [`StateMachine`] exits this state.
     ///
@@ -1092,7 +2861,8 @@ pub trait Substate<Parent>: Sized {
     /// Return [`Response::Next`] with something other than [`Next::None`]
     /// to transition to the given state, after which event handling is stopped.
     ///
-    /// Return [`Response::Drop`] to immediately stop event handling.
+    /// Return [`Response::Drop`] to immediately stop event handling, or [`Response::Handled`] to
+    /// do so while reporting the event as handled.
     ///
     /// # Example
     /// ```
@@ -1175,6 +2945,31 @@ pub trait TopState: Sized {
     /// The event type. Auto-filled by the [`state_machine`] macro.
     type Event: StateMachineEvent;
 
+    /// External resources that are passed into each call of the [`StateMachine`] rather than
+    /// being owned by it, and are reachable as `ctx.resources` in every [`Substate`] hook.
+    ///
+    /// Auto-filled as `()` by the [`state_machine`] macro if not specified.
+    ///
+    /// See [`StateMachine::update_with`].
+    type Resources: ?Sized;
+
+    /// Per-update input that is passed by reference to the `update` and `top_down_update` hooks of
+    /// every active state, e.g. a time delta or a sensor sample.
+    ///
+    /// Auto-filled as `()` by the [`state_machine`] macro if not specified.
+    ///
+    /// See [`StateMachine::update_input`].
+    type Input: ?Sized;
+
+    /// Outputs that states emit through `ctx.outputs` in their [`Substate`] hooks instead of
+    /// performing side effects themselves, e.g. commands to actuators.
+    ///
+    /// Outputs are queued by the [`StateMachine`] in its [`Outputs`] until the caller drains them
+    /// with [`StateMachine::drain_outputs`].
+    ///
+    /// Auto-filled as `()` by the [`state_machine`] macro if not specified.
+    type Output;
+
     /// Called when a [`StateMachine`] initializes (upon calling [`StateMachineBuilder::build`]) and
     /// upon transitions directly to this state.
     ///
@@ -1213,6 +3008,9 @@ pub trait TopState: Sized {
     ///
     /// This method may return a target state to transition to as a result of updating.
     ///
+    /// The `input` parameter receives the [`TopState::Input`] of the update, and may be omitted
+    /// from implementations, in which case it is added by the [`state_machine`] macro.
+    ///
     /// # Example
     /// ```
     /// # #[moku::state_machine]
@@ -1236,12 +3034,15 @@ pub trait TopState: Sized {
     /// #    pub struct Foo;
     /// #    impl Substate<Top> for Foo {}
     /// # }
-    fn update(&mut self) -> impl Into<Next<Self::State>> {}
+    fn update(&mut self, _input: &Self::Input) -> impl Into<Next<Self::State>> {}
 
     /// Called when [`StateMachine::top_down_update`] is called.
     ///
     /// This method may return a target state to transition to as a result of updating.
     ///
+    /// The `input` parameter receives the [`TopState::Input`] of the update, and may be omitted
+    /// from implementations, in which case it is added by the [`state_machine`] macro.
+    ///
     /// # Example
     /// ```
     /// # #[moku::state_machine]
@@ -1265,7 +3066,7 @@ pub trait TopState: Sized {
     /// #    pub struct Foo;
     /// #    impl Substate<Top> for Foo {}
     /// # }
-    fn top_down_update(&mut self) -> impl Into<Next<Self::State>> {}
+    fn top_down_update(&mut self, _input: &Self::Input) -> impl Into<Next<Self::State>> {}
 
     /// Called when [`StateMachine::handle_event`] is called.
     ///
@@ -1304,66 +3105,452 @@ pub trait TopState: Sized {
     /// ```
     #[allow(unused_variables)]
     fn handle_event(&mut self, event: &Self::Event) -> impl Into<Next<Self::State>> {}
+
+    /// Called when [`StateMachine::handle_event`] is called and no state handles the event.
+    ///
+    /// An event is unhandled when every active state, including the [`TopState`], defers it to
+    /// its superstate.
+    ///
+    /// # Example
+    /// ```
+    /// # #[moku::state_machine]
+    /// # mod example {
+    /// #    use moku::*;
+    /// #
+    /// #    #[machine_module]
+    /// #    pub mod machine {}
+    /// #    use machine::State;
+    /// #
+    /// #    pub enum Event { A, B }
+    /// #    impl StateMachineEvent for Event {}
+    /// #
+    /// // ...
+    ///     pub struct Top {
+    ///         unhandled: u32,
+    ///     }
+    ///
+    ///     impl TopState for Top {
+    ///         fn on_unhandled(&mut self, event: &Self::Event) {
+    ///             self.unhandled += 1;
+    ///         }
+    ///     }
+    /// // ...
+    /// # }
+    /// ```
+    #[allow(unused_variables)]
+    fn on_unhandled(&mut self, event: &Self::Event) {}
 }
 
-/// Types and traits for autogenerated state machine code.
-///
-/// The contents of this module are intended to be used only by the code that is generated by moku.
-pub mod internal {
-    use core::marker::PhantomData;
+pub mod testing;
+
+/// Types and traits for autogenerated state machine code.
+///
+/// The contents of this module are intended to be used only by the code that is generated by moku.
+pub mod internal {
+    use core::marker::PhantomData;
+
+    use log::info;
+
+    use super::*;
+
+    /// Internal state trait used by the generated code.
+    ///
+    /// This trait unifies [`TopState`] and [`Substate`] for use with [`Node`].
+    /// Users should implement [`TopState`] or [`Substate`] instead.
+    ///
+    /// `M` is the [`TopState`] of the state machine, which carries any of its generic parameters
+    /// through to the contexts of its substates.
+    pub trait StateLike<T, U, M>: Sized
+    where
+        T: StateEnum,
+        U: StateMachineEvent,
+    {
+        /// Type containing parent context.
+        type Context<'a>: TimedContext
+        where
+            M: 'a;
+
+        /// The [`TopState::Input`] passed to update hooks.
+        type Input: ?Sized;
+
+        /// Called when entering this state.
+        fn enter(ctx: &mut Self::Context<'_>) -> Entry<T, Self>;
+
+        /// Called after entering or on direct transition.
+        fn init(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<T>>;
+
+        /// Called during update.
+        fn update(
+            &mut self,
+            ctx: &mut Self::Context<'_>,
+            input: &Self::Input,
+        ) -> impl Into<Next<T>>;
+
+        /// Called during top-down update.
+        fn top_down_update(
+            &mut self,
+            ctx: &mut Self::Context<'_>,
+            input: &Self::Input,
+        ) -> impl Into<Next<T>>;
+
+        /// Called when exiting this state.
+        fn exit(self, ctx: &mut Self::Context<'_>) -> impl Into<Next<T>>;
+
+        /// Called to handle an event.
+        fn handle_event(
+            &mut self,
+            ctx: &mut Self::Context<'_>,
+            event: &U,
+        ) -> impl Into<Response<T>>;
+    }
+
+    /// Convert a [`Next`] of the [`Substate::State`] of a state, which is narrowed for states
+    /// with declared [`transitions`], into a [`Next`] of the state enum.
+    pub fn widen_next<T: StateEnum + From<U>, U: StateEnum>(next: Next<U>) -> Next<T> {
+        match next {
+            Next::None => Next::None,
+            Next::Target(target) => Next::Target(target.into()),
+            Next::ExactTarget(target) => Next::ExactTarget(target.into()),
+        }
+    }
+
+    /// Convert an [`Entry`] of the [`Substate::State`] of a state, which is narrowed for states
+    /// with declared [`transitions`], into an [`Entry`] of the state enum.
+    pub fn widen_entry<T: StateEnum + From<U>, U: StateEnum, V>(entry: Entry<U, V>) -> Entry<T, V> {
+        match entry {
+            Entry::State(state) => Entry::State(state),
+            Entry::Target(target) => Entry::Target(target.into()),
+            Entry::ExactTarget(target) => Entry::ExactTarget(target.into()),
+        }
+    }
+
+    /// Convert a [`Response`] of the [`Substate::State`] of a state, which is narrowed for states
+    /// with declared [`transitions`], into a [`Response`] of the state enum.
+    pub fn widen_response<T: StateEnum + From<U>, U: StateEnum>(
+        response: Response<U>,
+    ) -> Response<T> {
+        match response {
+            Response::Next(next) => Response::Next(widen_next(next)),
+            Response::Drop => Response::Drop,
+            Response::Handled => Response::Handled,
+        }
+    }
+
+    /// Forward an event to a nested [`StateMachine`] with the [`submachine`] attribute, and
+    /// convert its outcome into the [`Response`] of the state that owns the machine.
+    ///
+    /// Events that can't be converted to the event type of the nested machine are deferred to the
+    /// superstate.
+    pub fn forward_event<T, U, V, M, E, S>(
+        machine: &mut M,
+        resources: &mut V::Resources,
+        event: &E,
+    ) -> Response<S>
+    where
+        T: StateEnum,
+        U: StateMachineEvent + for<'a> TryFrom<&'a E>,
+        V: TopState,
+        M: StateMachine<T, U, V>,
+        S: StateEnum,
+    {
+        match U::try_from(event) {
+            Ok(event) => machine.handle_event_with(resources, &event).into_response(),
+            Err(_) => Response::Next(Next::None),
+        }
+    }
+
+    /// A reference to a state, formatted with its `Debug` impl if it has one.
+    ///
+    /// Call `(&DebugState(state)).debug_state()` with both [`DebugStateImpl`] and
+    /// [`DebugStateFallback`] in scope, so that method resolution picks the former if the state
+    /// implements `Debug`.
+    pub struct DebugState<'a, S>(pub &'a S);
+
+    /// Gets the `Debug` impl of a state that has one.
+    pub trait DebugStateImpl<'a> {
+        /// Get the state as `dyn Debug`.
+        fn debug_state(&self) -> Option<&'a dyn core::fmt::Debug>;
+    }
+
+    impl<'a, S: core::fmt::Debug> DebugStateImpl<'a> for DebugState<'a, S> {
+        fn debug_state(&self) -> Option<&'a dyn core::fmt::Debug> {
+            Some(self.0)
+        }
+    }
+
+    /// Gets nothing for a state without a `Debug` impl.
+    pub trait DebugStateFallback<'a> {
+        /// Get `None`, as the state doesn't implement `Debug`.
+        fn debug_state(&self) -> Option<&'a dyn core::fmt::Debug>;
+    }
+
+    impl<'a, S> DebugStateFallback<'a> for &DebugState<'a, S> {
+        fn debug_state(&self) -> Option<&'a dyn core::fmt::Debug> {
+            None
+        }
+    }
+
+    /// Formats with a closure as its `Debug` impl.
+    pub struct DebugWith<F>(pub F);
+
+    impl<F> core::fmt::Debug for DebugWith<F>
+    where
+        F: Fn(&mut core::fmt::Formatter<'_>) -> core::fmt::Result,
+    {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            (self.0)(f)
+        }
+    }
+
+    /// A state in a `STATE_CHART` whose states may be compiled out by `cfg` attributes.
+    #[derive(Debug, Clone, Copy)]
+    pub struct ChartLine {
+        /// Whether the state and all of its ancestors are compiled in.
+        pub enabled: bool,
+
+        /// The depth of the state below the top state.
+        pub depth: usize,
+
+        /// The name of the state.
+        pub name: &'static str,
+    }
+
+    /// Check if no enabled sibling of the state at `depth` follows the line at `index`, which is
+    /// either that state or one of its descendents.
+    const fn is_last_sibling(lines: &[ChartLine], index: usize, depth: usize) -> bool {
+        let mut i = index + 1;
+        while i < lines.len() {
+            if lines[i].depth < depth {
+                return true;
+            }
+            if lines[i].depth == depth && lines[i].enabled {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    /// Copy bytes into a chart at an offset, if the chart is large enough, and return the offset
+    /// after them.
+    const fn write_bytes<const N: usize>(chart: &mut [u8; N], at: usize, bytes: &[u8]) -> usize {
+        let mut i = 0;
+        while i < bytes.len() {
+            if at + i < N {
+                chart[at + i] = bytes[i];
+            }
+            i += 1;
+        }
+        at + bytes.len()
+    }
 
-    use log::info;
+    /// Render the enabled lines of a state chart, given in depth-first order starting with the
+    /// top state, and return the length of the full chart.
+    const fn render_chart<const N: usize>(lines: &[ChartLine], chart: &mut [u8; N]) -> usize {
+        let mut len = 0;
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            if line.enabled {
+                if i > 0 {
+                    len = write_bytes(chart, len, b"\n");
+                    let mut depth = 1;
+                    while depth < line.depth {
+                        let pad = match is_last_sibling(lines, i, depth) {
+                            true => "   ",
+                            false => "\u{02502}  ",
+                        };
+                        len = write_bytes(chart, len, pad.as_bytes());
+                        depth += 1;
+                    }
+                    let vert = match is_last_sibling(lines, i, line.depth) {
+                        true => "\u{02514}\u{02500} ",
+                        false => "\u{0251C}\u{02500} ",
+                    };
+                    len = write_bytes(chart, len, vert.as_bytes());
+                }
+                len = write_bytes(chart, len, line.name.as_bytes());
+            }
+            i += 1;
+        }
+        len
+    }
 
-    use super::*;
+    /// Get the length of a state chart rendered from [`ChartLine`]s.
+    pub const fn state_chart_len(lines: &[ChartLine]) -> usize {
+        render_chart(lines, &mut [])
+    }
 
-    /// Internal state trait used by the generated code.
+    /// Render a state chart from [`ChartLine`]s, with a length from [`state_chart_len`].
+    pub const fn state_chart<const N: usize>(lines: &[ChartLine]) -> [u8; N] {
+        let mut chart = [0; N];
+        render_chart(lines, &mut chart);
+        chart
+    }
+
+    /// A type named through a projection on the generic parameters `P`.
     ///
-    /// This trait unifies [`TopState`] and [`Substate`] for use with [`Node`].
-    /// Users should implement [`TopState`] or [`Substate`] instead.
-    pub trait StateLike<T, U = ()>: Sized
-    where
-        T: StateEnum,
-        U: StateMachineEvent,
-    {
-        /// Type containing parent context.
-        type Context<'a>;
+    /// Lets the type aliases of states take the generic parameters of their machine, even if the
+    /// states don't use them.
+    pub trait Identity<P: ?Sized> {
+        /// The type itself.
+        type Type: ?Sized;
+    }
 
-        /// Called when entering this state.
-        fn enter(ctx: &mut Self::Context<'_>) -> Entry<T, Self>;
+    impl<S: ?Sized, P: ?Sized> Identity<P> for S {
+        type Type = S;
+    }
 
-        /// Called after entering or on direct transition.
-        fn init(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<T>>;
+    /// An [`Observer`] that collects the [`Metrics`] of a state machine, and forwards every call
+    /// to the [`Observer`] set by the user.
+    #[cfg(feature = "metrics")]
+    #[derive(Clone)]
+    pub struct Metered<T: StateEnum, O, const N: usize> {
+        #[allow(missing_docs)]
+        pub observer: O,
 
-        /// Called during update.
-        fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<T>>;
+        #[allow(missing_docs)]
+        pub metrics: Metrics<T, N>,
 
-        /// Called during top-down update.
-        fn top_down_update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<T>>;
+        clock: Clock,
+        started: core::time::Duration,
+    }
 
-        /// Called when exiting this state.
-        fn exit(self, ctx: &mut Self::Context<'_>) -> impl Into<Next<T>>;
+    #[cfg(feature = "metrics")]
+    impl<T: StateEnum, O, const N: usize> Metered<T, O, N> {
+        /// Make a new [`Metered`] observer from the [`Observer`] set by the user and the [`Clock`]
+        /// used to time hooks.
+        pub fn new(observer: O, clock: Clock) -> Self {
+            Self {
+                observer,
+                metrics: Metrics::default(),
+                clock,
+                started: core::time::Duration::ZERO,
+            }
+        }
+    }
 
-        /// Called to handle an event.
-        fn handle_event(
-            &mut self,
-            ctx: &mut Self::Context<'_>,
-            event: &U,
-        ) -> impl Into<Response<T>>;
+    #[cfg(feature = "metrics")]
+    impl<T: StateEnum, O: Observer<T>, const N: usize> Observer<T> for Metered<T, O, N> {
+        fn on_action(&mut self, action: Action<T>) {
+            self.observer.on_action(action);
+            self.started = self.clock.now();
+        }
+
+        fn on_action_complete(&mut self, action: Action<T>) {
+            // hooks never run inside of other hooks, so the start time is that of this action
+            let elapsed = self.clock.now().saturating_sub(self.started);
+            let hook = match action {
+                Action::Enter(state) => &mut self.metrics.get_mut(state).enter,
+                Action::Exit(state) => &mut self.metrics.get_mut(state).exit,
+                Action::Init(state) => &mut self.metrics.get_mut(state).init,
+                Action::Update(state) => &mut self.metrics.get_mut(state).update,
+                Action::TopDownUpdate(state) => &mut self.metrics.get_mut(state).top_down_update,
+                Action::HandleEvent(state) => &mut self.metrics.get_mut(state).handle_event,
+            };
+            hook.calls += 1;
+            hook.time += elapsed;
+            self.observer.on_action_complete(action);
+        }
+
+        fn on_event_outcome(&mut self, outcome: EventOutcome<T>) {
+            if let Some(state) = outcome.state() {
+                let metrics = self.metrics.get_mut(state);
+                metrics.events_handled += 1;
+                if let EventOutcome::Dropped { .. } = outcome {
+                    metrics.events_dropped += 1;
+                }
+            }
+            self.observer.on_event_outcome(outcome);
+        }
+
+        fn on_machine_action(&mut self, action: MachineAction<T>) {
+            self.observer.on_machine_action(action);
+        }
+    }
+
+    /// The [`Clock`] of a state machine, and the entry time of the state whose hook is being
+    /// called.
+    #[derive(Debug, Clone, Copy)]
+    pub struct ContextTime {
+        #[allow(missing_docs)]
+        pub clock: Clock,
+
+        #[allow(missing_docs)]
+        pub entered: core::time::Duration,
+    }
+
+    /// A context that carries a [`ContextTime`].
+    pub trait TimedContext {
+        /// Get the [`ContextTime`] of this context.
+        fn time(&self) -> &ContextTime;
+
+        /// Get the [`ContextTime`] of this context mutably.
+        fn time_mut(&mut self) -> &mut ContextTime;
     }
 
-    /// Type containing parent context of a [`TopState`], i.e. nothing.
+    /// Type containing parent context of a [`TopState`], i.e. only its [`TopState::Resources`]
+    /// and the sink for its [`TopState::Output`]s.
     ///
     /// Not intended for use by users.
-    pub struct TopContext<'a>(PhantomData<&'a ()>);
+    ///
+    /// Names the [`TopState`] `V` so that `V: 'a` is implied wherever it is used.
+    pub struct TopContext<'a, V: TopState> {
+        #[allow(missing_docs)]
+        pub resources: &'a mut V::Resources,
+
+        #[allow(missing_docs)]
+        pub outputs: &'a mut dyn OutputQueue<V::Output>,
+
+        #[allow(missing_docs)]
+        pub time: ContextTime,
+
+        phantom: PhantomData<&'a V>,
+    }
+
+    impl<'a, V: TopState> TopContext<'a, V> {
+        /// Make a new [`TopContext`] from the resources passed into a [`StateMachine`] call, and
+        /// the outputs and clock of a [`TopNode`].
+        pub fn new(
+            resources: &'a mut V::Resources,
+            outputs: &'a mut dyn OutputQueue<V::Output>,
+            clock: Clock,
+        ) -> Self {
+            Self {
+                resources,
+                outputs,
+                time: ContextTime {
+                    clock,
+                    entered: core::time::Duration::ZERO,
+                },
+                phantom: PhantomData,
+            }
+        }
+    }
+
+    impl<V: TopState> TimedContext for TopContext<'_, V> {
+        fn time(&self) -> &ContextTime {
+            &self.time
+        }
+
+        fn time_mut(&mut self) -> &mut ContextTime {
+            &mut self.time
+        }
+    }
 
     /// Blanket implementation of StateLike for TopState.
-    impl<T, U, V> StateLike<T, U> for V
+    impl<T, U, V> StateLike<T, U, V> for V
     where
         T: StateEnum,
         U: StateMachineEvent,
         V: TopState<State = T, Event = U>,
     {
-        type Context<'a> = TopContext<'a>;
+        type Context<'a>
+            = TopContext<'a, V>
+        where
+            V: 'a;
+
+        type Input = V::Input;
 
         fn enter(_ctx: &mut Self::Context<'_>) -> Entry<T, Self> {
             unreachable!("TopState::enter should never be called")
@@ -1373,12 +3560,20 @@ pub mod internal {
             TopState::init(self)
         }
 
-        fn update(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<T>> {
-            TopState::update(self)
+        fn update(
+            &mut self,
+            _ctx: &mut Self::Context<'_>,
+            input: &Self::Input,
+        ) -> impl Into<Next<T>> {
+            TopState::update(self, input)
         }
 
-        fn top_down_update(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<T>> {
-            TopState::top_down_update(self)
+        fn top_down_update(
+            &mut self,
+            _ctx: &mut Self::Context<'_>,
+            input: &Self::Input,
+        ) -> impl Into<Next<T>> {
+            TopState::top_down_update(self, input)
         }
 
         fn exit(self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<T>> {
@@ -1412,11 +3607,11 @@ pub mod internal {
     /// The substate of a state.
     ///
     /// Also aggregates some functionality that would be attributed to the state.
-    pub trait SubstateEnum<T, U, V>
+    pub trait SubstateEnum<T, U, V, M>
     where
         T: StateEnum,
         U: StateMachineEvent,
-        V: StateLike<T, U>,
+        V: StateLike<T, U, M>,
     {
         /// The variant that represents no substate, i.e. being in exactly this state.
         fn none_variant() -> Self;
@@ -1440,26 +3635,50 @@ pub mod internal {
 
         /// Update this state and its active descendents.
         #[allow(unused_variables)]
-        fn update(&mut self, state: &mut V, ctx: &mut V::Context<'_>) -> Next<T> {
+        fn update<O: Observer<T>>(
+            &mut self,
+            state: &mut V,
+            ctx: &mut V::Context<'_>,
+            input: &V::Input,
+            observer: &mut O,
+        ) -> Next<T> {
             Next::None
         }
 
         /// Update this state and its active descendents if in need of update after a transition.
         #[allow(unused_variables)]
-        fn update_in_need(&mut self, state: &mut V, ctx: &mut V::Context<'_>) -> Next<T> {
+        fn update_in_need<O: Observer<T>>(
+            &mut self,
+            state: &mut V,
+            ctx: &mut V::Context<'_>,
+            input: &V::Input,
+            observer: &mut O,
+        ) -> Next<T> {
             Next::None
         }
 
         /// Top-down update this state and its active descendents.
         #[allow(unused_variables)]
-        fn top_down_update(&mut self, state: &mut V, ctx: &mut V::Context<'_>) -> Next<T> {
+        fn top_down_update<O: Observer<T>>(
+            &mut self,
+            state: &mut V,
+            ctx: &mut V::Context<'_>,
+            input: &V::Input,
+            observer: &mut O,
+        ) -> Next<T> {
             Next::None
         }
 
         /// Top-down update this state and its active descendents if in need of update after a
         /// transition.
         #[allow(unused_variables)]
-        fn top_down_update_in_need(&mut self, state: &mut V, ctx: &mut V::Context<'_>) -> Next<T> {
+        fn top_down_update_in_need<O: Observer<T>>(
+            &mut self,
+            state: &mut V,
+            ctx: &mut V::Context<'_>,
+            input: &V::Input,
+            observer: &mut O,
+        ) -> Next<T> {
             Next::None
         }
 
@@ -1468,19 +3687,26 @@ pub mod internal {
 
         /// Exit this state and its active descendents.
         #[allow(unused_variables)]
-        fn exit(&mut self, state: &mut V, ctx: &mut V::Context<'_>, indent: bool) -> Next<T> {
+        fn exit<O: Observer<T>>(
+            &mut self,
+            state: &mut V,
+            ctx: &mut V::Context<'_>,
+            indent: bool,
+            observer: &mut O,
+        ) -> Next<T> {
             Next::None
         }
 
         /// Transition this state and its active descendents.
         #[allow(unused_variables)]
-        fn transition(
+        fn transition<O: Observer<T>>(
             &mut self,
             target: T,
             state: &mut V,
             ctx: &mut V::Context<'_>,
             indent: bool,
             exact: bool,
+            observer: &mut O,
         ) -> TransitionResult<T> {
             TransitionResult::MoveUp
         }
@@ -1489,12 +3715,13 @@ pub mod internal {
         ///
         /// Panics if called when the target state is not a descendent of this state.
         #[allow(unused_variables)]
-        fn enter_substate_towards(
+        fn enter_substate_towards<O: Observer<T>>(
             &mut self,
             target: T,
             state: &mut V,
             ctx: &mut V::Context<'_>,
             indent: bool,
+            observer: &mut O,
         ) -> Next<T> {
             unreachable!()
         }
@@ -1504,15 +3731,22 @@ pub mod internal {
             Self::is_state(state)
         }
 
+        /// Get the entry time of a given state, if it is an active descendent of this state.
+        #[allow(unused_variables)]
+        fn entry_time(&self, state: T) -> Option<core::time::Duration> {
+            None
+        }
+
         /// Handle an event.
         #[allow(unused_variables)]
-        fn handle_event(
+        fn handle_event<O: Observer<T>>(
             &mut self,
             event: &U,
             state: &mut V,
             ctx: &mut V::Context<'_>,
-        ) -> Response<T> {
-            Response::Next(Next::None)
+            observer: &mut O,
+        ) -> EventOutcome<T> {
+            EventOutcome::Unhandled
         }
 
         /// Build a list of the currently active states.
@@ -1521,15 +3755,15 @@ pub mod internal {
     }
 
     /// The result of trying to enter a [`Node`].
-    pub enum NodeEntry<T, U, V, W>
+    pub enum NodeEntry<T, U, V, W, M>
     where
         T: StateEnum,
         U: StateMachineEvent,
-        V: StateLike<T, U>,
-        W: SubstateEnum<T, U, V>,
+        V: StateLike<T, U, M>,
+        W: SubstateEnum<T, U, V, M>,
     {
         /// Entry was successful, here is the new [`Node`].
-        Node(Node<T, U, V, W>),
+        Node(Node<T, U, V, W, M>),
 
         /// Entry resulted in a short circuit transition.
         Target(T),
@@ -1538,12 +3772,12 @@ pub mod internal {
         ExactTarget(T),
     }
 
-    impl<T, U, V, W> From<Entry<T, V>> for NodeEntry<T, U, V, W>
+    impl<T, U, V, W, M> From<Entry<T, V>> for NodeEntry<T, U, V, W, M>
     where
         T: StateEnum,
         U: StateMachineEvent,
-        V: StateLike<T, U>,
-        W: SubstateEnum<T, U, V>,
+        V: StateLike<T, U, M>,
+        W: SubstateEnum<T, U, V, M>,
     {
         fn from(entry: Entry<T, V>) -> Self {
             match entry {
@@ -1563,15 +3797,16 @@ pub mod internal {
     }
 
     /// A node in the state tree.
-    pub struct Node<T, U, V, W>
+    pub struct Node<T, U, V, W, M>
     where
         T: StateEnum,
         U: StateMachineEvent,
-        V: StateLike<T, U>,
-        W: SubstateEnum<T, U, V>,
+        V: StateLike<T, U, M>,
+        W: SubstateEnum<T, U, V, M>,
     {
         phantom_t: PhantomData<T>,
         phantom_u: PhantomData<U>,
+        phantom_m: PhantomData<M>,
 
         #[allow(missing_docs)]
         pub state: V,
@@ -1580,23 +3815,28 @@ pub mod internal {
         pub substate: W,
 
         flags: NodeFlags,
+
+        /// The time at which the state was entered, as measured by the [`Clock`] of the machine.
+        entered: core::time::Duration,
     }
 
-    impl<T, U, V, W> Node<T, U, V, W>
+    impl<T, U, V, W, M> Node<T, U, V, W, M>
     where
         T: StateEnum,
         U: StateMachineEvent,
-        V: StateLike<T, U>,
-        W: SubstateEnum<T, U, V>,
+        V: StateLike<T, U, M>,
+        W: SubstateEnum<T, U, V, M>,
     {
         /// Make a new [`Node`] from a state.
         pub fn from_state(state: V) -> Self {
             Self {
                 phantom_t: PhantomData,
                 phantom_u: PhantomData,
+                phantom_m: PhantomData,
                 state,
                 substate: W::none_variant(),
                 flags: NodeFlags::empty(),
+                entered: core::time::Duration::ZERO,
             }
         }
 
@@ -1606,25 +3846,41 @@ pub mod internal {
         }
 
         /// Enter this node.
-        pub fn enter(ctx: &mut V::Context<'_>, indent: bool) -> NodeEntry<T, U, V, W> {
+        pub fn enter<O: Observer<T>>(
+            ctx: &mut V::Context<'_>,
+            indent: bool,
+            observer: &mut O,
+        ) -> NodeEntry<T, U, V, W, M> {
             info!(
                 "{}\u{02502}Entering {:?}",
                 if indent { "\u{02502}" } else { "" },
                 W::this_state()
             );
-            match V::enter(ctx) {
+            let entered = ctx.time().clock.now();
+            ctx.time_mut().entered = entered;
+            observer.on_action(Action::Enter(W::this_state()));
+            let entry = V::enter(ctx);
+            observer.on_action_complete(Action::Enter(W::this_state()));
+            match entry {
                 Entry::State(state) => NodeEntry::Node(Self {
                     phantom_t: PhantomData,
                     phantom_u: PhantomData,
+                    phantom_m: PhantomData,
                     state,
                     substate: W::none_variant(),
                     flags: NodeFlags::empty(),
+                    entered,
                 }),
                 Entry::Target(target) => {
                     info!(
                         "{}\u{02502}Short circuit transition to {target:?}",
                         if indent { "\u{02502}" } else { "" },
                     );
+                    observer.on_machine_action(MachineAction::ShortCircuit {
+                        state: W::this_state(),
+                        target,
+                        exact: false,
+                    });
                     NodeEntry::Target(target)
                 }
                 Entry::ExactTarget(target) => {
@@ -1632,32 +3888,58 @@ pub mod internal {
                         "{}\u{02502}Short circuit exact transition to {target:?}",
                         if indent { "\u{02502}" } else { "" },
                     );
+                    observer.on_machine_action(MachineAction::ShortCircuit {
+                        state: W::this_state(),
+                        target,
+                        exact: true,
+                    });
                     NodeEntry::ExactTarget(target)
                 }
             }
         }
 
         /// Update this node and its active descendents.
-        pub fn update(&mut self, ctx: &mut V::Context<'_>) -> Next<T> {
+        pub fn update<O: Observer<T>>(
+            &mut self,
+            ctx: &mut V::Context<'_>,
+            input: &V::Input,
+            observer: &mut O,
+        ) -> Next<T> {
             self.flags.insert(NodeFlags::NEEDS_UPDATE);
-            match self.substate.update(&mut self.state, ctx) {
+            match self.substate.update(&mut self.state, ctx, input, observer) {
                 Next::None => {
                     info!("\u{02502}Updating {:?}", W::this_state());
+                    observer.on_action(Action::Update(W::this_state()));
                     self.flags.remove(NodeFlags::NEEDS_UPDATE);
-                    self.state.update(ctx).into()
+                    ctx.time_mut().entered = self.entered;
+                    let next = self.state.update(ctx, input).into();
+                    observer.on_action_complete(Action::Update(W::this_state()));
+                    next
                 }
                 target => target,
             }
         }
 
         /// Update this node and its active descendents if in need of update after a transition.
-        pub fn update_in_need(&mut self, ctx: &mut V::Context<'_>) -> Next<T> {
+        pub fn update_in_need<O: Observer<T>>(
+            &mut self,
+            ctx: &mut V::Context<'_>,
+            input: &V::Input,
+            observer: &mut O,
+        ) -> Next<T> {
             if self.flags.contains(NodeFlags::NEEDS_UPDATE) {
-                match self.substate.update_in_need(&mut self.state, ctx) {
+                match self
+                    .substate
+                    .update_in_need(&mut self.state, ctx, input, observer)
+                {
                     Next::None => {
                         info!("\u{02502}Updating {:?}", W::this_state());
+                        observer.on_action(Action::Update(W::this_state()));
                         self.flags.remove(NodeFlags::NEEDS_UPDATE);
-                        self.state.update(ctx).into()
+                        ctx.time_mut().entered = self.entered;
+                        let next = self.state.update(ctx, input).into();
+                        observer.on_action_complete(Action::Update(W::this_state()));
+                        next
                     }
                     target => target,
                 }
@@ -1667,28 +3949,49 @@ pub mod internal {
         }
 
         /// Top-down update this node and its active descendents.
-        pub fn top_down_update(&mut self, ctx: &mut V::Context<'_>) -> Next<T> {
+        pub fn top_down_update<O: Observer<T>>(
+            &mut self,
+            ctx: &mut V::Context<'_>,
+            input: &V::Input,
+            observer: &mut O,
+        ) -> Next<T> {
             info!("\u{02502}Top-down updating {:?}", W::this_state());
+            observer.on_action(Action::TopDownUpdate(W::this_state()));
             self.flags.insert(NodeFlags::TOP_DOWN_UPDATED);
-            match self.state.top_down_update(ctx).into() {
-                Next::None => self.substate.top_down_update(&mut self.state, ctx),
+            ctx.time_mut().entered = self.entered;
+            let next = self.state.top_down_update(ctx, input).into();
+            observer.on_action_complete(Action::TopDownUpdate(W::this_state()));
+            match next {
+                Next::None => self
+                    .substate
+                    .top_down_update(&mut self.state, ctx, input, observer),
                 target => target,
             }
         }
 
         /// Top-down update this node and its active descendents if in need of update after a
         /// transition.
-        pub fn top_down_update_in_need(&mut self, ctx: &mut V::Context<'_>) -> Next<T> {
+        pub fn top_down_update_in_need<O: Observer<T>>(
+            &mut self,
+            ctx: &mut V::Context<'_>,
+            input: &V::Input,
+            observer: &mut O,
+        ) -> Next<T> {
             if !self.flags.contains(NodeFlags::TOP_DOWN_UPDATED) {
                 info!("\u{02502}Top-down updating {:?}", W::this_state());
+                observer.on_action(Action::TopDownUpdate(W::this_state()));
                 self.flags.insert(NodeFlags::TOP_DOWN_UPDATED);
-                match self.state.top_down_update(ctx).into() {
+                ctx.time_mut().entered = self.entered;
+                let next = self.state.top_down_update(ctx, input).into();
+                observer.on_action_complete(Action::TopDownUpdate(W::this_state()));
+                match next {
                     Next::None => (),
                     target => return target,
                 }
             }
 
-            self.substate.top_down_update_in_need(&mut self.state, ctx)
+            self.substate
+                .top_down_update_in_need(&mut self.state, ctx, input, observer)
         }
 
         /// Clear the top-down update flag from this node and its active descendents.
@@ -1698,47 +4001,70 @@ pub mod internal {
         }
 
         /// Exit this node and its active descendents.
-        pub fn exit(self, ctx: &mut V::Context<'_>, indent: bool) -> Next<T> {
+        pub fn exit<O: Observer<T>>(
+            self,
+            ctx: &mut V::Context<'_>,
+            indent: bool,
+            observer: &mut O,
+        ) -> Next<T> {
             info!(
                 "{}\u{02502}Exiting {:?}",
                 if indent { "\u{02502}" } else { "" },
                 W::this_state()
             );
+            observer.on_action(Action::Exit(W::this_state()));
 
+            ctx.time_mut().entered = self.entered;
             let res = self.state.exit(ctx).into();
+            observer.on_action_complete(Action::Exit(W::this_state()));
 
-            match &res {
+            match res {
                 Next::None => (),
-                Next::Target(target) => info!(
-                    "{}\u{02502}Short circuit transition to {target:?}",
-                    if indent { "\u{02502}" } else { "" }
-                ),
-                Next::ExactTarget(target) => info!(
-                    "{}\u{02502}Short circuit exact transition to {target:?}",
-                    if indent { "\u{02502}" } else { "" }
-                ),
+                Next::Target(target) => {
+                    info!(
+                        "{}\u{02502}Short circuit transition to {target:?}",
+                        if indent { "\u{02502}" } else { "" }
+                    );
+                    observer.on_machine_action(MachineAction::ShortCircuit {
+                        state: W::this_state(),
+                        target,
+                        exact: false,
+                    });
+                }
+                Next::ExactTarget(target) => {
+                    info!(
+                        "{}\u{02502}Short circuit exact transition to {target:?}",
+                        if indent { "\u{02502}" } else { "" }
+                    );
+                    observer.on_machine_action(MachineAction::ShortCircuit {
+                        state: W::this_state(),
+                        target,
+                        exact: true,
+                    });
+                }
             }
 
             res
         }
 
         /// Transition this node and its active descendents.
-        pub fn transition(
+        pub fn transition<O: Observer<T>>(
             &mut self,
             target: T,
             ctx: &mut V::Context<'_>,
             indent: bool,
             exact: bool,
+            observer: &mut O,
         ) -> TransitionResult<T> {
             // try to transition the current substate towards the target state
             match self
                 .substate
-                .transition(target, &mut self.state, ctx, indent, exact)
+                .transition(target, &mut self.state, ctx, indent, exact, observer)
             {
                 // substate is not the target state or an ancestor of it
                 TransitionResult::MoveUp => {
                     // check if substate exit resulted in a short circuit transition
-                    match self.substate.exit(&mut self.state, ctx, indent) {
+                    match self.substate.exit(&mut self.state, ctx, indent, observer) {
                         Next::None => {
                             if W::is_ancestor(target) {
                                 match self.substate.enter_substate_towards(
@@ -1746,6 +4072,7 @@ pub mod internal {
                                     &mut self.state,
                                     ctx,
                                     indent,
+                                    observer,
                                 ) {
                                     // substate successfully moved towards target state,
                                     // continue transitioning downwards
@@ -1758,6 +4085,7 @@ pub mod internal {
                                         ctx,
                                         indent,
                                         false,
+                                        observer,
                                     ),
                                     // substate transition resulted in a short circuit transition
                                     res => TransitionResult::Next(res),
@@ -1768,7 +4096,10 @@ pub mod internal {
                                     // we need to leave and come back
                                     TransitionResult::MoveUp
                                 } else {
+                                    observer.on_action(Action::Init(W::this_state()));
+                                    ctx.time_mut().entered = self.entered;
                                     let res = self.state.init(ctx).into();
+                                    observer.on_action_complete(Action::Init(W::this_state()));
                                     match &res {
                                         Next::Target(new_target) => {
                                             info!("\u{02502}Initial transition to {new_target:?}")
@@ -1804,34 +4135,61 @@ pub mod internal {
             self.substate.state_matches(state)
         }
 
+        /// Get the entry time of a given state, if it is this node or an active descendent.
+        pub fn entry_time(&self, state: T) -> Option<core::time::Duration> {
+            if W::is_state(state) {
+                Some(self.entered)
+            } else {
+                self.substate.entry_time(state)
+            }
+        }
+
         /// Handle an event.
-        pub fn handle_event(&mut self, ctx: &mut V::Context<'_>, event: &U) -> Response<T> {
-            match self.substate.handle_event(event, &mut self.state, ctx) {
-                Response::Next(Next::None) => {
-                    let res = self.state.handle_event(ctx, event).into();
-                    match &res {
+        pub fn handle_event<O: Observer<T>>(
+            &mut self,
+            ctx: &mut V::Context<'_>,
+            event: &U,
+            observer: &mut O,
+        ) -> EventOutcome<T> {
+            match self
+                .substate
+                .handle_event(event, &mut self.state, ctx, observer)
+            {
+                EventOutcome::Unhandled => {
+                    let state = W::this_state();
+                    observer.on_action(Action::HandleEvent(state));
+                    ctx.time_mut().entered = self.entered;
+                    let response = self.state.handle_event(ctx, event).into();
+                    observer.on_action_complete(Action::HandleEvent(state));
+                    match response {
                         Response::Drop => {
-                            info!("\u{02502}{:?} dropping event", W::this_state())
+                            info!("\u{02502}{:?} dropping event {}", state, event.name());
+                            EventOutcome::Dropped { state }
+                        }
+                        Response::Handled => {
+                            info!("\u{02502}{:?} handled event {}", state, event.name());
+                            EventOutcome::Handled { state }
                         }
                         Response::Next(next) => match next {
                             Next::None => {
-                                info!("\u{02502}{:?} deferring event", W::this_state())
+                                info!("\u{02502}{:?} deferring event {}", state, event.name());
+                                EventOutcome::Unhandled
+                            }
+                            Next::Target(target) => {
+                                info!("\u{02502}{:?} triggered transition to {:?}", state, target);
+                                EventOutcome::Transition { state, target }
+                            }
+                            Next::ExactTarget(target) => {
+                                info!(
+                                    "\u{02502}{:?} triggered exact transition to {:?}",
+                                    state, target
+                                );
+                                EventOutcome::ExactTransition { state, target }
                             }
-                            Next::Target(target) => info!(
-                                "\u{02502}{:?} triggered transition to {:?}",
-                                W::this_state(),
-                                target
-                            ),
-                            Next::ExactTarget(target) => info!(
-                                "\u{02502}{:?} triggered exact transition to {:?}",
-                                W::this_state(),
-                                target
-                            ),
                         },
                     }
-                    res
                 }
-                substate_res => substate_res,
+                outcome => outcome,
             }
         }
 
@@ -1844,16 +4202,81 @@ pub mod internal {
         }
     }
 
+    impl<T, U, V, W, M> Clone for Node<T, U, V, W, M>
+    where
+        T: StateEnum,
+        U: StateMachineEvent,
+        V: StateLike<T, U, M> + Clone,
+        W: SubstateEnum<T, U, V, M> + Clone,
+    {
+        fn clone(&self) -> Self {
+            Self {
+                phantom_t: PhantomData,
+                phantom_u: PhantomData,
+                phantom_m: PhantomData,
+                state: self.state.clone(),
+                substate: self.substate.clone(),
+                flags: self.flags,
+                entered: self.entered,
+            }
+        }
+    }
+
+    /// Nodes are compared by their state and active descendents, ignoring transient update flags
+    /// and entry times.
+    impl<T, U, V, W, M> PartialEq for Node<T, U, V, W, M>
+    where
+        T: StateEnum,
+        U: StateMachineEvent,
+        V: StateLike<T, U, M> + PartialEq,
+        W: SubstateEnum<T, U, V, M> + PartialEq,
+    {
+        fn eq(&self, other: &Self) -> bool {
+            self.state == other.state && self.substate == other.substate
+        }
+    }
+
+    impl<T, U, V, W, M> Eq for Node<T, U, V, W, M>
+    where
+        T: StateEnum,
+        U: StateMachineEvent,
+        V: StateLike<T, U, M> + Eq,
+        W: SubstateEnum<T, U, V, M> + Eq,
+    {
+    }
+
+    impl<T, U, V, W, M> core::hash::Hash for Node<T, U, V, W, M>
+    where
+        T: StateEnum,
+        U: StateMachineEvent,
+        V: StateLike<T, U, M> + core::hash::Hash,
+        W: SubstateEnum<T, U, V, M> + core::hash::Hash,
+    {
+        fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+            self.state.hash(state);
+            self.substate.hash(state);
+        }
+    }
+
     /// The root node of a state tree.
-    pub struct TopNode<T, U, V, W>
+    pub struct TopNode<T, U, V, W, O = (), const N: usize = 16>
     where
         T: StateEnum,
         U: StateMachineEvent,
         V: TopState<State = T, Event = U>,
-        W: SubstateEnum<T, U, V>,
+        W: SubstateEnum<T, U, V, V>,
+        O: Observer<T>,
     {
         #[allow(missing_docs)]
-        pub node: Node<T, U, V, W>,
+        pub node: Node<T, U, V, W, V>,
+
+        #[allow(missing_docs)]
+        pub observer: O,
+
+        #[allow(missing_docs)]
+        pub outputs: Outputs<V::Output, N>,
+
+        clock: Clock,
 
         #[cfg(feature = "std")]
         name: String,
@@ -1862,42 +4285,120 @@ pub mod internal {
         name: &'static str,
     }
 
-    impl<T, U, V, W> TopNode<T, U, V, W>
+    impl<T, U, V, W, O, const N: usize> Clone for TopNode<T, U, V, W, O, N>
+    where
+        T: StateEnum,
+        U: StateMachineEvent,
+        V: TopState<State = T, Event = U> + Clone,
+        W: SubstateEnum<T, U, V, V> + Clone,
+        O: Observer<T> + Clone,
+        V::Output: Clone,
+    {
+        fn clone(&self) -> Self {
+            Self {
+                node: self.node.clone(),
+                observer: self.observer.clone(),
+                outputs: self.outputs.clone(),
+                clock: self.clock,
+                #[cfg(feature = "std")]
+                name: self.name.clone(),
+                #[cfg(not(feature = "std"))]
+                name: self.name,
+            }
+        }
+    }
+
+    /// Top nodes are compared by their state tree only, ignoring the machine name, observer,
+    /// outputs, and clock.
+    impl<T, U, V, W, O, const N: usize> PartialEq for TopNode<T, U, V, W, O, N>
+    where
+        T: StateEnum,
+        U: StateMachineEvent,
+        V: TopState<State = T, Event = U> + PartialEq,
+        W: SubstateEnum<T, U, V, V> + PartialEq,
+        O: Observer<T>,
+    {
+        fn eq(&self, other: &Self) -> bool {
+            self.node == other.node
+        }
+    }
+
+    impl<T, U, V, W, O, const N: usize> Eq for TopNode<T, U, V, W, O, N>
+    where
+        T: StateEnum,
+        U: StateMachineEvent,
+        V: TopState<State = T, Event = U> + Eq,
+        W: SubstateEnum<T, U, V, V> + Eq,
+        O: Observer<T>,
+    {
+    }
+
+    impl<T, U, V, W, O, const N: usize> core::hash::Hash for TopNode<T, U, V, W, O, N>
+    where
+        T: StateEnum,
+        U: StateMachineEvent,
+        V: TopState<State = T, Event = U> + core::hash::Hash,
+        W: SubstateEnum<T, U, V, V> + core::hash::Hash,
+        O: Observer<T>,
+    {
+        fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+            self.node.hash(state);
+        }
+    }
+
+    impl<T, U, V, W, O, const N: usize> TopNode<T, U, V, W, O, N>
     where
         T: StateEnum,
         U: StateMachineEvent,
         V: TopState<State = T, Event = U>,
-        W: SubstateEnum<T, U, V>,
+        W: SubstateEnum<T, U, V, V>,
+        O: Observer<T>,
     {
-        /// Make a new [`TopNode`] from a [`TopState`] and a machine name.
+        /// Make a new [`TopNode`] from a [`TopState`], a machine name, an [`Observer`], and a
+        /// [`Clock`].
         #[cfg(feature = "std")]
-        pub fn new(top_state: V, name: String) -> Self {
+        pub fn new(top_state: V, name: String, observer: O, clock: Clock) -> Self {
+            let mut node = Node::from_state(top_state);
+            node.entered = clock.now();
             Self {
-                node: Node::from_state(top_state),
+                node,
+                observer,
+                outputs: Outputs::new(),
+                clock,
                 name,
             }
         }
 
-        /// Make a new [`TopNode`] from a [`TopState`] and a machine name.
+        /// Make a new [`TopNode`] from a [`TopState`], a machine name, an [`Observer`], and a
+        /// [`Clock`].
         #[cfg(not(feature = "std"))]
-        pub fn new(top_state: V, name: &'static str) -> Self {
+        pub fn new(top_state: V, name: &'static str, observer: O, clock: Clock) -> Self {
+            let mut node = Node::from_state(top_state);
+            node.entered = clock.now();
             Self {
-                node: Node::from_state(top_state),
+                node,
+                observer,
+                outputs: Outputs::new(),
+                clock,
                 name,
             }
         }
 
         /// Perform the initial transition of this node.
-        pub fn init(&mut self) {
-            match TopState::init(&mut self.node.state).into() {
+        pub fn init(&mut self, resources: &mut V::Resources) {
+            self.observer.on_action(Action::Init(W::this_state()));
+            let next = TopState::init(&mut self.node.state).into();
+            self.observer
+                .on_action_complete(Action::Init(W::this_state()));
+            match next {
                 Next::Target(target) => {
                     info!("{}: Initial transition to {target:?}", self.name());
-                    self.transition_quiet(target, false, false);
+                    self.transition_quiet(resources, target, false, false);
                     info!("\u{02514}Transition complete");
                 }
                 Next::ExactTarget(target) => {
                     info!("{}: Initial exact transition to {target:?}", self.name());
-                    self.transition_quiet(target, false, true);
+                    self.transition_quiet(resources, target, false, true);
                     info!("\u{02514}Transition complete");
                 }
                 Next::None => (),
@@ -1905,23 +4406,35 @@ pub mod internal {
         }
 
         /// Update this node and its active descendents.
-        pub fn update(&mut self) {
+        pub fn update(&mut self, resources: &mut V::Resources, input: &V::Input) {
             info!("{}: Updating", self.name());
-
-            match self.node.update(&mut TopContext(PhantomData)) {
+            self.observer
+                .on_machine_action(MachineAction::Update(self.state()));
+
+            match self.node.update(
+                &mut TopContext::new(resources, &mut self.outputs, self.clock),
+                input,
+                &mut self.observer,
+            ) {
                 Next::None => (),
                 next => {
                     match next {
                         Next::None => unreachable!(),
-                        Next::Target(target) => self.transition(target, true, false),
-                        Next::ExactTarget(target) => self.transition(target, true, true),
+                        Next::Target(target) => self.transition(resources, target, true, false),
+                        Next::ExactTarget(target) => self.transition(resources, target, true, true),
                     }
 
                     while self.node.needs_update() {
-                        match self.node.update_in_need(&mut TopContext(PhantomData)) {
+                        match self.node.update_in_need(
+                            &mut TopContext::new(resources, &mut self.outputs, self.clock),
+                            input,
+                            &mut self.observer,
+                        ) {
                             Next::None => (),
-                            Next::Target(target) => self.transition(target, true, false),
-                            Next::ExactTarget(target) => self.transition(target, true, true),
+                            Next::Target(target) => self.transition(resources, target, true, false),
+                            Next::ExactTarget(target) => {
+                                self.transition(resources, target, true, true)
+                            }
                         }
                     }
                 }
@@ -1930,26 +4443,35 @@ pub mod internal {
         }
 
         /// Top-down update this node and its active descendents.
-        pub fn top_down_update(&mut self) {
+        pub fn top_down_update(&mut self, resources: &mut V::Resources, input: &V::Input) {
             info!("{}: Top-down updating", self.name());
-
-            match self.node.top_down_update(&mut TopContext(PhantomData)) {
+            self.observer
+                .on_machine_action(MachineAction::TopDownUpdate(self.state()));
+
+            match self.node.top_down_update(
+                &mut TopContext::new(resources, &mut self.outputs, self.clock),
+                input,
+                &mut self.observer,
+            ) {
                 Next::None => (),
                 next => {
                     match next {
                         Next::None => unreachable!(),
-                        Next::Target(target) => self.transition(target, true, false),
-                        Next::ExactTarget(target) => self.transition(target, true, true),
+                        Next::Target(target) => self.transition(resources, target, true, false),
+                        Next::ExactTarget(target) => self.transition(resources, target, true, true),
                     }
 
                     loop {
-                        match self
-                            .node
-                            .top_down_update_in_need(&mut TopContext(PhantomData))
-                        {
+                        match self.node.top_down_update_in_need(
+                            &mut TopContext::new(resources, &mut self.outputs, self.clock),
+                            input,
+                            &mut self.observer,
+                        ) {
                             Next::None => break,
-                            Next::Target(target) => self.transition(target, true, false),
-                            Next::ExactTarget(target) => self.transition(target, true, true),
+                            Next::Target(target) => self.transition(resources, target, true, false),
+                            Next::ExactTarget(target) => {
+                                self.transition(resources, target, true, true)
+                            }
                         }
                     }
                 }
@@ -1960,35 +4482,52 @@ pub mod internal {
 
         /// Transition this node and its active descendents without logging the start and end of
         /// the transition.
-        pub fn transition_quiet(&mut self, target: T, indent: bool, exact: bool) {
+        pub fn transition_quiet(
+            &mut self,
+            resources: &mut V::Resources,
+            target: T,
+            indent: bool,
+            exact: bool,
+        ) {
             if !exact && self.state_matches(target) {
                 return;
             }
 
-            match self
-                .node
-                .transition(target, &mut TopContext(PhantomData), indent, exact)
-            {
+            match self.node.transition(
+                target,
+                &mut TopContext::new(resources, &mut self.outputs, self.clock),
+                indent,
+                exact,
+                &mut self.observer,
+            ) {
                 TransitionResult::MoveUp => {
                     assert!(exact);
                     if W::is_state(target) {
-                        self.init();
+                        self.init(resources);
                     } else {
-                        self.transition_quiet(target, indent, false);
+                        self.transition_quiet(resources, target, indent, false);
                     }
                 }
                 TransitionResult::Next(next) => match next {
                     Next::None => (),
-                    Next::Target(new_target) => self.transition_quiet(new_target, indent, false),
+                    Next::Target(new_target) => {
+                        self.transition_quiet(resources, new_target, indent, false)
+                    }
                     Next::ExactTarget(new_target) => {
-                        self.transition_quiet(new_target, indent, true)
+                        self.transition_quiet(resources, new_target, indent, true)
                     }
                 },
             }
         }
 
         /// Transition this node and its active descendents.
-        pub fn transition(&mut self, target: T, indent: bool, exact: bool) {
+        pub fn transition(
+            &mut self,
+            resources: &mut V::Resources,
+            target: T,
+            indent: bool,
+            exact: bool,
+        ) {
             if indent {
                 info!(
                     "\u{02502}Transitioning from {:?} to {target:?}",
@@ -2001,6 +4540,11 @@ pub mod internal {
                     self.state(),
                 );
             }
+            self.observer.on_machine_action(MachineAction::Transition {
+                source: self.state(),
+                target,
+                exact,
+            });
 
             if !exact && self.state_matches(target) {
                 info!(
@@ -2008,7 +4552,7 @@ pub mod internal {
                     if indent { "\u{02502}" } else { "" },
                 );
             } else {
-                self.transition_quiet(target, indent, exact);
+                self.transition_quiet(resources, target, indent, exact);
             }
 
             info!(
@@ -2017,6 +4561,24 @@ pub mod internal {
             );
         }
 
+        /// Exit every active node below the root node, deepest first, ignoring short circuit
+        /// transitions.
+        pub fn shutdown(&mut self, resources: &mut V::Resources) {
+            info!("{}: Shutting down from {:?}", self.name(), self.state());
+            while !W::is_state(self.state()) {
+                // each exact transition to the root exits at least one node before any short
+                // circuit transition is returned
+                let _ = self.node.transition(
+                    W::this_state(),
+                    &mut TopContext::new(resources, &mut self.outputs, self.clock),
+                    false,
+                    true,
+                    &mut self.observer,
+                );
+            }
+            info!("\u{02514}Shutdown complete");
+        }
+
         /// Get the current leaf state of this state tree.
         pub fn state(&self) -> T {
             self.node.current_state()
@@ -2042,18 +4604,45 @@ pub mod internal {
             self.node.state_matches(state)
         }
 
+        /// Get the time at which a given state was entered, if it is active.
+        pub fn entry_time(&self, state: T) -> Option<core::time::Duration> {
+            self.node.entry_time(state)
+        }
+
+        /// Get the time that a given state has been active, if it is active.
+        pub fn time_in_state(&self, state: T) -> Option<core::time::Duration> {
+            self.entry_time(state)
+                .map(|entered| self.clock.now().saturating_sub(entered))
+        }
+
         /// Handle an event.
-        pub fn handle_event(&mut self, event: &U) {
-            info!("{}: Handling event", self.name());
-            match self.node.handle_event(&mut TopContext(PhantomData), event) {
-                Response::Drop => (),
-                Response::Next(next) => match next {
-                    Next::None => (),
-                    Next::Target(target) => self.transition(target, true, false),
-                    Next::ExactTarget(target) => self.transition(target, true, true),
-                },
+        pub fn handle_event(&mut self, resources: &mut V::Resources, event: &U) -> EventOutcome<T> {
+            info!("{}: Handling event {}", self.name(), event.name());
+            let mut outcome = self.node.handle_event(
+                &mut TopContext::new(resources, &mut self.outputs, self.clock),
+                event,
+                &mut self.observer,
+            );
+            match &mut outcome {
+                EventOutcome::Transition { target, .. } => {
+                    self.transition(resources, *target, true, false);
+                    *target = self.state();
+                }
+                EventOutcome::ExactTransition { target, .. } => {
+                    self.transition(resources, *target, true, true);
+                    *target = self.state();
+                }
+                EventOutcome::Dropped { .. } | EventOutcome::Handled { .. } => (),
+                EventOutcome::Unhandled => {
+                    info!("\u{02502}Event {} unhandled", event.name());
+                    self.node.state.on_unhandled(event);
+                }
             }
+            self.observer.on_event_outcome(outcome);
+            self.observer
+                .on_machine_action(MachineAction::Event(outcome));
             info!("\u{02514}Event handled");
+            outcome
         }
     }
 }
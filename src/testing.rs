@@ -0,0 +1,216 @@
+//! Utilities for testing state machines.
+//!
+//! A [`Recorder`] is an [`Observer`] that records the [`Action`]s taken by a state machine,
+//! which can then be compared against an expected trace with [`assert_trace`](crate::assert_trace).
+//!
+//! Recorders have a fixed capacity and do not allocate, so they can be used in `no_std`
+//! environments.
+//!
+//! With the `std` feature, [`explore`] drives a state machine with random sequences of
+//! [`Step`]s while checking invariants, and shrinks any failing sequence to a minimal
+//! reproduction. [`model_check`] exhaustively explores every reachable configuration of a
+//! state machine to find unreachable states, deadlocks, and dropped events.
+//!
+//! # Example
+//! ```
+//! #[moku::state_machine]
+//! mod example {
+//!     #[moku::machine_module]
+//!     pub mod machine {}
+//!
+//!     use machine::State;
+//!
+//!     pub struct Top;
+//!     impl moku::TopState for Top {}
+//!
+//!     pub struct Foo;
+//!     impl moku::Substate<Top> for Foo {}
+//!
+//!     pub struct Bar;
+//!     impl moku::Substate<Top> for Bar {}
+//! }
+//!
+//! use moku::{testing::Recorder, *};
+//! use example::machine::{Builder, State::*};
+//!
+//! let mut machine = Builder::new(example::Top)
+//!     .observer(Recorder::new())
+//!     .build();
+//!
+//! assert_trace!(machine, transition(Foo) => [Enter(Foo), Init(Foo)]);
+//! assert_trace!(machine, transition(Bar) => [Exit(Foo), Enter(Bar), Init(Bar)]);
+//! assert_trace!(machine, update() => [Update(Bar), Update(Top)]);
+//! ```
+
+use core::fmt;
+
+use crate::{Action, Observer, StateEnum};
+
+#[cfg(feature = "std")]
+mod explore;
+
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[cfg(feature = "std")]
+pub use explore::{explore, Explorer, Failure, Rng, Step, Violation};
+
+#[cfg(feature = "std")]
+mod model;
+
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[cfg(feature = "std")]
+pub use model::{model_check, Deadlock, DroppedEvent, ModelChecker, Report};
+
+/// An [`Observer`] that records up to `N` [`Action`]s taken by a state machine.
+///
+/// Panics if more than `N` actions are recorded without clearing the recorder.
+#[derive(Clone)]
+pub struct Recorder<T: StateEnum, const N: usize = 64> {
+    actions: [Option<Action<T>>; N],
+    len: usize,
+}
+
+impl<T: StateEnum> Recorder<T> {
+    /// Make a new, empty [`Recorder`] with the default capacity of 64 actions.
+    ///
+    /// Use [`Recorder::default`] to make a [`Recorder`] with a different capacity, e.g.
+    /// `Recorder::<_, 256>::default()`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T: StateEnum, const N: usize> Recorder<T, N> {
+    /// Remove all recorded actions.
+    pub fn clear(&mut self) {
+        self.actions = [None; N];
+        self.len = 0;
+    }
+
+    /// Get the number of recorded actions.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if no actions have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get a recorded action by its index.
+    pub fn get(&self, index: usize) -> Option<Action<T>> {
+        self.actions.get(index).copied().flatten()
+    }
+
+    /// Iterate over the recorded actions in the order that they were taken.
+    pub fn iter(&self) -> impl Iterator<Item = Action<T>> + '_ {
+        self.actions[..self.len].iter().flatten().copied()
+    }
+
+    /// Check if the recorded actions are exactly equal to a trace of actions.
+    pub fn matches(&self, trace: &[Action<T>]) -> bool {
+        self.len == trace.len() && self.iter().eq(trace.iter().copied())
+    }
+}
+
+impl<T: StateEnum, const N: usize> Default for Recorder<T, N> {
+    fn default() -> Self {
+        Self {
+            actions: [None; N],
+            len: 0,
+        }
+    }
+}
+
+impl<T: StateEnum, const N: usize> Observer<T> for Recorder<T, N> {
+    fn on_action(&mut self, action: Action<T>) {
+        assert!(
+            self.len < N,
+            "Recorder capacity of {N} actions exceeded while recording {action:?}"
+        );
+        self.actions[self.len] = Some(action);
+        self.len += 1;
+    }
+}
+
+impl<T: StateEnum, const N: usize> fmt::Debug for Recorder<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Assert that a method call on a state machine takes exactly a sequence of [`Action`]s.
+///
+/// The machine must have a [`Recorder`] as its [`Observer`]. The recorder is cleared before
+/// the call, so the trace contains only the actions taken during the call.
+///
+/// Each expected action is written as the name of an [`Action`] variant, followed by its state.
+///
+/// [`Action`]: crate::Action
+/// [`Observer`]: crate::Observer
+/// [`Recorder`]: crate::testing::Recorder
+///
+/// # Example
+/// ```
+/// # #[moku::state_machine]
+/// # mod example {
+/// #     use moku::*;
+/// #
+/// #     #[machine_module]
+/// #     pub mod machine {}
+/// #     use machine::State;
+/// #
+/// #     pub struct Event;
+/// #     impl StateMachineEvent for Event {}
+/// #
+/// #     pub struct Top;
+/// #     impl TopState for Top {}
+/// #
+/// #     pub struct A;
+/// #     impl Substate<Top> for A {
+/// #         fn handle_event(
+/// #             &mut self,
+/// #             _ctx: &mut Self::Context<'_>,
+/// #             _event: &Self::Event,
+/// #         ) -> impl Into<Response<Self::State>> {
+/// #             State::B
+/// #         }
+/// #     }
+/// #
+/// #     pub struct B;
+/// #     impl Substate<Top> for B {}
+/// # }
+/// use moku::{testing::Recorder, *};
+/// use example::{machine::{Builder, State}, Event, Top};
+///
+/// let mut machine = Builder::new(Top).observer(Recorder::new()).build();
+/// machine.transition(State::A);
+///
+/// assert_trace!(
+///     machine,
+///     handle_event(&Event) => [
+///         HandleEvent(State::A),
+///         Exit(State::A),
+///         Enter(State::B),
+///         Init(State::B),
+///     ]
+/// );
+/// ```
+#[macro_export]
+macro_rules! assert_trace {
+    ($machine:expr, $method:ident($($arg:expr),* $(,)?) => [$($action:ident($state:expr)),* $(,)?]) => {{
+        let machine = &mut $machine;
+        machine.observer_mut().clear();
+        machine.$method($($arg),*);
+
+        let trace = [$($crate::Action::$action($state)),*];
+        let recorder = machine.observer();
+        if !recorder.matches(&trace) {
+            panic!(
+                "assertion `{}` failed\n  actual: {:?}\nexpected: {:?}",
+                concat!(stringify!($method), "(", stringify!($($arg),*), ")"),
+                recorder,
+                trace,
+            );
+        }
+    }};
+}
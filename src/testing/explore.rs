@@ -0,0 +1,465 @@
+use core::{fmt, marker::PhantomData};
+
+use crate::{StateEnum, StateMachine, StateMachineEvent, TopState};
+
+/// A single action taken on a state machine from outside of it.
+///
+/// Generated by the step generator of an [`Explorer`], or by [`arbitrary`](crate::arbitrary)
+/// for use with `cargo fuzz` when the `arbitrary` feature is enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step<T: StateEnum, U> {
+    /// Call [`StateMachine::handle_event`] with an event.
+    HandleEvent(U),
+
+    /// Call [`StateMachine::update`].
+    Update,
+
+    /// Call [`StateMachine::top_down_update`].
+    TopDownUpdate,
+
+    /// Call [`StateMachine::transition`] with a target state.
+    Transition(T),
+
+    /// Call [`StateMachine::exact_transition`] with a target state.
+    ExactTransition(T),
+}
+
+impl<T: StateEnum, U: StateMachineEvent> Step<T, U> {
+    /// Take this step on a state machine.
+    pub fn apply<V: TopState<Resources = (), Input = ()>>(
+        &self,
+        machine: &mut impl StateMachine<T, U, V>,
+    ) {
+        match self {
+            Step::HandleEvent(event) => {
+                machine.handle_event(event);
+            }
+            Step::Update => machine.update(),
+            Step::TopDownUpdate => machine.top_down_update(),
+            Step::Transition(target) => machine.transition(*target),
+            Step::ExactTransition(target) => machine.exact_transition(*target),
+        }
+    }
+}
+
+impl<T: StateEnum, U: fmt::Debug> fmt::Display for Step<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::HandleEvent(event) => write!(f, "handle_event(&{event:?})"),
+            Step::Update => write!(f, "update()"),
+            Step::TopDownUpdate => write!(f, "top_down_update()"),
+            Step::Transition(target) => write!(f, "transition({target:?})"),
+            Step::ExactTransition(target) => write!(f, "exact_transition({target:?})"),
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, T, U> arbitrary::Arbitrary<'a> for Step<T, U>
+where
+    T: StateEnum + arbitrary::Arbitrary<'a>,
+    U: arbitrary::Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(match u.int_in_range(0..=4)? {
+            0 => Step::HandleEvent(U::arbitrary(u)?),
+            1 => Step::Update,
+            2 => Step::TopDownUpdate,
+            3 => Step::Transition(T::arbitrary(u)?),
+            _ => Step::ExactTransition(T::arbitrary(u)?),
+        })
+    }
+}
+
+/// A small, seedable pseudorandom number generator for generating [`Step`]s.
+///
+/// Not suitable for cryptographic use.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Make a new [`Rng`] from a seed.
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Generate a random `u64`.
+    pub fn next_u64(&mut self) -> u64 {
+        // splitmix64
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Generate a random `usize` in the range `0..n`.
+    ///
+    /// Panics if `n` is zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "Rng::below called with n = 0");
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Generate a random `bool`.
+    pub fn bool(&mut self) -> bool {
+        self.next_u64() & 1 == 1
+    }
+
+    /// Choose a random item from a slice.
+    ///
+    /// Panics if the slice is empty.
+    pub fn choose<'a, X>(&mut self, items: &'a [X]) -> &'a X {
+        &items[self.below(items.len())]
+    }
+
+    /// Choose a random state of a state machine.
+    pub fn state<T: StateEnum>(&mut self) -> T {
+        *self.choose(T::ALL)
+    }
+}
+
+/// A violated property of a state machine, found by an [`Explorer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation<T: StateEnum> {
+    /// A user invariant returned `false`.
+    Invariant(&'static str),
+
+    /// [`StateMachine::state_matches`] disagreed with the active states of the machine.
+    StateMatches {
+        /// The state that was checked.
+        state: T,
+
+        /// The active states of the machine, from [`StateMachine::state_list`].
+        active: Vec<T>,
+    },
+
+    /// [`StateMachine::state`] was not the deepest active state of the machine.
+    State {
+        /// The state returned by [`StateMachine::state`].
+        state: T,
+
+        /// The active states of the machine, from [`StateMachine::state_list`].
+        active: Vec<T>,
+    },
+}
+
+impl<T: StateEnum> Violation<T> {
+    /// Check if two violations are of the same kind, and of the same invariant if any, ignoring
+    /// the states that they were found in.
+    ///
+    /// Steps are only removed while shrinking if the violation stays of the same kind.
+    pub fn is_same_kind(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Violation::Invariant(name), Violation::Invariant(other)) => name == other,
+            _ => core::mem::discriminant(self) == core::mem::discriminant(other),
+        }
+    }
+}
+
+impl<T: StateEnum> fmt::Display for Violation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Invariant(name) => write!(f, "invariant `{name}` violated"),
+            Violation::StateMatches { state, active } => write!(
+                f,
+                "state_matches({state:?}) disagrees with the active states {active:?}"
+            ),
+            Violation::State { state, active } => write!(
+                f,
+                "state() returned {state:?} but the active states are {active:?}"
+            ),
+        }
+    }
+}
+
+/// A minimal sequence of [`Step`]s that results in a [`Violation`], found by an [`Explorer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure<T: StateEnum, U> {
+    /// The seed of the run that first found the violation, if found by [`Explorer::run`].
+    pub seed: Option<u64>,
+
+    /// The steps that result in the violation when taken on a newly built machine.
+    pub steps: Vec<Step<T, U>>,
+
+    /// The violated property.
+    pub violation: Violation<T>,
+}
+
+impl<T: StateEnum, U: fmt::Debug> fmt::Display for Failure<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} after {} steps", self.violation, self.steps.len())?;
+        if let Some(seed) = self.seed {
+            write!(f, " (seed {seed})")?;
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            write!(f, "\n  {}. {step}", i + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: StateEnum, U: fmt::Debug> std::error::Error for Failure<T, U> {}
+
+type Generator<T, U> = Box<dyn FnMut(&mut Rng) -> Step<T, U>>;
+type Invariant<M> = (&'static str, Box<dyn Fn(&M) -> bool>);
+
+/// Randomized exploration of a state machine, created with [`explore`].
+pub struct Explorer<T, U, V, M>
+where
+    T: StateEnum,
+    U: StateMachineEvent + Clone + fmt::Debug,
+    V: TopState<Resources = (), Input = ()>,
+    M: StateMachine<T, U, V>,
+{
+    build: Box<dyn FnMut() -> M>,
+    generator: Generator<T, U>,
+    invariants: Vec<Invariant<M>>,
+    runs: usize,
+    max_steps: usize,
+    seed: u64,
+    phantom: PhantomData<V>,
+}
+
+/// Explore a state machine with random sequences of [`Step`]s.
+///
+/// Each run builds a new machine with `build` and takes up to a maximum number of steps on it.
+/// After building the machine and after every step, the [`Explorer`] checks every user invariant
+/// as well as the internal consistency of the machine, i.e. that [`StateMachine::state`] and
+/// [`StateMachine::state_matches`] agree with [`StateMachine::state_list`].
+///
+/// When a violation is found, the sequence of steps that caused it is shrunk to a minimal
+/// sequence that still results in a violation and returned as a [`Failure`].
+///
+/// By default, steps are generated uniformly from updates, top-down updates, and transitions to
+/// random states, and no events are handled. Use [`Explorer::generator`] to generate events.
+///
+/// Only machines without [`TopState::Resources`] or [`TopState::Input`] can be explored.
+///
+/// # Example
+/// ```
+/// #[moku::state_machine]
+/// mod counter {
+///     use moku::*;
+///
+///     #[machine_module]
+///     pub mod machine {}
+///     use machine::State;
+///
+///     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///     pub enum Event {
+///         Increment,
+///         Reset,
+///     }
+///     impl StateMachineEvent for Event {}
+///
+///     pub struct Top {
+///         pub count: u8,
+///     }
+///     impl TopState for Top {
+///         fn handle_event(&mut self, event: &Event) -> impl Into<Next<Self::State>> {
+///             match event {
+///                 Event::Increment => self.count += 1,
+///                 Event::Reset => self.count = 0,
+///             }
+///         }
+///     }
+/// }
+///
+/// use moku::{testing::*, *};
+/// use counter::{machine::Builder, Event, Top};
+///
+/// let failure = explore(|| Builder::new(Top { count: 0 }).build())
+///     .generator(|rng| match rng.below(4) {
+///         0 => Step::HandleEvent(Event::Reset),
+///         _ => Step::HandleEvent(Event::Increment),
+///     })
+///     .invariant("count is less than 3", |machine| machine.top_ref().count < 3)
+///     .run()
+///     .unwrap_err();
+///
+/// assert_eq!(failure.violation, Violation::Invariant("count is less than 3"));
+/// assert_eq!(failure.steps, vec![Step::HandleEvent(Event::Increment); 3]);
+/// ```
+pub fn explore<T, U, V, M>(build: impl FnMut() -> M + 'static) -> Explorer<T, U, V, M>
+where
+    T: StateEnum,
+    U: StateMachineEvent + Clone + fmt::Debug,
+    V: TopState<Resources = (), Input = ()>,
+    M: StateMachine<T, U, V>,
+{
+    Explorer {
+        build: Box::new(build),
+        generator: Box::new(|rng| match rng.below(4) {
+            0 => Step::Update,
+            1 => Step::TopDownUpdate,
+            2 => Step::Transition(rng.state()),
+            _ => Step::ExactTransition(rng.state()),
+        }),
+        invariants: Vec::new(),
+        runs: 256,
+        max_steps: 64,
+        seed: 0,
+        phantom: PhantomData,
+    }
+}
+
+impl<T, U, V, M> Explorer<T, U, V, M>
+where
+    T: StateEnum,
+    U: StateMachineEvent + Clone + fmt::Debug,
+    V: TopState<Resources = (), Input = ()>,
+    M: StateMachine<T, U, V>,
+{
+    /// Set the function used to generate each random [`Step`].
+    pub fn generator(mut self, generator: impl FnMut(&mut Rng) -> Step<T, U> + 'static) -> Self {
+        self.generator = Box::new(generator);
+        self
+    }
+
+    /// Add a named invariant that must hold after the machine is built and after every step.
+    pub fn invariant(mut self, name: &'static str, check: impl Fn(&M) -> bool + 'static) -> Self {
+        self.invariants.push((name, Box::new(check)));
+        self
+    }
+
+    /// Set the number of runs, each with a newly built machine. Defaults to 256.
+    pub fn runs(mut self, runs: usize) -> Self {
+        self.runs = runs;
+        self
+    }
+
+    /// Set the maximum number of steps taken in each run. Defaults to 64.
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Set the seed of the first run. Each subsequent run uses the next seed. Defaults to 0.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Explore the machine, returning the first [`Failure`] found, shrunk to a minimal sequence
+    /// of steps.
+    pub fn run(mut self) -> Result<(), Failure<T, U>> {
+        for run in 0..self.runs {
+            let seed = self.seed.wrapping_add(run as u64);
+            let mut rng = Rng::new(seed);
+            let mut steps = Vec::new();
+            let mut machine = (self.build)();
+
+            let mut violation = self.violation(&machine);
+            while violation.is_none() && steps.len() < self.max_steps {
+                let step = (self.generator)(&mut rng);
+                step.apply(&mut machine);
+                steps.push(step);
+                violation = self.violation(&machine);
+            }
+
+            if let Some(violation) = violation {
+                let (steps, violation) = self.shrink(steps, violation);
+                return Err(Failure {
+                    seed: Some(seed),
+                    steps,
+                    violation,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Take a given sequence of steps on a newly built machine, checking for violations after
+    /// every step.
+    ///
+    /// Useful for checking sequences of steps generated by a fuzzer. The sequence is not shrunk.
+    pub fn replay(&mut self, steps: &[Step<T, U>]) -> Result<(), Failure<T, U>> {
+        match self.first_violation(steps) {
+            None => Ok(()),
+            Some((len, violation)) => Err(Failure {
+                seed: None,
+                steps: steps[..len].to_vec(),
+                violation,
+            }),
+        }
+    }
+
+    /// Check the invariants and internal consistency of a machine.
+    fn violation(&self, machine: &M) -> Option<Violation<T>> {
+        let active = machine.state_list();
+
+        if active.last() != Some(&machine.state()) {
+            return Some(Violation::State {
+                state: machine.state(),
+                active,
+            });
+        }
+
+        for &state in T::ALL {
+            if machine.state_matches(state) != active.contains(&state) {
+                return Some(Violation::StateMatches { state, active });
+            }
+        }
+
+        self.invariants
+            .iter()
+            .find(|(_, check)| !check(machine))
+            .map(|(name, _)| Violation::Invariant(name))
+    }
+
+    /// Find the number of steps after which a sequence of steps first results in a violation.
+    fn first_violation(&mut self, steps: &[Step<T, U>]) -> Option<(usize, Violation<T>)> {
+        let mut machine = (self.build)();
+
+        if let Some(violation) = self.violation(&machine) {
+            return Some((0, violation));
+        }
+
+        for (i, step) in steps.iter().enumerate() {
+            step.apply(&mut machine);
+            if let Some(violation) = self.violation(&machine) {
+                return Some((i + 1, violation));
+            }
+        }
+
+        None
+    }
+
+    /// Shrink a sequence of steps that results in a violation by removing ever smaller chunks
+    /// of steps, for as long as the sequence still results in the same kind of violation.
+    fn shrink(
+        &mut self,
+        mut steps: Vec<Step<T, U>>,
+        mut violation: Violation<T>,
+    ) -> (Vec<Step<T, U>>, Violation<T>) {
+        let mut chunk = steps.len() / 2;
+
+        while chunk > 0 {
+            let mut start = 0;
+
+            while start < steps.len() {
+                let end = (start + chunk).min(steps.len());
+                let candidate: Vec<_> = steps[..start]
+                    .iter()
+                    .chain(&steps[end..])
+                    .cloned()
+                    .collect();
+
+                match self.first_violation(&candidate) {
+                    Some((len, new_violation)) if new_violation.is_same_kind(&violation) => {
+                        steps = candidate;
+                        steps.truncate(len);
+                        violation = new_violation;
+                    }
+                    _ => start += chunk,
+                }
+            }
+
+            chunk /= 2;
+        }
+
+        (steps, violation)
+    }
+}
@@ -0,0 +1,357 @@
+use core::{fmt, hash::Hash, marker::PhantomData};
+use std::collections::{HashSet, VecDeque};
+
+use super::Step;
+use crate::{EventOutcome, StateEnum, StateMachine, StateMachineEvent, TopState};
+
+/// A configuration with no way out, found by a [`ModelChecker`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deadlock<T: StateEnum, U> {
+    /// The active state of the configuration.
+    pub state: T,
+
+    /// The shortest sequence of steps that reaches the configuration from a newly built machine.
+    pub steps: Vec<Step<T, U>>,
+}
+
+/// An event that is unhandled by every active state of some configuration and leaves it
+/// unchanged, found by a [`ModelChecker`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DroppedEvent<T: StateEnum, U> {
+    /// The active state when the event is dropped.
+    pub state: T,
+
+    /// The dropped event.
+    pub event: U,
+
+    /// The shortest sequence of steps that reaches the configuration in which the event is
+    /// dropped from a newly built machine.
+    pub steps: Vec<Step<T, U>>,
+}
+
+/// The results of a [`ModelChecker`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report<T: StateEnum, U> {
+    /// The number of distinct configurations explored.
+    pub configurations: usize,
+
+    /// Whether every reachable configuration was explored, i.e. the configuration limit was not
+    /// reached.
+    pub complete: bool,
+
+    /// States that are not active in any explored configuration.
+    pub unreached: Vec<T>,
+
+    /// The first configuration with no way out found for each active state.
+    pub deadlocks: Vec<Deadlock<T, U>>,
+
+    /// The first configuration found in which each event is dropped in each active state.
+    pub dropped: Vec<DroppedEvent<T, U>>,
+}
+
+impl<T: StateEnum, U> Report<T, U> {
+    /// Check if no unreached states, deadlocks, or dropped events were found.
+    pub fn is_ok(&self) -> bool {
+        self.unreached.is_empty() && self.deadlocks.is_empty() && self.dropped.is_empty()
+    }
+}
+
+impl<T: StateEnum, U: fmt::Debug> fmt::Display for Report<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "explored {} configurations{}",
+            self.configurations,
+            if self.complete { "" } else { " (incomplete)" }
+        )?;
+
+        if !self.unreached.is_empty() {
+            write!(f, "\nunreached states: {:?}", self.unreached)?;
+        }
+
+        for deadlock in &self.deadlocks {
+            write!(f, "\ndeadlock in {:?} after", deadlock.state)?;
+            write_steps(f, &deadlock.steps)?;
+        }
+
+        for dropped in &self.dropped {
+            write!(
+                f,
+                "\n{:?} dropped in {:?} after",
+                dropped.event, dropped.state
+            )?;
+            write_steps(f, &dropped.steps)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_steps<T: StateEnum, U: fmt::Debug>(
+    f: &mut fmt::Formatter<'_>,
+    steps: &[Step<T, U>],
+) -> fmt::Result {
+    if steps.is_empty() {
+        write!(f, " build()")
+    } else {
+        steps.iter().try_for_each(|step| write!(f, " {step}"))
+    }
+}
+
+/// Exhaustive exploration of the configurations of a state machine, created with
+/// [`model_check`].
+pub struct ModelChecker<T, U, V, M>
+where
+    T: StateEnum,
+    U: StateMachineEvent + Clone,
+    V: TopState<Resources = (), Input = ()>,
+    M: StateMachine<T, U, V> + Clone + Hash + Eq,
+{
+    machine: M,
+    events: Vec<U>,
+    max_configurations: usize,
+    phantom: PhantomData<(T, V)>,
+}
+
+/// Exhaustively explore every configuration of a state machine that is reachable over a finite
+/// alphabet of events plus [`StateMachine::update`].
+///
+/// A configuration is the set of active states along with their data, so every state must
+/// implement `Clone`, `Hash`, and `Eq`. The [`Observer`](crate::Observer) of the machine is
+/// cloned along with it, but is not part of its configuration.
+///
+/// Only machines without [`TopState::Resources`] or [`TopState::Input`] can be model checked.
+///
+/// Configurations are explored breadth-first, starting from the given newly built machine.
+/// The resulting [`Report`] lists:
+/// - states that are never active in any configuration
+/// - configurations with no way out, i.e. those that no event or update changes
+/// - events that are silently dropped, i.e. that are unhandled by every active state and do not
+///   change the configuration
+///
+/// Events that a state explicitly drops with [`Response::Drop`](crate::Response::Drop) are
+/// intentionally ignored, so they are not reported.
+///
+/// Deadlocks and dropped events are reported once per active state, along with the shortest
+/// sequence of steps that reproduces them.
+///
+/// # Example
+/// ```
+/// #[moku::state_machine]
+/// mod door {
+///     use moku::*;
+///
+///     #[machine_module]
+///     pub mod machine {}
+///     use machine::State;
+///
+///     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///     pub enum Event {
+///         Open,
+///         Close,
+///     }
+///     impl StateMachineEvent for Event {}
+///
+///     #[derive(Clone, PartialEq, Eq, Hash)]
+///     pub struct Top;
+///     impl TopState for Top {
+///         fn init(&mut self) -> impl Into<Next<Self::State>> {
+///             State::Closed
+///         }
+///     }
+///
+///     #[derive(Clone, PartialEq, Eq, Hash)]
+///     struct Closed;
+///     impl Substate<Top> for Closed {
+///         fn handle_event(
+///             &mut self,
+///             _ctx: &mut Self::Context<'_>,
+///             event: &Self::Event,
+///         ) -> impl Into<Response<Self::State>> {
+///             match event {
+///                 Event::Open => Response::Next(State::Opened.into()),
+///                 Event::Close => Response::Drop,
+///             }
+///         }
+///     }
+///
+///     #[derive(Clone, PartialEq, Eq, Hash)]
+///     struct Opened;
+///     impl Substate<Top> for Opened {
+///         fn handle_event(
+///             &mut self,
+///             _ctx: &mut Self::Context<'_>,
+///             event: &Self::Event,
+///         ) -> impl Into<Response<Self::State>> {
+///             match event {
+///                 Event::Close => Response::Next(State::Closed.into()),
+///                 Event::Open => Response::Next(Next::None),
+///             }
+///         }
+///     }
+///
+///     #[derive(Clone, PartialEq, Eq, Hash)]
+///     struct Broken;
+///     impl Substate<Top> for Broken {}
+/// }
+///
+/// use moku::{testing::*, *};
+/// use door::{machine::{Builder, State}, Event, Top};
+///
+/// let report = model_check(Builder::new(Top).build())
+///     .events([Event::Open, Event::Close])
+///     .run();
+///
+/// assert_eq!(report.configurations, 2);
+/// assert_eq!(report.unreached, [State::Broken]);
+/// assert!(report.deadlocks.is_empty());
+/// assert_eq!(report.dropped.len(), 1);
+/// assert_eq!(report.dropped[0].state, State::Opened);
+/// assert_eq!(report.dropped[0].event, Event::Open);
+/// ```
+pub fn model_check<T, U, V, M>(machine: M) -> ModelChecker<T, U, V, M>
+where
+    T: StateEnum,
+    U: StateMachineEvent + Clone,
+    V: TopState<Resources = (), Input = ()>,
+    M: StateMachine<T, U, V> + Clone + Hash + Eq,
+{
+    ModelChecker {
+        machine,
+        events: Vec::new(),
+        max_configurations: 100_000,
+        phantom: PhantomData,
+    }
+}
+
+impl<T, U, V, M> ModelChecker<T, U, V, M>
+where
+    T: StateEnum,
+    U: StateMachineEvent + Clone,
+    V: TopState<Resources = (), Input = ()>,
+    M: StateMachine<T, U, V> + Clone + Hash + Eq,
+{
+    /// Set the alphabet of events handled in each configuration.
+    pub fn events(mut self, events: impl IntoIterator<Item = U>) -> Self {
+        self.events = events.into_iter().collect();
+        self
+    }
+
+    /// Set the maximum number of configurations to explore. Defaults to 100,000.
+    ///
+    /// Machines with unbounded state data, e.g. counters, have an unbounded number of
+    /// configurations, so exploration stops and the [`Report`] is marked incomplete once this
+    /// limit is reached.
+    pub fn max_configurations(mut self, max_configurations: usize) -> Self {
+        self.max_configurations = max_configurations;
+        self
+    }
+
+    /// Explore every reachable configuration and report the results.
+    pub fn run(self) -> Report<T, U> {
+        let steps: Vec<_> = self
+            .events
+            .iter()
+            .cloned()
+            .map(Step::HandleEvent)
+            .chain([Step::Update])
+            .collect();
+
+        // the step that first reached each configuration and the configuration it was taken from
+        let mut parents: Vec<Option<(usize, usize)>> = vec![None];
+        let mut seen = HashSet::from([self.machine.clone()]);
+        let mut queue = VecDeque::from([(0, self.machine)]);
+
+        let mut reached = vec![false; T::ALL.len()];
+        let mut complete = true;
+        let mut deadlocks: Vec<Deadlock<T, U>> = Vec::new();
+        let mut dropped: Vec<(T, usize, usize)> = Vec::new();
+
+        let trace = |parents: &[Option<(usize, usize)>], mut id: usize| {
+            let mut trace = Vec::new();
+            while let Some((parent, step)) = parents[id] {
+                trace.push(steps[step].clone());
+                id = parent;
+            }
+            trace.reverse();
+            trace
+        };
+
+        while let Some((id, machine)) = queue.pop_front() {
+            for state in machine.state_list() {
+                if let Some(i) = T::ALL.iter().position(|&s| s == state) {
+                    reached[i] = true;
+                }
+            }
+
+            let state = machine.state();
+            let mut way_out = false;
+
+            for (step_index, step) in steps.iter().enumerate() {
+                let mut next = machine.clone();
+                let outcome = match step {
+                    Step::HandleEvent(event) => Some(next.handle_event(event)),
+                    _ => {
+                        step.apply(&mut next);
+                        None
+                    }
+                };
+
+                if next == machine {
+                    if outcome == Some(EventOutcome::Unhandled)
+                        && !dropped
+                            .iter()
+                            .any(|&(s, e, _)| s == state && e == step_index)
+                    {
+                        dropped.push((state, step_index, id));
+                    }
+                    continue;
+                }
+
+                way_out = true;
+
+                if !seen.contains(&next) {
+                    if seen.len() >= self.max_configurations {
+                        complete = false;
+                        continue;
+                    }
+
+                    let next_id = parents.len();
+                    parents.push(Some((id, step_index)));
+                    seen.insert(next.clone());
+                    queue.push_back((next_id, next));
+                }
+            }
+
+            if !way_out && !deadlocks.iter().any(|deadlock| deadlock.state == state) {
+                deadlocks.push(Deadlock {
+                    state,
+                    steps: trace(&parents, id),
+                });
+            }
+        }
+
+        Report {
+            configurations: seen.len(),
+            complete,
+            unreached: T::ALL
+                .iter()
+                .zip(reached)
+                .filter(|(_, reached)| !reached)
+                .map(|(&state, _)| state)
+                .collect(),
+            deadlocks,
+            dropped: dropped
+                .into_iter()
+                .map(|(state, step, id)| DroppedEvent {
+                    state,
+                    event: match &steps[step] {
+                        Step::HandleEvent(event) => event.clone(),
+                        _ => unreachable!(),
+                    },
+                    steps: trace(&parents, id),
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "arbitrary")]
+
+use moku::{
+    arbitrary::{Arbitrary, Unstructured},
+    testing::*,
+    *,
+};
+use test_log::test;
+use tester::{
+    machine::{Builder, State},
+    Event, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Event;
+    impl StateMachineEvent for Event {}
+
+    impl<'a> arbitrary::Arbitrary<'a> for Event {
+        fn arbitrary(_u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+            Ok(Event)
+        }
+    }
+
+    pub struct Top;
+    impl TopState for Top {}
+
+    struct A;
+    impl Substate<Top> for A {}
+
+    struct AA;
+    impl Substate<A> for AA {}
+
+    struct B;
+    impl Substate<Top> for B {}
+}
+
+#[test]
+fn arbitrary_state() {
+    let bytes: Vec<u8> = (0..=255).collect();
+    let mut u = Unstructured::new(&bytes);
+
+    let mut seen = Vec::new();
+    while !u.is_empty() {
+        let state = State::arbitrary(&mut u).unwrap();
+        if !seen.contains(&state) {
+            seen.push(state);
+        }
+    }
+
+    assert_eq!(seen.len(), State::ALL.len());
+}
+
+#[test]
+fn fuzz_steps() {
+    let bytes: Vec<u8> = (0..=255).rev().collect();
+    let steps = Vec::<Step<State, Event>>::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+    assert!(!steps.is_empty());
+
+    explore(|| Builder::new(Top).build())
+        .replay(&steps)
+        .unwrap();
+}
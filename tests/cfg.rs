@@ -0,0 +1,123 @@
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, State, STATE_CHART},
+    Event, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Next,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Top<T: Default> {
+        pub value: T,
+    }
+
+    impl<T: Default> TopState for Top<T> {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Enabled
+        }
+    }
+
+    #[cfg(test)]
+    #[derive(Debug, Clone, PartialEq)]
+    struct Enabled;
+
+    #[cfg(test)]
+    impl<T: Default> Substate<Top<T>> for Enabled {
+        #[on(Event::Next)]
+        fn next() -> State {
+            State::Lonely
+        }
+    }
+
+    #[cfg(test)]
+    #[derive(Debug, Clone, PartialEq)]
+    struct EnabledChild;
+
+    #[cfg(test)]
+    impl Substate<Enabled> for EnabledChild {}
+
+    // every child of this state is compiled out
+    #[derive(Debug, Clone, PartialEq)]
+    struct Lonely;
+    impl<T: Default> Substate<Top<T>> for Lonely {}
+
+    #[cfg(not(test))]
+    struct Disabled;
+
+    #[cfg(not(test))]
+    impl Substate<Lonely> for Disabled {
+        #[on(Event::Next)]
+        fn next() -> State {
+            State::Enabled
+        }
+    }
+
+    #[cfg(not(test))]
+    struct DisabledChild;
+
+    #[cfg(not(test))]
+    impl Substate<Disabled> for DisabledChild {}
+
+    #[cfg(not(test))]
+    struct Hidden;
+
+    #[cfg(not(test))]
+    impl<T: Default> Substate<Top<T>> for Hidden {}
+
+    #[cfg(not(test))]
+    mod diagnostics {
+        use super::*;
+
+        struct Diagnostics;
+        impl Substate<Enabled> for Diagnostics {}
+    }
+}
+
+#[test]
+fn compiled_out_states() {
+    assert_eq!(
+        <State as StateEnum>::ALL,
+        [
+            State::Top,
+            State::Enabled,
+            State::EnabledChild,
+            State::Lonely
+        ]
+    );
+    assert_eq!(
+        STATE_CHART,
+        "Top
+├─ Enabled
+│  └─ EnabledChild
+└─ Lonely"
+    );
+}
+
+#[test]
+fn transitions() {
+    let mut machine = Builder::new(Top { value: 'x' }).build();
+    assert_eq!(machine.state(), State::Enabled);
+
+    machine.handle_event(&Event::Next);
+    assert_eq!(machine.state(), State::Lonely);
+    assert_eq!(
+        format!("{:?}", machine.state_debug(State::Lonely).unwrap()),
+        "Lonely"
+    );
+    assert_eq!(machine.handle_event(&Event::Next), EventOutcome::Unhandled);
+
+    machine.transition(State::Enabled);
+    assert!(machine.clone() == machine);
+}
@@ -7,4 +7,7 @@ fn compile_fail() {
     t.compile_fail("tests/compile_fail/event/*.rs");
     t.compile_fail("tests/compile_fail/state/*.rs");
     t.compile_fail("tests/compile_fail/state_machine/*.rs");
+    t.compile_fail("tests/compile_fail/scxml/*.rs");
+    t.compile_fail("tests/compile_fail/handlers/*.rs");
+    t.compile_fail("tests/compile_fail/submachine/*.rs");
 }
@@ -0,0 +1,8 @@
+use moku::*;
+
+#[derive(StateMachineEvent)]
+union Event {
+    a: u8,
+}
+
+fn main() {}
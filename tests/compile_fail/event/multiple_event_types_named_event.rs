@@ -7,13 +7,13 @@ mod blinky {
     #[machine_module]
     mod machine {}
 
-    struct EventA;
+    struct Event;
 
-    impl StateMachineEvent for EventA {}
+    impl StateMachineEvent for Event {}
 
-    struct EventB;
+    struct Tick;
 
-    impl StateMachineEvent for EventB {}
+    impl StateMachineEvent for Tick {}
 
     struct Top;
 
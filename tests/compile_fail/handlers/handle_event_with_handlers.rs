@@ -0,0 +1,31 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    mod machine {}
+
+    struct Event;
+    impl StateMachineEvent for Event {}
+
+    struct Top;
+    impl TopState for Top {}
+
+    struct Enabled;
+
+    impl Substate<Top> for Enabled {
+        #[on(Event)]
+        fn toggle(&mut self) {}
+
+        fn handle_event(
+            &mut self,
+            _ctx: &mut Self::Context<'_>,
+            _event: &Self::Event,
+        ) -> impl Into<Response<Self::State>> {
+        }
+    }
+}
+
+fn main() {}
@@ -0,0 +1,24 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    mod machine {}
+
+    struct Event;
+    impl StateMachineEvent for Event {}
+
+    struct Top;
+    impl TopState for Top {}
+
+    struct Enabled;
+
+    impl Substate<Top> for Enabled {
+        #[on(Event)]
+        fn update(&mut self) {}
+    }
+}
+
+fn main() {}
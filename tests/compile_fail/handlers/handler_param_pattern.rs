@@ -0,0 +1,24 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    mod machine {}
+
+    struct Event(u8);
+    impl StateMachineEvent for Event {}
+
+    struct Top;
+    impl TopState for Top {}
+
+    struct Enabled;
+
+    impl Substate<Top> for Enabled {
+        #[on(Event(value))]
+        fn toggle(&mut self, (value,): (u8,)) {}
+    }
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+struct Foo;
+
+impl Foo {
+    #[moku::on(Event)]
+    fn toggle(&mut self) {}
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    mod machine {}
+
+    struct Top;
+    impl TopState for Top {}
+
+    struct Enabled;
+
+    #[unmatched(ignore)]
+    impl Substate<Top> for Enabled {}
+}
+
+fn main() {}
@@ -0,0 +1,18 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    mod states {
+        use moku::*;
+
+        #[machine_module]
+        mod machine {}
+    }
+
+    struct Top;
+    impl TopState for Top {}
+}
+
+fn main() {}
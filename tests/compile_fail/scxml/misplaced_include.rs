@@ -0,0 +1,3 @@
+moku::include_scxml!("unknown_target.scxml");
+
+fn main() {}
@@ -0,0 +1,10 @@
+#[moku::state_machine]
+mod chart {
+    #[moku::machine_module]
+    pub mod machine {}
+
+    moku::include_scxml!("wrong_superstate.scxml");
+    moku::include_scxml!("wrong_superstate.scxml");
+}
+
+fn main() {}
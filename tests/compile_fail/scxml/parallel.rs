@@ -0,0 +1,9 @@
+#[moku::state_machine]
+mod chart {
+    #[moku::machine_module]
+    pub mod machine {}
+
+    moku::include_scxml!("parallel.scxml");
+}
+
+fn main() {}
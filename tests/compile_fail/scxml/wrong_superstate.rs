@@ -0,0 +1,14 @@
+#[moku::state_machine]
+mod chart {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+
+    include_scxml!("wrong_superstate.scxml");
+
+    pub struct LedOn;
+    impl Substate<Top> for LedOn {}
+}
+
+fn main() {}
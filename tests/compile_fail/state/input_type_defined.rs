@@ -13,7 +13,7 @@ mod blinky {
     struct Bottom;
 
     impl Substate<Top> for Bottom {
-        type Event = ();
+        type Input = u32;
     }
 }
 
@@ -0,0 +1,19 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    mod machine {}
+
+    struct Top;
+    impl TopState for Top {}
+
+    #[describe("The LED is lit.")]
+    #[describe("The LED is on.")]
+    struct On;
+    impl Substate<Top> for On {}
+}
+
+fn main() {}
@@ -0,0 +1,21 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+
+    #[derive(Debug)]
+    pub struct Top;
+    impl TopState for Top {}
+
+    struct Disabled;
+    impl Substate<Top> for Disabled {}
+}
+
+fn main() {
+    let machine = blinky::machine::Builder::new(blinky::Top).build();
+    machine.state_debug(blinky::machine::State::Top);
+}
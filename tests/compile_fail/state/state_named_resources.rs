@@ -0,0 +1,17 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    mod machine {}
+
+    struct Top;
+    impl TopState for Top {}
+
+    struct Resources;
+    impl Substate<Top> for Resources {}
+}
+
+fn main() {}
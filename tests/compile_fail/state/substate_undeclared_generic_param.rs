@@ -0,0 +1,20 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    mod machine {}
+
+    struct Top<T> {
+        t: T,
+    }
+    impl<T> TopState for Top<T> {}
+
+    struct Bottom;
+
+    impl Substate<Top<T>> for Bottom {}
+}
+
+fn main() {}
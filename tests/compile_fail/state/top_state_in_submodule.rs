@@ -0,0 +1,18 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    mod machine {}
+
+    mod states {
+        use moku::*;
+
+        struct Top;
+        impl TopState for Top {}
+    }
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    mod machine {}
+
+    struct Top;
+
+    #[transitions(LedOn)]
+    impl TopState for Top {}
+
+    struct LedOn;
+    impl Substate<Top> for LedOn {}
+}
+
+fn main() {}
@@ -0,0 +1,30 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    mod machine {}
+
+    struct Top;
+    impl TopState for Top {}
+
+    struct Blinking;
+
+    // the initial substate must be declared as a transition too
+    #[transitions(Disabled)]
+    impl Substate<Top> for Blinking {
+        fn init(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            Self::State::LedOn
+        }
+    }
+
+    struct LedOn;
+    impl Substate<Blinking> for LedOn {}
+
+    struct Disabled;
+    impl Substate<Top> for Disabled {}
+}
+
+fn main() {}
@@ -0,0 +1,29 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    mod machine {}
+
+    struct Top;
+    impl TopState for Top {}
+
+    struct LedOn;
+
+    #[transitions(LedOff)]
+    impl Substate<Top> for LedOn {
+        fn update(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            Self::State::Disabled
+        }
+    }
+
+    struct LedOff;
+    impl Substate<Top> for LedOff {}
+
+    struct Disabled;
+    impl Substate<Top> for Disabled {}
+}
+
+fn main() {}
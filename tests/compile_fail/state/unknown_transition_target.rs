@@ -0,0 +1,22 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    mod machine {}
+
+    struct Top;
+    impl TopState for Top {}
+
+    struct LedOn;
+
+    #[transitions(LedOff, Disabled)]
+    impl Substate<Top> for LedOn {}
+
+    struct LedOff;
+    impl Substate<Top> for LedOff {}
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+struct Foo;
+
+#[moku::submachine(inner)]
+impl Foo {}
+
+fn main() {}
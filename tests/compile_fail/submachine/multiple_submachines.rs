@@ -0,0 +1,20 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    mod machine {}
+
+    struct Top;
+    impl TopState for Top {}
+
+    struct Blinking;
+
+    #[submachine(first)]
+    #[submachine(second)]
+    impl Substate<Top> for Blinking {}
+}
+
+fn main() {}
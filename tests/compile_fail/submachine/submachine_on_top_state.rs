@@ -0,0 +1,16 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    mod machine {}
+
+    struct Top;
+
+    #[submachine(inner)]
+    impl TopState for Top {}
+}
+
+fn main() {}
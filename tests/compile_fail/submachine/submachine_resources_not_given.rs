@@ -0,0 +1,60 @@
+use moku::*;
+
+#[state_machine]
+mod inner {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Ping,
+    }
+
+    pub struct Top;
+    impl TopState for Top {
+        type Resources = u8;
+    }
+}
+
+#[state_machine]
+mod outer {
+    use moku::*;
+
+    use super::inner;
+
+    #[machine_module]
+    mod machine {}
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Ping,
+    }
+
+    impl TryFrom<&Event> for inner::Event {
+        type Error = ();
+
+        fn try_from(_event: &Event) -> Result<Self, ()> {
+            Ok(inner::Event::Ping)
+        }
+    }
+
+    struct Top;
+    impl TopState for Top {}
+
+    struct Nested {
+        inner: inner::machine::Machine,
+    }
+
+    #[submachine(inner)]
+    impl Substate<Top> for Nested {
+        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            Self {
+                inner: inner::machine::Builder::new(inner::Top).build_with(&mut 0),
+            }
+        }
+    }
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use moku::*;
+
+#[state_machine]
+mod blinky {
+    use moku::*;
+
+    #[machine_module]
+    mod machine {}
+
+    struct Top;
+    impl TopState for Top {}
+
+    struct Blinking;
+
+    #[submachine(inner, outputs = ctx.outputs)]
+    impl Substate<Top> for Blinking {}
+}
+
+fn main() {}
@@ -0,0 +1,87 @@
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, State},
+    Top,
+};
+
+#[state_machine(Blinky)]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(Debug)]
+    pub struct Top {
+        pub period: u8,
+    }
+
+    impl TopState for Top {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::LedOn
+        }
+    }
+
+    struct Disabled;
+    impl Substate<Top> for Disabled {}
+
+    // no Debug impl
+    struct Enabled;
+    impl Substate<Top> for Enabled {}
+
+    #[derive(Debug)]
+    struct LedOn {
+        level: u8,
+    }
+
+    impl Substate<Enabled> for LedOn {
+        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            Self { level: 3 }
+        }
+
+        fn update(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            self.level = self.level.saturating_sub(1);
+        }
+    }
+}
+
+#[test]
+fn display() {
+    let mut machine = Builder::new(Top { period: 1 }).build();
+    assert_eq!(machine.to_string(), "Blinky[Top/Enabled/LedOn]");
+
+    machine.transition(State::Disabled);
+    assert_eq!(machine.to_string(), "Blinky[Top/Disabled]");
+}
+
+#[test]
+fn debug() {
+    let mut machine = Builder::new(Top { period: 1 }).build();
+    assert_eq!(
+        format!("{machine:?}"),
+        r#"Machine { name: "Blinky", active: [Top { period: 1 }, Enabled, LedOn { level: 3 }] }"#
+    );
+
+    machine.update();
+    assert_eq!(
+        format!("{machine:?}"),
+        r#"Machine { name: "Blinky", active: [Top { period: 1 }, Enabled, LedOn { level: 2 }] }"#
+    );
+}
+
+#[test]
+fn render_active_chart() {
+    let mut machine = Builder::new(Top { period: 1 }).build();
+    assert_eq!(
+        machine.render_active_chart(),
+        "Top *\n├─ Disabled\n└─ Enabled *\n   └─ LedOn *"
+    );
+
+    machine.transition(State::Disabled);
+    assert_eq!(
+        machine.render_active_chart(),
+        "Top *\n├─ Disabled *\n└─ Enabled\n   └─ LedOn"
+    );
+}
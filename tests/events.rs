@@ -9,6 +9,9 @@ fn state_chart() {
         "Top
 ├─ Foo
 ├─ Bar
+├─ Redirect
+├─ Nest
+│  └─ Nested
 ├─ Dropper
 └─ FooPasser
    └─ BarPasser"
@@ -28,6 +31,9 @@ mod tester {
         A,
         B,
         C,
+        D,
+        E,
+        F,
     }
 
     impl StateMachineEvent for Event {}
@@ -40,6 +46,9 @@ mod tester {
                 Event::A => State::Foo.into(),
                 Event::B => State::Bar.into(),
                 Event::C => Next::None,
+                Event::D => State::Redirect.into(),
+                Event::E => State::Nest.into(),
+                Event::F => State::Top.into(),
             }
         }
     }
@@ -50,15 +59,37 @@ mod tester {
     struct Bar;
     impl Substate<Top> for Bar {}
 
+    struct Redirect;
+
+    impl Substate<Top> for Redirect {
+        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            Entry::Target(State::Bar)
+        }
+    }
+
+    struct Nest;
+
+    impl Substate<Top> for Nest {
+        fn init(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            State::Nested
+        }
+    }
+
+    struct Nested;
+    impl Substate<Nest> for Nested {}
+
     struct Dropper;
 
     impl Substate<Top> for Dropper {
         fn handle_event(
             &mut self,
             _ctx: &mut Self::Context<'_>,
-            _event: &Self::Event,
+            event: &Self::Event,
         ) -> impl Into<Response<Self::State>> {
-            Response::Drop
+            match event {
+                Event::F => Response::Handled,
+                _ => Response::Drop,
+            }
         }
     }
 
@@ -161,3 +192,189 @@ fn bar_pass() {
     machine.handle_event(&Event::C);
     assert!(matches!(machine.state(), State::Foo));
 }
+
+#[state_machine]
+mod derived {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+
+    use machine::State;
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Unit,
+        Tuple(u8),
+        Struct { _value: u8 },
+    }
+
+    #[derive(Default)]
+    pub struct Top {
+        pub unhandled: Vec<String>,
+    }
+
+    impl TopState for Top {
+        fn handle_event(&mut self, event: &Self::Event) -> impl Into<Next<Self::State>> {
+            match event {
+                Event::Unit => State::Foo.into(),
+                _ => Next::None,
+            }
+        }
+
+        fn on_unhandled(&mut self, event: &Self::Event) {
+            self.unhandled.push(event.name().to_owned());
+        }
+    }
+
+    struct Foo;
+    impl Substate<Top> for Foo {}
+}
+
+#[derive(StateMachineEvent)]
+struct Named;
+
+#[test]
+fn names() {
+    assert_eq!(derived::Event::Unit.name(), "Unit");
+    assert_eq!(derived::Event::Tuple(0).name(), "Tuple");
+    assert_eq!(derived::Event::Struct { _value: 0 }.name(), "Struct");
+    assert_eq!(Named.name(), "Named");
+    assert!(Event::A.name().ends_with("Event"));
+}
+
+#[test]
+fn derived_event() {
+    let mut machine = derived::machine::Builder::new(derived::Top::default()).build();
+    machine.handle_event(&derived::Event::Unit);
+    assert!(matches!(machine.state(), derived::machine::State::Foo));
+}
+
+#[test]
+fn outcome() {
+    let mut machine = Builder::new(Top).build();
+    assert_eq!(
+        machine.handle_event(&Event::A),
+        EventOutcome::Transition {
+            state: State::Top,
+            target: State::Foo,
+        }
+    );
+    assert_eq!(machine.handle_event(&Event::C), EventOutcome::Unhandled);
+
+    machine.transition(State::Dropper);
+    let outcome = machine.handle_event(&Event::A);
+    assert_eq!(
+        outcome,
+        EventOutcome::Dropped {
+            state: State::Dropper
+        }
+    );
+    assert!(outcome.is_handled());
+    assert_eq!(outcome.state(), Some(State::Dropper));
+    assert_eq!(outcome.target(), None);
+
+    let outcome = machine.handle_event(&Event::F);
+    assert_eq!(
+        outcome,
+        EventOutcome::Handled {
+            state: State::Dropper
+        }
+    );
+    assert!(outcome.is_handled());
+    assert_eq!(outcome.state(), Some(State::Dropper));
+    assert_eq!(outcome.target(), None);
+    assert_eq!(machine.state(), State::Dropper);
+
+    machine.transition(State::BarPasser);
+    let outcome = machine.handle_event(&Event::C);
+    assert_eq!(
+        outcome,
+        EventOutcome::Transition {
+            state: State::FooPasser,
+            target: State::Foo,
+        }
+    );
+    assert_eq!(outcome.state(), Some(State::FooPasser));
+    assert_eq!(outcome.target(), Some(State::Foo));
+}
+
+#[test]
+fn outcome_redirected() {
+    let mut machine = Builder::new(Top).build();
+
+    // Redirect short circuits to Bar when entered
+    assert_eq!(
+        machine.handle_event(&Event::D),
+        EventOutcome::Transition {
+            state: State::Top,
+            target: State::Bar,
+        }
+    );
+    assert_eq!(machine.state(), State::Bar);
+
+    // Nest initializes into Nested
+    assert_eq!(
+        machine.handle_event(&Event::E),
+        EventOutcome::Transition {
+            state: State::Top,
+            target: State::Nested,
+        }
+    );
+    assert_eq!(machine.state(), State::Nested);
+}
+
+#[test]
+fn outcome_already_active() {
+    let mut machine = Builder::new(Top).build();
+    machine.transition(State::Foo);
+
+    // the top state is always active, so the machine stays in Foo
+    assert_eq!(
+        machine.handle_event(&Event::F),
+        EventOutcome::Transition {
+            state: State::Top,
+            target: State::Foo,
+        }
+    );
+    assert_eq!(machine.state(), State::Foo);
+}
+
+#[test]
+fn unhandled() {
+    let mut machine = derived::machine::Builder::new(derived::Top::default()).build();
+    let outcome = machine.handle_event(&derived::Event::Tuple(1));
+    assert_eq!(outcome, EventOutcome::Unhandled);
+    assert!(!outcome.is_handled());
+    assert_eq!(outcome.state(), None);
+
+    machine.handle_event(&derived::Event::Unit);
+    machine.handle_event(&derived::Event::Struct { _value: 2 });
+    assert_eq!(machine.top_ref().unhandled, ["Tuple", "Struct"]);
+}
+
+#[derive(Default)]
+struct Outcomes(Vec<EventOutcome<State>>);
+
+impl Observer<State> for Outcomes {
+    fn on_event_outcome(&mut self, outcome: EventOutcome<State>) {
+        self.0.push(outcome);
+    }
+}
+
+#[test]
+fn observer() {
+    let mut machine = Builder::new(Top).observer(Outcomes::default()).build();
+    machine.handle_event(&Event::A);
+    machine.handle_event(&Event::C);
+    assert_eq!(
+        machine.observer().0,
+        [
+            EventOutcome::Transition {
+                state: State::Top,
+                target: State::Foo,
+            },
+            EventOutcome::Unhandled,
+        ]
+    );
+}
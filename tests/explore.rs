@@ -0,0 +1,277 @@
+use moku::{testing::*, *};
+use test_log::test;
+use tester::{
+    machine::{Builder, State},
+    Event, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Event {
+        Arm,
+        Fire,
+        Noise,
+    }
+    impl StateMachineEvent for Event {}
+
+    pub struct Top {
+        pub updates: u8,
+    }
+    impl TopState for Top {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Idle
+        }
+
+        fn update(&mut self) -> impl Into<Next<Self::State>> {
+            self.updates = self.updates.saturating_add(1);
+        }
+    }
+
+    struct Idle;
+    impl Substate<Top> for Idle {
+        fn handle_event(
+            &mut self,
+            _ctx: &mut Self::Context<'_>,
+            event: &Self::Event,
+        ) -> impl Into<Response<Self::State>> {
+            match event {
+                Event::Arm => Response::Next(State::Armed.into()),
+                _ => Response::Drop,
+            }
+        }
+    }
+
+    struct Armed;
+    impl Substate<Top> for Armed {
+        fn handle_event(
+            &mut self,
+            _ctx: &mut Self::Context<'_>,
+            event: &Self::Event,
+        ) -> impl Into<Response<Self::State>> {
+            match event {
+                Event::Fire => Response::Next(State::Fired.into()),
+                _ => Response::Drop,
+            }
+        }
+    }
+
+    struct Fired;
+    impl Substate<Armed> for Fired {}
+}
+
+fn events(rng: &mut Rng) -> Step<State, Event> {
+    match rng.below(5) {
+        0 => Step::HandleEvent(Event::Arm),
+        1 => Step::HandleEvent(Event::Fire),
+        2 => Step::HandleEvent(Event::Noise),
+        3 => Step::Update,
+        _ => Step::TopDownUpdate,
+    }
+}
+
+#[test]
+fn consistent() {
+    explore(|| Builder::new(Top { updates: 0 }).build())
+        .run()
+        .unwrap();
+    explore(|| Builder::new(Top { updates: 0 }).build())
+        .generator(events)
+        .run()
+        .unwrap();
+}
+
+#[test]
+fn invariant() {
+    let failure = explore(|| Builder::new(Top { updates: 0 }).build())
+        .generator(events)
+        .invariant("updates below 100", |machine| {
+            machine.top_ref().updates < 100
+        })
+        .invariant("never fired", |machine| {
+            !machine.state_matches(State::Fired)
+        })
+        .run()
+        .unwrap_err();
+
+    assert_eq!(failure.violation, Violation::Invariant("never fired"));
+    assert_eq!(
+        failure.steps,
+        [
+            Step::HandleEvent(Event::Arm),
+            Step::HandleEvent(Event::Fire)
+        ]
+    );
+    assert!(failure.seed.is_some());
+}
+
+#[test]
+fn invariant_after_build() {
+    let failure = explore(|| Builder::new(Top { updates: 0 }).build())
+        .invariant("never idle", |machine| machine.state() != State::Idle)
+        .run()
+        .unwrap_err();
+
+    assert_eq!(failure.violation, Violation::Invariant("never idle"));
+    assert!(failure.steps.is_empty());
+    assert_eq!(failure.seed, Some(0));
+}
+
+#[test]
+fn transitions() {
+    let failure = explore(|| Builder::new(Top { updates: 0 }).build())
+        .invariant("never fired", |machine| machine.state() != State::Fired)
+        .run()
+        .unwrap_err();
+
+    assert_eq!(failure.steps.len(), 1);
+    assert!(matches!(
+        failure.steps[0],
+        Step::Transition(State::Fired) | Step::ExactTransition(State::Fired)
+    ));
+}
+
+#[test]
+fn deterministic() {
+    let run = || {
+        explore(|| Builder::new(Top { updates: 0 }).build())
+            .generator(events)
+            .invariant("few updates", |machine| machine.top_ref().updates < 5)
+            .seed(42)
+            .run()
+            .unwrap_err()
+    };
+
+    let failure = run();
+    assert_eq!(failure, run());
+    assert_eq!(failure.seed, Some(42));
+    assert_eq!(failure.steps, vec![Step::Update; 5]);
+}
+
+#[test]
+fn limits() {
+    // too few steps to arm and fire
+    explore(|| Builder::new(Top { updates: 0 }).build())
+        .generator(events)
+        .invariant("never fired", |machine| {
+            !machine.state_matches(State::Fired)
+        })
+        .max_steps(1)
+        .run()
+        .unwrap();
+
+    explore(|| Builder::new(Top { updates: 0 }).build())
+        .generator(events)
+        .invariant("never fired", |machine| {
+            !machine.state_matches(State::Fired)
+        })
+        .runs(0)
+        .run()
+        .unwrap();
+}
+
+#[test]
+fn replay() {
+    let mut explorer = explore(|| Builder::new(Top { updates: 0 }).build())
+        .invariant("never fired", |machine| machine.state() != State::Fired);
+
+    explorer
+        .replay(&[Step::HandleEvent(Event::Fire), Step::Update])
+        .unwrap();
+
+    let failure = explorer
+        .replay(&[
+            Step::HandleEvent(Event::Arm),
+            Step::HandleEvent(Event::Fire),
+            Step::Update,
+        ])
+        .unwrap_err();
+
+    assert_eq!(failure.seed, None);
+    assert_eq!(
+        failure.steps,
+        [
+            Step::HandleEvent(Event::Arm),
+            Step::HandleEvent(Event::Fire)
+        ]
+    );
+    assert_eq!(
+        failure.to_string(),
+        "invariant `never fired` violated after 2 steps
+  1. handle_event(&Arm)
+  2. handle_event(&Fire)"
+    );
+}
+
+#[test]
+fn shrink_same_violation() {
+    let failure = explore(|| Builder::new(Top { updates: 0 }).build())
+        .invariant("never fired", |machine| {
+            !machine.state_matches(State::Fired)
+        })
+        .invariant("armed after update", |machine| {
+            !machine.state_matches(State::Armed) || machine.top_ref().updates > 0
+        })
+        .replay(&[
+            Step::Update,
+            Step::HandleEvent(Event::Arm),
+            Step::HandleEvent(Event::Fire),
+        ])
+        .unwrap_err();
+
+    // removing the update would violate the other invariant instead
+    assert_eq!(failure.violation, Violation::Invariant("never fired"));
+    assert_eq!(
+        failure.steps,
+        [
+            Step::Update,
+            Step::HandleEvent(Event::Arm),
+            Step::HandleEvent(Event::Fire)
+        ]
+    );
+}
+
+#[test]
+fn same_kind() {
+    let state = Violation::State {
+        state: State::Idle,
+        active: vec![State::Top, State::Armed],
+    };
+    let other_state = Violation::State {
+        state: State::Armed,
+        active: vec![State::Top, State::Armed, State::Fired],
+    };
+    let state_matches = Violation::StateMatches {
+        state: State::Idle,
+        active: vec![State::Top, State::Armed],
+    };
+
+    // the states that a violation was found in are ignored
+    assert!(state.is_same_kind(&other_state));
+    assert!(!state.is_same_kind(&state_matches));
+    assert!(Violation::<State>::Invariant("a").is_same_kind(&Violation::Invariant("a")));
+    assert!(!Violation::<State>::Invariant("a").is_same_kind(&Violation::Invariant("b")));
+    assert!(!Violation::Invariant("a").is_same_kind(&state));
+}
+
+#[test]
+fn rng() {
+    let mut rng = Rng::new(7);
+    let mut other = Rng::new(7);
+
+    for _ in 0..100 {
+        assert_eq!(rng.next_u64(), other.next_u64());
+    }
+
+    for _ in 0..100 {
+        assert!(rng.below(3) < 3);
+        assert!([1, 2, 3].contains(rng.choose(&[1, 2, 3])));
+        assert!(State::ALL.contains(&rng.state::<State>()));
+    }
+}
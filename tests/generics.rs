@@ -0,0 +1,270 @@
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, Machine, State},
+    Counter, Event, Hal, Leaf, MockHal, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    pub trait Hal {
+        fn write(&mut self, value: u8);
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq)]
+    pub struct MockHal {
+        pub written: Vec<u8>,
+    }
+
+    impl Hal for MockHal {
+        fn write(&mut self, value: u8) {
+            self.written.push(value);
+        }
+    }
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Write(u8),
+        Bump,
+    }
+
+    #[derive(Clone, PartialEq)]
+    pub struct Top<H: Hal, const N: usize> {
+        pub hal: H,
+        pub buf: [u8; N],
+    }
+
+    impl<H, const N: usize> TopState for Top<H, N>
+    where
+        H: Hal,
+    {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Leaf
+        }
+
+        #[on(Event::Write(value))]
+        fn write(&mut self, value: &u8) {
+            self.hal.write(*value);
+        }
+    }
+
+    #[derive(Clone, PartialEq)]
+    struct Branch;
+
+    impl<H: Hal, const N: usize> Substate<Top<H, N>> for Branch {
+        fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            ctx.top.hal.write(N as u8);
+            Self
+        }
+    }
+
+    #[derive(Clone, PartialEq)]
+    pub struct Leaf;
+
+    impl Substate<Branch> for Leaf {
+        fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            ctx.top.buf[0] = 1;
+            Self
+        }
+
+        #[on(Event::Bump)]
+        fn bump(ctx: &mut Self::Context<'_>) -> Self::State {
+            ctx.top.buf[0] += 1;
+            ctx.top.hal.write(ctx.top.buf[0]);
+            State::Counter
+        }
+    }
+
+    #[derive(Clone, PartialEq)]
+    pub struct Counter<H: Hal, const N: usize> {
+        pub count: usize,
+        phantom: core::marker::PhantomData<H>,
+    }
+
+    impl<H: Hal, const N: usize> Substate<Branch> for Counter<H, N> {
+        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            Self {
+                count: N,
+                phantom: core::marker::PhantomData,
+            }
+        }
+
+        fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            self.count += 1;
+            ctx.top.hal.write(self.count as u8);
+        }
+    }
+}
+
+#[test]
+fn generic_top_state() {
+    let mut machine = Builder::new(Top {
+        hal: MockHal::default(),
+        buf: [0; 4],
+    })
+    .build();
+    assert_eq!(machine.state(), State::Leaf);
+    assert_eq!(machine.top_ref().hal.written, [4]);
+    assert_eq!(machine.top_ref().buf, [1, 0, 0, 0]);
+
+    machine.handle_event(&Event::Write(9));
+    assert_eq!(machine.top_ref().hal.written, [4, 9]);
+
+    assert_eq!(
+        machine.handle_event(&Event::Bump),
+        EventOutcome::Transition {
+            state: State::Leaf,
+            target: State::Counter,
+        }
+    );
+    assert_eq!(machine.state(), State::Counter);
+    assert_eq!(machine.top_ref().hal.written, [4, 9, 2]);
+}
+
+#[test]
+fn generic_substate() {
+    let mut machine = Builder::new(Top {
+        hal: MockHal::default(),
+        buf: [0; 4],
+    })
+    .build();
+    machine.transition(State::Counter);
+
+    let counter: Option<&Counter<MockHal, 4>> = machine.state_ref();
+    assert_eq!(counter.unwrap().count, 4);
+
+    machine.update();
+    let counter: Option<&Counter<MockHal, 4>> = machine.state_ref();
+    assert_eq!(counter.unwrap().count, 5);
+    assert_eq!(machine.top_ref().hal.written, [4, 5]);
+
+    let leaf: Option<&Leaf> = machine.state_ref();
+    assert!(leaf.is_none());
+}
+
+#[test]
+fn generic_machine_traits() {
+    let mut machine = Builder::new(Top {
+        hal: MockHal::default(),
+        buf: [0; 4],
+    })
+    .build();
+    let mut clone = machine.clone();
+    assert!(machine == clone);
+
+    machine.transition(State::Counter);
+    assert!(machine != clone);
+
+    clone.transition(State::Counter);
+    assert!(machine == clone);
+}
+
+#[test]
+fn generic_observer() {
+    let mut machine: Machine<_, 2, testing::Recorder<State>> = Builder::new(Top {
+        hal: MockHal::default(),
+        buf: [0; 2],
+    })
+    .observer(testing::Recorder::new())
+    .build();
+
+    machine.transition(State::Counter);
+    assert!(machine
+        .observer()
+        .iter()
+        .any(|action| action == Action::Enter(State::Counter)));
+}
+
+#[test]
+fn generic_bound() {
+    fn write_all<H: Hal, const N: usize>(machine: &mut Machine<H, N>) {
+        machine.top_mut().hal.write(0);
+    }
+
+    let mut machine = Builder::new(Top {
+        hal: MockHal::default(),
+        buf: [0; 4],
+    })
+    .build();
+    write_all(&mut machine);
+    assert_eq!(machine.top_ref().hal.written, [4, 0]);
+}
+
+#[state_machine]
+mod multi {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+
+    #[derive(StateMachineEvent)]
+    pub struct Add(pub u32);
+
+    #[derive(StateMachineEvent)]
+    pub struct Clear;
+
+    pub struct Top<T: Default + Into<u32>> {
+        pub total: u32,
+        pub step: T,
+    }
+
+    impl<T: Default + Into<u32>> TopState for Top<T> {
+        #[on(machine::Event::Add(Add(amount)))]
+        fn add(&mut self, amount: &u32) {
+            self.total += amount;
+        }
+
+        #[on(machine::Event::Clear(_))]
+        fn clear(&mut self) {
+            self.total = core::mem::take(&mut self.step).into();
+        }
+    }
+}
+
+#[test]
+fn generic_multiple_events() {
+    let mut machine = multi::machine::Builder::new(multi::Top {
+        total: 0,
+        step: 7u8,
+    })
+    .build();
+
+    machine.handle_add(multi::Add(3));
+    assert_eq!(machine.top_ref().total, 3);
+
+    machine.handle_clear(multi::Clear);
+    assert_eq!(machine.top_ref().total, 7);
+}
+
+#[state_machine]
+mod named {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+
+    // named like the observer parameters of the generated `Machine` and `Builder`
+    pub struct Top<O, P> {
+        pub o: O,
+        pub p: P,
+    }
+
+    impl<O, P> TopState for Top<O, P> {}
+}
+
+#[test]
+fn generic_named_like_observer() {
+    let machine = named::machine::Builder::new(named::Top { o: 1u8, p: "p" })
+        .observer(testing::Recorder::new())
+        .build();
+
+    assert_eq!(machine.top_ref().o, 1);
+    assert_eq!(machine.top_ref().p, "p");
+    assert!(!machine.observer().is_empty());
+}
@@ -0,0 +1,213 @@
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, State},
+    Event, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    pub enum Event {
+        Go(State),
+        Add { amount: u32 },
+        Reset,
+        Ping,
+        Pong,
+        Ignored,
+    }
+    impl StateMachineEvent for Event {}
+
+    #[derive(Default)]
+    pub struct Top {
+        pub total: u32,
+        pub pings: u32,
+    }
+
+    impl TopState for Top {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Deferrer
+        }
+
+        #[on(Event::Go(target))]
+        fn go(target: &State) -> Next<Self::State> {
+            (*target).into()
+        }
+
+        #[on(Event::Ping | Event::Pong)]
+        fn ping(&mut self) {
+            self.pings += 1;
+        }
+    }
+
+    struct Deferrer;
+
+    impl Substate<Top> for Deferrer {
+        #[on(Event::Add { amount })]
+        fn add(&mut self, ctx: &mut Self::Context<'_>, amount: &u32) -> Response<Self::State> {
+            ctx.top.total += amount;
+            Response::Drop
+        }
+
+        #[on(Event::Reset)]
+        #[on(Event::Ignored)]
+        fn reset(&mut self, _ctx: &mut Self::Context<'_>, _event: &Self::Event) -> Self::State {
+            State::Dropper
+        }
+    }
+
+    struct Dropper;
+
+    #[unmatched(drop)]
+    impl Substate<Top> for Dropper {
+        #[on(Event::Reset)]
+        fn reset(&mut self) -> Option<State> {
+            Some(State::Deferrer)
+        }
+    }
+
+    struct Nested;
+
+    #[moku::unmatched(defer)]
+    impl Substate<Dropper> for Nested {
+        #[moku::on(Event::Add { .. })]
+        fn add(&mut self, event: &Self::Event) -> Response<Self::State> {
+            match event {
+                Event::Add { amount: 0 } => Response::Next(Next::None),
+                _ => Response::Next(State::Deferrer.into()),
+            }
+        }
+    }
+
+    struct Catcher;
+
+    impl Substate<Top> for Catcher {
+        #[on(Event::Ping)]
+        fn ping(&mut self) {}
+
+        #[on(_)]
+        fn catch_all(&mut self) -> Response<Self::State> {
+            Response::Drop
+        }
+    }
+
+    #[unmatched(drop)]
+    impl Substate<Top> for Wall {}
+
+    struct Wall;
+}
+
+#[test]
+fn bindings() {
+    let mut machine = Builder::new(Top::default()).build();
+    let outcome = machine.handle_event(&Event::Add { amount: 2 });
+    assert_eq!(
+        outcome,
+        EventOutcome::Dropped {
+            state: State::Deferrer
+        }
+    );
+    machine.handle_event(&Event::Add { amount: 3 });
+    assert_eq!(machine.top_ref().total, 5);
+
+    machine.handle_event(&Event::Go(State::Wall));
+    assert!(matches!(machine.state(), State::Wall));
+}
+
+#[test]
+fn multiple_patterns() {
+    let mut machine = Builder::new(Top::default()).build();
+    machine.handle_event(&Event::Ping);
+    machine.handle_event(&Event::Pong);
+    assert_eq!(machine.top_ref().pings, 2);
+
+    assert_eq!(
+        machine.handle_event(&Event::Ignored),
+        EventOutcome::Transition {
+            state: State::Deferrer,
+            target: State::Dropper,
+        }
+    );
+
+    machine.handle_event(&Event::Reset);
+    assert!(matches!(machine.state(), State::Deferrer));
+}
+
+#[test]
+fn unmatched() {
+    let mut machine = Builder::new(Top::default()).build();
+    assert_eq!(
+        machine.handle_event(&Event::Ignored),
+        EventOutcome::Transition {
+            state: State::Deferrer,
+            target: State::Dropper,
+        }
+    );
+
+    // unmatched events are dropped
+    assert_eq!(
+        machine.handle_event(&Event::Ping),
+        EventOutcome::Dropped {
+            state: State::Dropper
+        }
+    );
+    assert_eq!(machine.top_ref().pings, 0);
+
+    // unmatched events are deferred to the dropper
+    machine.transition(State::Nested);
+    assert_eq!(
+        machine.handle_event(&Event::Add { amount: 0 }),
+        EventOutcome::Dropped {
+            state: State::Dropper
+        }
+    );
+    assert_eq!(
+        machine.handle_event(&Event::Reset),
+        EventOutcome::Transition {
+            state: State::Dropper,
+            target: State::Deferrer,
+        }
+    );
+
+    machine.transition(State::Nested);
+    machine.handle_event(&Event::Add { amount: 1 });
+    assert!(matches!(machine.state(), State::Deferrer));
+
+    // events deferred by the top state are unhandled
+    assert_eq!(machine.handle_event(&Event::Pong), EventOutcome::Unhandled);
+    assert_eq!(machine.top_ref().pings, 1);
+}
+
+#[test]
+fn catch_all() {
+    let mut machine = Builder::new(Top::default()).build();
+    machine.transition(State::Catcher);
+
+    // `ping` returns `()`, handling the event without a transition
+    assert_eq!(
+        machine.handle_event(&Event::Ping),
+        EventOutcome::Handled {
+            state: State::Catcher
+        }
+    );
+    assert_eq!(machine.top_ref().pings, 0);
+
+    assert_eq!(
+        machine.handle_event(&Event::Pong),
+        EventOutcome::Dropped {
+            state: State::Catcher
+        }
+    );
+    assert_eq!(machine.top_ref().pings, 0);
+
+    machine.transition(State::Wall);
+    assert_eq!(
+        machine.handle_event(&Event::Go(State::Catcher)),
+        EventOutcome::Dropped { state: State::Wall }
+    );
+}
@@ -0,0 +1,83 @@
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, State},
+    Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+
+    pub struct Top<T: Copy + Default> {
+        pub log: Vec<T>,
+    }
+
+    impl<T: Copy + Default> TopState for Top<T> {}
+
+    /// Logs from any state below `Top`.
+    fn log<T: Copy + Default>(ctx: &mut impl HasState<Top<T>>, value: T) {
+        ctx.get_mut::<Top<T>>().log.push(value);
+    }
+
+    pub struct Parent {
+        pub entries: u8,
+    }
+
+    impl<T: Copy + Default> Substate<Top<T>> for Parent {
+        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            Self { entries: 0 }
+        }
+    }
+
+    /// Counts entries into any state below `Parent`.
+    fn count(ctx: &mut impl HasState<Parent>) {
+        ctx.get_mut::<Parent>().entries += 1;
+    }
+
+    struct Child;
+
+    impl Substate<Parent> for Child {
+        fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            count(ctx);
+            log(ctx, T::default());
+            Self
+        }
+
+        fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            let entries = ctx.get::<Parent>().entries;
+            assert_eq!(entries, ctx.parent.entries);
+        }
+    }
+
+    mod grandchildren {
+        use super::*;
+
+        pub struct Grandchild;
+
+        impl Substate<Child> for Grandchild {
+            fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+                count(ctx);
+                log(ctx, T::default());
+                Self
+            }
+        }
+    }
+}
+
+#[test]
+fn generic_helpers() {
+    let mut machine = Builder::new(Top {
+        log: Vec::<u8>::new(),
+    })
+    .build();
+    machine.transition(State::Grandchild);
+    machine.update();
+
+    let parent: &tester::Parent = machine.state_ref().unwrap();
+    assert_eq!(parent.entries, 2);
+    assert_eq!(machine.top_ref().log, [0, 0]);
+}
@@ -0,0 +1,213 @@
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, State},
+    Event, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Toggle,
+        Poke,
+        Ignore,
+        Bounce,
+    }
+
+    pub struct Top;
+
+    impl TopState for Top {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Off
+        }
+    }
+
+    struct Off;
+
+    impl Substate<Top> for Off {
+        #[on(Event::Toggle)]
+        fn toggle() -> State {
+            State::On
+        }
+
+        #[on(Event::Bounce)]
+        fn bounce() -> State {
+            State::Bouncing
+        }
+    }
+
+    struct On;
+
+    impl Substate<Top> for On {
+        fn exit(self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            State::Off
+        }
+
+        #[on(Event::Toggle)]
+        fn toggle() -> State {
+            State::Bouncing
+        }
+
+        #[on(Event::Poke)]
+        fn poke(&mut self) -> Response<State> {
+            Response::Drop
+        }
+    }
+
+    struct Bouncing;
+
+    impl Substate<Top> for Bouncing {
+        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            Entry::ExactTarget(State::Off)
+        }
+    }
+}
+
+#[test]
+fn actions() {
+    let mut machine = Builder::new(Top)
+        .observer(History::<State, 32>::default())
+        .build();
+    assert!(machine.history().is_empty());
+
+    machine.handle_event(&Event::Toggle);
+    machine.handle_event(&Event::Poke);
+    machine.handle_event(&Event::Ignore);
+    machine.update();
+    machine.top_down_update();
+    machine.handle_event(&Event::Toggle);
+    machine.exact_transition(State::Off);
+
+    assert_eq!(
+        machine.history().iter().collect::<Vec<_>>(),
+        [
+            MachineAction::Transition {
+                source: State::Off,
+                target: State::On,
+                exact: false,
+            },
+            MachineAction::Event(EventOutcome::Transition {
+                state: State::Off,
+                target: State::On,
+            }),
+            MachineAction::Event(EventOutcome::Dropped { state: State::On }),
+            MachineAction::Event(EventOutcome::Unhandled),
+            MachineAction::Update(State::On),
+            MachineAction::TopDownUpdate(State::On),
+            MachineAction::Transition {
+                source: State::On,
+                target: State::Bouncing,
+                exact: false,
+            },
+            MachineAction::ShortCircuit {
+                state: State::On,
+                target: State::Off,
+                exact: false,
+            },
+            // events are recorded once handled, along with the state the machine ended in
+            MachineAction::Event(EventOutcome::Transition {
+                state: State::On,
+                target: State::Off,
+            }),
+            MachineAction::Transition {
+                source: State::Off,
+                target: State::Off,
+                exact: true,
+            },
+        ]
+    );
+    assert_eq!(machine.history().len(), 10);
+    assert_eq!(machine.state(), State::Off);
+}
+
+#[test]
+fn short_circuit_on_entry() {
+    let mut machine = Builder::new(Top)
+        .observer(History::<State, 32>::default())
+        .build();
+
+    machine.handle_event(&Event::Bounce);
+    assert_eq!(
+        machine.history().iter().skip(1).collect::<Vec<_>>(),
+        [
+            MachineAction::ShortCircuit {
+                state: State::Bouncing,
+                target: State::Off,
+                exact: true,
+            },
+            MachineAction::Event(EventOutcome::Transition {
+                state: State::Off,
+                target: State::Off,
+            }),
+        ]
+    );
+    assert_eq!(machine.state(), State::Off);
+}
+
+#[test]
+fn ring_buffer() {
+    let mut machine = Builder::new(Top)
+        .observer(History::<State, 3>::default())
+        .build();
+    for _ in 0..4 {
+        machine.update();
+    }
+    machine.handle_event(&Event::Ignore);
+
+    assert_eq!(machine.history().len(), 3);
+    assert_eq!(machine.history().total(), 5);
+    assert_eq!(
+        machine.history().iter().collect::<Vec<_>>(),
+        [
+            MachineAction::Update(State::Off),
+            MachineAction::Update(State::Off),
+            MachineAction::Event(EventOutcome::Unhandled),
+        ]
+    );
+
+    // a copy can be kept, e.g. for a panic handler
+    let history = *machine.history();
+    machine.observer_mut().clear();
+    assert!(machine.history().is_empty());
+    assert_eq!(machine.history().last(), None);
+    assert_eq!(history.len(), 3);
+
+    let mut machine = Builder::new(Top)
+        .observer(History::<State, 0>::default())
+        .build();
+    machine.update();
+    assert!(machine.history().is_empty());
+    assert_eq!(machine.history().last(), None);
+    assert_eq!(machine.history().total(), 1);
+}
+
+#[test]
+fn display() {
+    let mut machine = Builder::new(Top)
+        .observer(History::<State, 32>::default())
+        .build();
+    machine.handle_event(&Event::Toggle);
+    machine.handle_event(&Event::Poke);
+    machine.update();
+    machine.handle_event(&Event::Toggle);
+    machine.handle_event(&Event::Ignore);
+
+    assert_eq!(
+        machine.history().to_string(),
+        "Transition from Off to On\n\
+         Off handled event with transition to On\n\
+         On dropped event\n\
+         Update in On\n\
+         Transition from On to Bouncing\n\
+         On triggered short circuit transition to Off\n\
+         On handled event with transition to Off\n\
+         Event unhandled\n"
+    );
+}
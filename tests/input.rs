@@ -0,0 +1,184 @@
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, State},
+    Event, Sample, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Inject(Sample),
+    }
+
+    pub struct Sample {
+        pub dt: u32,
+        pub level: u8,
+    }
+
+    pub struct Top {
+        pub elapsed: u32,
+        pub top_down: Vec<u8>,
+    }
+
+    impl TopState for Top {
+        type Input = Sample;
+
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Low
+        }
+
+        fn update(&mut self, input: &Sample) -> impl Into<Next<Self::State>> {
+            self.elapsed += input.dt;
+        }
+
+        fn top_down_update(&mut self, input: &Sample) -> impl Into<Next<Self::State>> {
+            self.top_down.push(input.level);
+        }
+    }
+
+    struct Low;
+
+    impl Substate<Top> for Low {
+        fn update(
+            &mut self,
+            _ctx: &mut Self::Context<'_>,
+            input: &Sample,
+        ) -> impl Into<Next<Self::State>> {
+            if input.level > 5 {
+                State::High.into()
+            } else {
+                Next::None
+            }
+        }
+
+        #[on(Event::Inject(sample))]
+        fn inject(ctx: &mut Self::Context<'_>, sample: &Self::Input) {
+            ctx.top.elapsed += sample.dt;
+        }
+    }
+
+    struct High;
+
+    impl Substate<Top> for High {
+        fn top_down_update(
+            &mut self,
+            ctx: &mut Self::Context<'_>,
+            input: &Self::Input,
+        ) -> impl Into<Next<Self::State>> {
+            ctx.top.top_down.push(input.level + 1);
+            if input.level <= 5 {
+                State::Low.into()
+            } else {
+                Next::None
+            }
+        }
+    }
+
+    // hooks may omit the input parameter
+    struct Idle;
+
+    impl Substate<High> for Idle {
+        fn update(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {}
+    }
+}
+
+#[test]
+fn update_input() {
+    let mut machine = Builder::new(Top {
+        elapsed: 0,
+        top_down: Vec::new(),
+    })
+    .build();
+
+    machine.update_input(&Sample { dt: 2, level: 1 });
+    assert_eq!(machine.state(), State::Low);
+
+    machine.update_input(&Sample { dt: 3, level: 8 });
+    assert_eq!(machine.state(), State::High);
+    assert_eq!(machine.top_ref().elapsed, 5);
+
+    machine.transition(State::Idle);
+    machine.update_input(&Sample { dt: 1, level: 8 });
+    assert_eq!(machine.state(), State::Idle);
+    assert_eq!(machine.top_ref().elapsed, 6);
+}
+
+#[test]
+fn top_down_update_input() {
+    let mut machine = Builder::new(Top {
+        elapsed: 0,
+        top_down: Vec::new(),
+    })
+    .build();
+    machine.transition(State::High);
+
+    machine.top_down_update_input(&Sample { dt: 0, level: 7 });
+    assert_eq!(machine.state(), State::High);
+
+    machine.top_down_update_input(&Sample { dt: 0, level: 2 });
+    assert_eq!(machine.state(), State::Low);
+    assert_eq!(machine.top_ref().top_down, [7, 8, 2, 3]);
+}
+
+#[state_machine]
+mod resources {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    pub struct Top;
+
+    impl TopState for Top {
+        type Resources = Vec<u32>;
+        type Input = [u32];
+
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Logging
+        }
+    }
+
+    struct Logging;
+
+    impl Substate<Top> for Logging {
+        fn update(
+            &mut self,
+            ctx: &mut Self::Context<'_>,
+            input: &[u32],
+        ) -> impl Into<Next<Self::State>> {
+            ctx.resources.extend_from_slice(input);
+        }
+    }
+}
+
+#[test]
+fn update_with_input() {
+    let mut log = Vec::new();
+    let mut machine = resources::machine::Builder::new(resources::Top).build_with(&mut log);
+
+    machine.update_with_input(&mut log, &[1, 2]);
+    machine.update_with_input(&mut log, &[3]);
+    assert_eq!(log, [1, 2, 3]);
+}
+
+#[test]
+fn handler_input_type() {
+    let mut machine = Builder::new(Top {
+        elapsed: 0,
+        top_down: Vec::new(),
+    })
+    .build();
+
+    let outcome = machine.handle_event(&Event::Inject(Sample { dt: 4, level: 0 }));
+    assert_eq!(outcome, EventOutcome::Handled { state: State::Low });
+    assert_eq!(machine.top_ref().elapsed, 4);
+}
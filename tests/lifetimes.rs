@@ -0,0 +1,216 @@
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, State},
+    Event, Reader, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Next,
+        Log(u8),
+    }
+
+    pub struct Top<'a> {
+        pub log: &'a mut Vec<u8>,
+        pub input: &'a [u8],
+    }
+
+    impl<'a> TopState for Top<'a> {
+        #[on(Event::Log(value))]
+        fn log(&mut self, value: &u8) {
+            self.log.push(*value);
+        }
+    }
+
+    pub struct Reader<'a> {
+        pub data: &'a [u8],
+    }
+
+    impl<'a> Substate<Top<'a>> for Reader<'a> {
+        fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            Self {
+                data: ctx.top.input,
+            }
+        }
+
+        fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            match self.data.split_first() {
+                Some((first, rest)) => {
+                    ctx.top.log.push(*first);
+                    self.data = rest;
+                    Next::None
+                }
+                None => State::Done.into(),
+            }
+        }
+    }
+
+    struct Done;
+
+    impl<'a> Substate<Top<'a>> for Done {
+        fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            ctx.top.log.push(0);
+            Self
+        }
+
+        #[on(Event::Next)]
+        fn next(ctx: &mut Self::Context<'_>) -> State {
+            ctx.top.log.clear();
+            State::Reader
+        }
+    }
+}
+
+#[test]
+fn borrowed_top_state() {
+    let mut log = Vec::new();
+    let input = [1, 2];
+
+    {
+        let mut machine = Builder::new(Top {
+            log: &mut log,
+            input: &input,
+        })
+        .build();
+        machine.transition(State::Reader);
+
+        machine.update();
+        machine.update();
+        assert_eq!(*machine.top_ref().log, [1, 2]);
+
+        machine.update();
+        assert_eq!(machine.state(), State::Done);
+
+        machine.handle_event(&Event::Log(7));
+    }
+
+    assert_eq!(log, [1, 2, 0, 7]);
+}
+
+#[test]
+fn borrowed_substate() {
+    let mut log = Vec::new();
+    let input = vec![4, 5, 6];
+    let mut machine = Builder::new(Top {
+        log: &mut log,
+        input: &input,
+    })
+    .build();
+
+    machine.transition(State::Reader);
+    machine.update();
+    let reader: Option<&Reader> = machine.state_ref();
+    assert_eq!(reader.unwrap().data, [5, 6]);
+}
+
+#[test]
+fn borrowed_handler() {
+    let mut log = Vec::new();
+    let input = [3];
+    let mut machine = Builder::new(Top {
+        log: &mut log,
+        input: &input,
+    })
+    .build();
+
+    machine.transition(State::Done);
+    assert_eq!(
+        machine.handle_event(&Event::Next),
+        EventOutcome::Transition {
+            state: State::Done,
+            target: State::Reader,
+        }
+    );
+    assert!(machine.top_ref().log.is_empty());
+}
+
+#[state_machine]
+mod mixed {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    pub trait Sink {
+        fn put(&mut self, value: u8);
+    }
+
+    impl Sink for Vec<u8> {
+        fn put(&mut self, value: u8) {
+            self.push(value);
+        }
+    }
+
+    pub struct Top<'a, S: Sink + ?Sized> {
+        pub sink: &'a mut S,
+    }
+
+    impl<'a, S: Sink + ?Sized> TopState for Top<'a, S> {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Writer
+        }
+    }
+
+    struct Writer;
+
+    impl<'a, S: Sink + ?Sized> Substate<Top<'a, S>> for Writer {
+        fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            ctx.top.sink.put(1);
+            Self
+        }
+    }
+}
+
+#[test]
+fn borrowed_generic_top_state() {
+    let mut sink = Vec::new();
+    let sink_dyn: &mut dyn mixed::Sink = &mut sink;
+    drop(mixed::machine::Builder::new(mixed::Top { sink: sink_dyn }).build());
+    assert_eq!(sink, [1]);
+}
+
+#[state_machine]
+mod named {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    // named like the lifetime of the generated contexts
+    pub struct Top<'ctx> {
+        pub buf: &'ctx mut [u8],
+    }
+
+    impl<'ctx> TopState for Top<'ctx> {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Filler
+        }
+    }
+
+    struct Filler;
+
+    impl<'ctx> Substate<Top<'ctx>> for Filler {
+        fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            ctx.top.buf.fill(1);
+            Self
+        }
+    }
+}
+
+#[test]
+fn lifetime_named_like_context() {
+    let mut buf = [0; 2];
+    drop(named::machine::Builder::new(named::Top { buf: &mut buf }).build());
+    assert_eq!(buf, [1, 1]);
+}
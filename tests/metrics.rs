@@ -0,0 +1,172 @@
+#![cfg(feature = "metrics")]
+
+use core::time::Duration;
+use std::cell::Cell;
+
+use moku::{testing::Recorder, *};
+use test_log::test;
+use tester::{
+    machine::{Builder, State},
+    Event, Top,
+};
+
+thread_local! {
+    static NOW: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+fn now() -> Duration {
+    NOW.with(Cell::get)
+}
+
+fn advance(millis: u64) {
+    NOW.with(|now| now.set(now.get() + Duration::from_millis(millis)));
+}
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    use super::advance;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Toggle,
+        Poke,
+        Count,
+        Ignore,
+    }
+
+    pub struct Top;
+
+    impl TopState for Top {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Off
+        }
+    }
+
+    struct Off;
+
+    impl Substate<Top> for Off {
+        #[on(Event::Toggle)]
+        fn toggle() -> State {
+            State::On
+        }
+    }
+
+    struct On;
+
+    impl Substate<Top> for On {
+        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            advance(5);
+            Self
+        }
+
+        fn update(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            advance(2);
+        }
+
+        #[on(Event::Toggle)]
+        fn toggle() -> State {
+            State::Off
+        }
+
+        #[on(Event::Poke)]
+        fn poke(&mut self) -> Response<State> {
+            advance(1);
+            Response::Drop
+        }
+
+        #[on(Event::Count)]
+        fn count() -> Response<State> {
+            Response::Handled
+        }
+    }
+}
+
+#[test]
+fn counters() {
+    let mut machine = Builder::new(Top).clock(Clock::new(now)).build();
+    assert_eq!(machine.metrics().get(State::Top).init.calls, 1);
+    assert_eq!(machine.metrics().get(State::Off).enter.calls, 1);
+
+    machine.handle_event(&Event::Toggle);
+    machine.update();
+    machine.update();
+    machine.top_down_update();
+    machine.handle_event(&Event::Poke);
+    machine.handle_event(&Event::Count);
+    machine.handle_event(&Event::Ignore);
+    machine.handle_event(&Event::Toggle);
+
+    let off = machine.metrics().get(State::Off);
+    assert_eq!(off.enter.calls, 2);
+    assert_eq!(off.exit.calls, 1);
+    assert_eq!(off.handle_event.calls, 1);
+    assert_eq!(off.events_handled, 1);
+    assert_eq!(off.events_dropped, 0);
+
+    let on = machine.metrics().get(State::On);
+    assert_eq!(on.enter.calls, 1);
+    assert_eq!(on.init.calls, 1);
+    assert_eq!(on.update.calls, 2);
+    assert_eq!(on.top_down_update.calls, 1);
+    assert_eq!(on.exit.calls, 1);
+    assert_eq!(on.handle_event.calls, 4);
+    assert_eq!(on.events_handled, 3);
+    assert_eq!(on.events_dropped, 1);
+
+    let top = machine.metrics().get(State::Top);
+    assert_eq!(top.update.calls, 2);
+    assert_eq!(top.handle_event.calls, 1);
+    assert_eq!(top.events_handled, 0);
+    assert_eq!(top.enter, HookMetrics::default());
+
+    machine.reset_metrics();
+    assert!(machine
+        .metrics()
+        .iter()
+        .all(|(_, metrics)| *metrics == StateMetrics::default()));
+}
+
+#[test]
+fn hook_time() {
+    let mut machine = Builder::new(Top).clock(Clock::new(now)).build();
+    machine.handle_event(&Event::Toggle);
+    machine.update();
+    machine.update();
+    machine.handle_event(&Event::Poke);
+
+    let on = machine.metrics().get(State::On);
+    assert_eq!(on.enter.time, Duration::from_millis(5));
+    assert_eq!(on.update.time, Duration::from_millis(4));
+    assert_eq!(on.handle_event.time, Duration::from_millis(1));
+    assert_eq!(on.total_time(), Duration::from_millis(10));
+    assert_eq!(
+        machine.metrics().get(State::Top).total_time(),
+        Duration::ZERO
+    );
+
+    let (hottest, _) = machine
+        .metrics()
+        .iter()
+        .max_by_key(|(_, metrics)| metrics.total_time())
+        .unwrap();
+    assert_eq!(hottest, State::On);
+}
+
+#[test]
+fn observer_is_forwarded() {
+    let mut machine = Builder::new(Top)
+        .clock(Clock::new(now))
+        .observer(Recorder::new())
+        .build();
+    machine.observer_mut().clear();
+
+    machine.handle_event(&Event::Toggle);
+    assert_eq!(machine.observer().len(), 4);
+    assert_eq!(machine.metrics().get(State::On).enter.calls, 1);
+}
@@ -0,0 +1,195 @@
+use std::collections::HashSet;
+
+use moku::{testing::*, *};
+use test_log::test;
+use tester::{
+    machine::{Builder, State},
+    Event, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Event {
+        Start,
+        Stop,
+        Jam,
+    }
+    impl StateMachineEvent for Event {}
+
+    #[derive(Clone, PartialEq, Eq, Hash)]
+    pub struct Top {
+        pub limit: u8,
+    }
+    impl TopState for Top {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Idle
+        }
+
+        fn handle_event(&mut self, _event: &Self::Event) -> impl Into<Next<Self::State>> {}
+    }
+
+    #[derive(Clone, PartialEq, Eq, Hash)]
+    struct Idle;
+    impl Substate<Top> for Idle {
+        fn handle_event(
+            &mut self,
+            _ctx: &mut Self::Context<'_>,
+            event: &Self::Event,
+        ) -> impl Into<Response<Self::State>> {
+            match event {
+                Event::Start => Response::Next(State::Running.into()),
+                _ => Response::Drop,
+            }
+        }
+    }
+
+    #[derive(Clone, PartialEq, Eq, Hash)]
+    struct Running {
+        ticks: u8,
+    }
+    impl Substate<Top> for Running {
+        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            Self { ticks: 0 }
+        }
+
+        fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            if self.ticks < ctx.top.limit {
+                self.ticks += 1;
+            }
+        }
+
+        fn handle_event(
+            &mut self,
+            _ctx: &mut Self::Context<'_>,
+            event: &Self::Event,
+        ) -> impl Into<Response<Self::State>> {
+            match event {
+                Event::Stop => Response::Next(State::Idle.into()),
+                Event::Jam => Response::Next(State::Jammed.into()),
+                Event::Start => Response::Next(Next::None),
+            }
+        }
+    }
+
+    #[derive(Clone, PartialEq, Eq, Hash)]
+    struct Jammed;
+    impl Substate<Top> for Jammed {}
+
+    #[derive(Clone, PartialEq, Eq, Hash)]
+    struct Orphan;
+    impl Substate<Top> for Orphan {}
+}
+
+#[test]
+fn report() {
+    let report = model_check(Builder::new(Top { limit: 2 }).build())
+        .events([Event::Start, Event::Stop, Event::Jam])
+        .run();
+
+    // Idle, Running with 0..=2 ticks, and Jammed
+    assert_eq!(report.configurations, 5);
+    assert!(report.complete);
+    assert!(!report.is_ok());
+
+    assert_eq!(report.unreached, [State::Orphan]);
+
+    assert_eq!(
+        report.deadlocks,
+        [Deadlock {
+            state: State::Jammed,
+            steps: vec![
+                Step::HandleEvent(Event::Start),
+                Step::HandleEvent(Event::Jam),
+            ],
+        }]
+    );
+
+    let dropped: Vec<_> = report
+        .dropped
+        .iter()
+        .map(|dropped| (dropped.state, dropped.event))
+        .collect();
+    // Idle explicitly drops Stop and Jam, so they are not reported
+    assert_eq!(
+        dropped,
+        [
+            (State::Running, Event::Start),
+            (State::Jammed, Event::Start),
+            (State::Jammed, Event::Stop),
+            (State::Jammed, Event::Jam),
+        ]
+    );
+    assert_eq!(report.dropped[0].steps, [Step::HandleEvent(Event::Start)]);
+
+    assert_eq!(
+        report.to_string(),
+        "explored 5 configurations
+unreached states: [Orphan]
+deadlock in Jammed after handle_event(&Start) handle_event(&Jam)
+Start dropped in Running after handle_event(&Start)
+Start dropped in Jammed after handle_event(&Start) handle_event(&Jam)
+Stop dropped in Jammed after handle_event(&Start) handle_event(&Jam)
+Jam dropped in Jammed after handle_event(&Start) handle_event(&Jam)"
+    );
+}
+
+#[test]
+fn no_events() {
+    let report = model_check(Builder::new(Top { limit: 2 }).build()).run();
+    assert_eq!(report.configurations, 1);
+    assert_eq!(
+        report.unreached,
+        [State::Running, State::Jammed, State::Orphan]
+    );
+    assert_eq!(report.deadlocks.len(), 1);
+    assert_eq!(report.deadlocks[0].state, State::Idle);
+    assert!(report.deadlocks[0].steps.is_empty());
+    assert!(report.dropped.is_empty());
+}
+
+#[test]
+fn max_configurations() {
+    let report = model_check(Builder::new(Top { limit: 200 }).build())
+        .events([Event::Start, Event::Stop])
+        .max_configurations(10)
+        .run();
+
+    assert_eq!(report.configurations, 10);
+    assert!(!report.complete);
+    assert!(report
+        .to_string()
+        .starts_with("explored 10 configurations (incomplete)"));
+}
+
+#[test]
+fn configurations() {
+    let mut machine = Builder::new(Top { limit: 2 }).build();
+    let copy = machine.clone();
+    assert!(machine == copy);
+
+    machine.handle_event(&Event::Start);
+    assert!(machine != copy);
+    assert!(matches!(copy.state(), State::Idle));
+
+    let running = machine.clone();
+    machine.update();
+    assert!(machine != running);
+
+    // the observer is not part of the configuration
+    let mut observed = Builder::new(Top { limit: 2 })
+        .observer(Recorder::new())
+        .build();
+    observed.handle_event(&Event::Start);
+    observed.update();
+    assert!(observed.clone() == observed);
+
+    let set = HashSet::from([machine.clone(), machine, running, copy]);
+    assert_eq!(set.len(), 3);
+}
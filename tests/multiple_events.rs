@@ -0,0 +1,174 @@
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, Event, State},
+    Command, Digit, Frame, Tick, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(Debug, Clone, PartialEq, StateMachineEvent)]
+    pub struct Tick;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Frame(pub Vec<u8>);
+    impl StateMachineEvent for Frame {}
+
+    #[derive(Debug, Clone, PartialEq, StateMachineEvent)]
+    pub enum Command {
+        Open,
+        Close,
+    }
+
+    /// A digit received in a frame, only handled by the `Keypad` subtree.
+    pub struct Digit(pub u8);
+
+    impl TryFrom<&machine::Event> for Digit {
+        type Error = ();
+
+        fn try_from(event: &machine::Event) -> Result<Self, ()> {
+            match event {
+                machine::Event::Frame(Frame(bytes)) => match bytes.as_slice() {
+                    [byte @ b'0'..=b'9'] => Ok(Digit(byte - b'0')),
+                    _ => Err(()),
+                },
+                _ => Err(()),
+            }
+        }
+    }
+
+    #[derive(Default)]
+    pub struct Top {
+        pub ticks: u32,
+        pub frames: u32,
+        pub digits: Vec<u8>,
+    }
+
+    impl TopState for Top {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Closed
+        }
+
+        fn handle_event(&mut self, event: &Self::Event) -> impl Into<Next<Self::State>> {
+            match event {
+                machine::Event::Tick(_) => self.ticks += 1,
+                machine::Event::Frame(_) => self.frames += 1,
+                machine::Event::Command(_) => (),
+            }
+        }
+    }
+
+    struct Closed;
+
+    impl Substate<Top> for Closed {
+        type Event = Command;
+
+        fn handle_event(
+            &mut self,
+            _ctx: &mut Self::Context<'_>,
+            event: &Self::Event,
+        ) -> impl Into<Response<Self::State>> {
+            match event {
+                Command::Open => Response::Next(State::Keypad.into()),
+                Command::Close => Response::Drop,
+            }
+        }
+    }
+
+    struct Keypad;
+
+    impl Substate<Closed> for Keypad {
+        #[on(Command::Close)]
+        fn close(&mut self) -> Next<State> {
+            Next::ExactTarget(State::Closed)
+        }
+    }
+
+    struct Entry;
+
+    impl Substate<Keypad> for Entry {
+        type Event = Digit;
+
+        #[on(Digit(digit))]
+        fn digit(&mut self, ctx: &mut Self::Context<'_>, digit: &u8) -> Response<Self::State> {
+            ctx.top.digits.push(*digit);
+            Response::Drop
+        }
+    }
+}
+
+#[test]
+fn entry_points() {
+    let mut machine = Builder::new(Top::default()).build();
+    assert_eq!(machine.handle_tick(Tick), EventOutcome::Unhandled);
+    assert_eq!(
+        machine.handle_frame(Frame(vec![1])),
+        EventOutcome::Unhandled
+    );
+    assert_eq!(
+        machine.handle_command(Command::Open),
+        EventOutcome::Transition {
+            state: State::Closed,
+            target: State::Keypad,
+        }
+    );
+    assert_eq!(machine.handle_event(&Tick.into()), EventOutcome::Unhandled);
+
+    assert_eq!(machine.top_ref().ticks, 2);
+    assert_eq!(machine.top_ref().frames, 1);
+}
+
+#[test]
+fn narrowing() {
+    let mut machine = Builder::new(Top::default()).build();
+    machine.transition(State::Entry);
+
+    // converted for the subtree, and bubbled to the top state otherwise
+    assert_eq!(
+        machine.handle_frame(Frame(b"7".to_vec())),
+        EventOutcome::Dropped {
+            state: State::Entry
+        }
+    );
+    assert_eq!(
+        machine.handle_frame(Frame(b"42".to_vec())),
+        EventOutcome::Unhandled
+    );
+    assert_eq!(machine.handle_tick(Tick), EventOutcome::Unhandled);
+    assert_eq!(machine.top_ref().digits, [7]);
+    assert_eq!(machine.top_ref().frames, 1);
+    assert_eq!(machine.top_ref().ticks, 1);
+
+    // deferred by the digit subtree and converted to a command by its superstates
+    assert_eq!(
+        machine.handle_command(Command::Close),
+        EventOutcome::ExactTransition {
+            state: State::Keypad,
+            target: State::Closed,
+        }
+    );
+    assert_eq!(
+        machine.handle_command(Command::Close),
+        EventOutcome::Dropped {
+            state: State::Closed
+        }
+    );
+}
+
+#[test]
+fn combined_event() {
+    let event = Event::from(Command::Open);
+    assert_eq!(event.name(), "Open");
+    assert_eq!(format!("{event:?}"), "Open");
+    assert_eq!(event.clone(), event);
+    assert!(event != Event::Tick(Tick));
+    assert_eq!(Command::try_from(&event), Ok(Command::Open));
+    assert_eq!(Tick::try_from(&event), Err(()));
+    assert!(Digit::try_from(&Event::Frame(Frame(b"1".to_vec()))).is_ok());
+}
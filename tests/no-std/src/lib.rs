@@ -455,6 +455,32 @@ mod tests {
         assert_eq!(state.enter, 1);
         assert_eq!(state.init, 1);
     }
+
+    #[test]
+    fn trace() {
+        let mut machine = Builder::new(Top::default())
+            .observer(testing::Recorder::new())
+            .build();
+
+        assert_trace!(machine, transition(State::AA) => [
+            Enter(State::A),
+            Enter(State::AA),
+            Init(State::AA),
+        ]);
+
+        assert_trace!(machine, update() => [
+            Update(State::AA),
+            Update(State::A),
+            Update(State::Top),
+        ]);
+
+        assert_trace!(machine, transition(State::B) => [
+            Exit(State::AA),
+            Exit(State::A),
+            Enter(State::B),
+            Init(State::B),
+        ]);
+    }
 }
 
 #[state_machine(Kikai)]
@@ -479,3 +505,106 @@ mod named_tests {
         assert_eq!(machine.name(), "Kikai");
     }
 }
+
+#[cfg(test)]
+mod history_tests {
+    use super::tester::{machine::*, *};
+    use moku::*;
+
+    #[test]
+    fn history() {
+        let mut machine = Builder::new(Top::default())
+            .observer(History::<_, 2>::default())
+            .build();
+        machine.transition(State::AA);
+        machine.update();
+        machine.transition(State::B);
+
+        let mut actions = machine.history().iter();
+        assert_eq!(actions.next(), Some(MachineAction::Update(State::AA)));
+        assert_eq!(
+            actions.next(),
+            Some(MachineAction::Transition {
+                source: State::AA,
+                target: State::B,
+                exact: false,
+            })
+        );
+        assert_eq!(actions.next(), None);
+    }
+}
+
+#[state_machine]
+mod emitter {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    pub struct Top;
+
+    impl TopState for Top {
+        type Output = u8;
+
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Emitting
+        }
+    }
+
+    struct Emitting;
+
+    impl Substate<Top> for Emitting {
+        fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            let _ = ctx.outputs.push(ctx.outputs.len() as u8);
+        }
+    }
+}
+
+#[cfg(test)]
+mod outputs_tests {
+    use super::emitter::{machine::Builder, *};
+    use moku::*;
+
+    #[test]
+    fn outputs() {
+        let mut machine = Builder::new(Top).output_capacity::<2>().build();
+        machine.update();
+        machine.update();
+        machine.update();
+
+        let mut outputs = machine.drain_outputs();
+        assert_eq!(outputs.next(), Some(0));
+        assert_eq!(outputs.next(), Some(1));
+        assert_eq!(outputs.next(), None);
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod metrics_tests {
+    use super::tester::{machine::*, *};
+    use core::{
+        sync::atomic::{AtomicU64, Ordering},
+        time::Duration,
+    };
+    use moku::*;
+
+    static TICKS: AtomicU64 = AtomicU64::new(0);
+
+    fn now() -> Duration {
+        Duration::from_millis(TICKS.fetch_add(1, Ordering::Relaxed))
+    }
+
+    #[test]
+    fn metrics() {
+        let mut machine = Builder::new(Top::default()).clock(Clock::new(now)).build();
+        machine.transition(State::AA);
+        machine.update();
+
+        let metrics = machine.metrics();
+        assert_eq!(metrics.get(State::A).enter.calls, 1);
+        assert_eq!(metrics.get(State::AA).update.calls, 1);
+        assert_eq!(metrics.get(State::AA).update.time, Duration::from_millis(1));
+        assert_eq!(metrics.get(State::Top).update.calls, 1);
+    }
+}
@@ -0,0 +1,155 @@
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, State},
+    Command, Event, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Command {
+        Motor(i8),
+        Beep,
+    }
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Go(i8),
+        Stop,
+    }
+
+    #[derive(Clone, PartialEq)]
+    pub struct Top;
+
+    impl TopState for Top {
+        type Output = Command;
+
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Stopped
+        }
+    }
+
+    #[derive(Clone, PartialEq)]
+    struct Stopped;
+
+    impl Substate<Top> for Stopped {
+        fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            ctx.outputs.push(Command::Motor(0)).unwrap();
+            Self
+        }
+
+        #[on(Event::Go(speed))]
+        fn go(ctx: &mut Self::Context<'_>, speed: &i8) -> State {
+            ctx.outputs.push(Command::Beep).unwrap();
+            ctx.outputs.push(Command::Motor(*speed)).unwrap();
+            State::Moving
+        }
+    }
+
+    #[derive(Clone, PartialEq)]
+    struct Moving;
+
+    impl Substate<Top> for Moving {
+        fn exit(self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            ctx.outputs.push(Command::Beep).unwrap();
+        }
+
+        #[on(Event::Stop)]
+        fn stop() -> State {
+            State::Stopped
+        }
+    }
+}
+
+#[test]
+fn outputs_collected() {
+    let mut machine = Builder::new(Top).build();
+    assert_eq!(
+        machine.outputs().iter().collect::<Vec<_>>(),
+        [&Command::Motor(0)]
+    );
+
+    machine.handle_event(&Event::Go(5));
+    assert_eq!(machine.state(), State::Moving);
+    assert_eq!(
+        machine.outputs().iter().collect::<Vec<_>>(),
+        [&Command::Motor(0), &Command::Beep, &Command::Motor(5)]
+    );
+}
+
+#[test]
+fn outputs_drained() {
+    let mut machine = Builder::new(Top).build();
+    assert_eq!(
+        machine.drain_outputs().collect::<Vec<_>>(),
+        [Command::Motor(0)]
+    );
+    assert!(machine.outputs().is_empty());
+
+    machine.handle_event(&Event::Go(3));
+    machine.drain_outputs();
+
+    machine.handle_event(&Event::Stop);
+    assert_eq!(
+        machine.drain_outputs().collect::<Vec<_>>(),
+        [Command::Beep, Command::Motor(0)]
+    );
+}
+
+#[test]
+fn outputs_ignored_by_eq() {
+    let mut machine = Builder::new(Top).build();
+    let clone = machine.clone();
+    assert!(clone.outputs().iter().eq(machine.outputs().iter()));
+
+    machine.drain_outputs();
+    assert!(machine == clone);
+}
+
+#[test]
+fn no_outputs() {
+    #[state_machine]
+    mod plain {
+        use moku::*;
+
+        #[machine_module]
+        pub mod machine {}
+
+        pub struct Top;
+        impl TopState for Top {}
+    }
+
+    let machine = plain::machine::Builder::new(plain::Top).build();
+    assert!(machine.outputs().is_empty());
+}
+
+#[test]
+fn outputs_bounded() {
+    let mut machine = Builder::new(Top).output_capacity::<2>().build();
+    assert_eq!(machine.outputs().capacity(), 2);
+
+    // outputs pushed while the queue is full are given back
+    machine.outputs_mut().push(Command::Beep).unwrap();
+    assert!(machine.outputs().is_full());
+    assert_eq!(
+        machine.outputs_mut().push(Command::Beep),
+        Err(Command::Beep)
+    );
+
+    // outputs that aren't iterated over are still drained
+    assert_eq!(machine.drain_outputs().next(), Some(Command::Motor(0)));
+    assert!(machine.outputs().is_empty());
+
+    machine.handle_event(&Event::Go(1));
+    assert_eq!(
+        machine.drain_outputs().collect::<Vec<_>>(),
+        [Command::Beep, Command::Motor(1)]
+    );
+}
@@ -0,0 +1,184 @@
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, State},
+    Bus, Event, MockBus, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    pub trait Bus {
+        fn write(&mut self, value: u8);
+    }
+
+    #[derive(Default)]
+    pub struct MockBus {
+        pub written: Vec<u8>,
+    }
+
+    impl Bus for MockBus {
+        fn write(&mut self, value: u8) {
+            self.written.push(value);
+        }
+    }
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Write(u8),
+        Stop,
+    }
+
+    pub struct Top;
+
+    impl TopState for Top {
+        type Resources = dyn Bus;
+
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Idle
+        }
+    }
+
+    struct Idle;
+
+    impl Substate<Top> for Idle {
+        fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            ctx.resources.write(1);
+            Self
+        }
+
+        fn update(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            State::Writing
+        }
+    }
+
+    struct Writing;
+
+    impl Substate<Top> for Writing {
+        fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            ctx.resources.write(2);
+        }
+
+        fn exit(self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            ctx.resources.write(3);
+        }
+
+        #[on(Event::Write(value))]
+        fn write(ctx: &mut Self::Context<'_>, value: &u8) {
+            ctx.resources.write(*value);
+        }
+
+        #[on(Event::Stop)]
+        fn stop() -> State {
+            State::Idle
+        }
+    }
+}
+
+#[test]
+fn build_with() {
+    let mut bus = MockBus::default();
+    let machine = Builder::new(Top).build_with(&mut bus);
+    assert_eq!(machine.state(), State::Idle);
+    assert_eq!(bus.written, [1]);
+}
+
+#[test]
+fn update_with() {
+    let mut bus = MockBus::default();
+    let mut machine = Builder::new(Top).build_with(&mut bus);
+
+    machine.update_with(&mut bus);
+    assert_eq!(machine.state(), State::Writing);
+
+    machine.update_with(&mut bus);
+    machine.top_down_update_with(&mut bus);
+    assert_eq!(bus.written, [1, 2]);
+}
+
+#[test]
+fn transition_with() {
+    let mut bus = MockBus::default();
+    let mut machine = Builder::new(Top).build_with(&mut bus);
+
+    machine.transition_with(&mut bus, State::Writing);
+    machine.exact_transition_with(&mut bus, State::Idle);
+    assert_eq!(bus.written, [1, 3, 1]);
+}
+
+#[test]
+fn handle_event_with() {
+    let mut bus = MockBus::default();
+    let mut machine = Builder::new(Top).build_with(&mut bus);
+    machine.transition_with(&mut bus, State::Writing);
+
+    // the resources may be used by other code between calls
+    bus.write(0);
+
+    machine.handle_event_with(&mut bus, &Event::Write(7));
+    assert_eq!(
+        machine.handle_event_with(&mut bus, &Event::Stop),
+        EventOutcome::Transition {
+            state: State::Writing,
+            target: State::Idle,
+        }
+    );
+    assert_eq!(bus.written, [1, 0, 7, 3, 1]);
+}
+
+#[state_machine]
+mod multi {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+
+    #[derive(StateMachineEvent)]
+    pub struct Add(pub u32);
+
+    #[derive(StateMachineEvent)]
+    pub struct Clear;
+
+    pub struct Top<T: Copy + Into<u32>> {
+        pub step: T,
+    }
+
+    impl<T: Copy + Into<u32>> TopState for Top<T> {
+        type Resources = u32;
+
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            machine::State::Counting
+        }
+    }
+
+    struct Counting;
+
+    impl<T: Copy + Into<u32>> Substate<Top<T>> for Counting {
+        #[on(machine::Event::Add(Add(amount)))]
+        fn add(ctx: &mut Self::Context<'_>, amount: &u32) {
+            *ctx.resources += amount;
+        }
+
+        #[on(machine::Event::Clear(_))]
+        fn clear(ctx: &mut Self::Context<'_>) {
+            *ctx.resources = ctx.top.step.into();
+        }
+    }
+}
+
+#[test]
+fn generic_multiple_events_with() {
+    let mut total = 0;
+    let mut machine = multi::machine::Builder::new(multi::Top { step: 7u8 }).build_with(&mut total);
+
+    machine.handle_add_with(&mut total, multi::Add(3));
+    assert_eq!(total, 3);
+
+    machine.handle_clear_with(&mut total, multi::Clear);
+    assert_eq!(total, 7);
+}
@@ -0,0 +1,177 @@
+use media_player::{machine::*, *};
+use moku::{testing::*, *};
+use test_log::test;
+
+#[state_machine]
+mod media_player {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    // the chart generates the `Event` enum, the transitions, and any state that isn't defined here
+    include_scxml!("scxml/player.scxml");
+
+    #[derive(Default)]
+    pub struct Top {
+        pub plays: u8,
+    }
+
+    impl TopState for Top {}
+
+    pub struct Playing {
+        pub position: u32,
+    }
+
+    impl Substate<Active> for Playing {
+        fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            ctx.top.plays += 1;
+            Self { position: 0 }
+        }
+
+        fn update(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            self.position += 1;
+        }
+
+        // takes precedence over the `pause` transition of the chart
+        #[on(Event::Pause)]
+        fn pause(&self) -> Option<State> {
+            (self.position > 0).then_some(State::Paused)
+        }
+    }
+}
+
+#[test]
+fn state_chart() {
+    assert_eq!(
+        STATE_CHART,
+        "Top
+├─ Idle
+├─ Active
+│  ├─ Playing
+│  └─ Paused
+├─ Buffering
+├─ Loading
+│  └─ Fetching
+└─ Done"
+    );
+}
+
+#[test]
+fn name() {
+    let machine = Builder::new(Top::default()).build();
+    assert_eq!(machine.name(), "MediaPlayer");
+}
+
+#[test]
+fn initial() {
+    let mut machine = Builder::new(Top::default()).build();
+    assert!(matches!(machine.state(), State::Idle));
+
+    machine.transition(State::Active);
+    assert!(matches!(machine.state(), State::Playing));
+}
+
+#[test]
+fn events() {
+    let mut machine = Builder::new(Top::default()).build();
+
+    machine.handle_event(&Event::MediaLoad);
+    assert!(matches!(machine.state(), State::Playing));
+
+    machine.update();
+    machine.handle_event(&Event::Pause);
+    assert!(matches!(machine.state(), State::Paused));
+
+    machine.handle_event(&Event::Play);
+    assert!(matches!(machine.state(), State::Playing));
+
+    machine.handle_event(&Event::MediaEject);
+    assert!(matches!(machine.state(), State::Idle));
+}
+
+#[test]
+fn multiple_events() {
+    let mut machine = Builder::new(Top::default()).build();
+    machine.transition(State::Paused);
+
+    machine.handle_event(&Event::Pause);
+    assert!(matches!(machine.state(), State::Playing));
+}
+
+#[test]
+fn wildcard_drop() {
+    let mut machine = Builder::new(Top::default()).build();
+    machine.transition(State::Paused);
+
+    // the wildcard transition without a target consumes events the superstate would handle
+    machine.handle_event(&Event::MediaEject);
+    assert!(matches!(machine.state(), State::Paused));
+
+    machine.handle_event(&Event::Reset);
+    assert!(matches!(machine.state(), State::Paused));
+}
+
+#[test]
+fn self_transition() {
+    let mut machine = Builder::new(Top::default())
+        .observer(Recorder::new())
+        .build();
+    machine.transition(State::Playing);
+
+    // an SCXML transition from a state to itself exits and re-enters it, along with its
+    // initial substate
+    assert_trace!(
+        machine,
+        handle_event(&Event::Reset) => [
+            HandleEvent(State::Playing),
+            HandleEvent(State::Active),
+            Exit(State::Playing),
+            Exit(State::Active),
+            Enter(State::Active),
+            Init(State::Active),
+            Enter(State::Playing),
+            Init(State::Playing),
+        ]
+    );
+
+    // whereas a manual transition to an active state leaves it as it is
+    machine.transition(State::Paused);
+    assert_trace!(machine, transition(State::Active) => []);
+    assert!(matches!(machine.state(), State::Paused));
+}
+
+#[test]
+fn eventless() {
+    let mut machine = Builder::new(Top::default()).build();
+
+    // eventless transitions are taken as soon as their state is entered
+    machine.transition(State::Buffering);
+    assert!(matches!(machine.state(), State::Done));
+
+    // including those of the ancestors of the entered state
+    machine.transition(State::Fetching);
+    assert!(matches!(machine.state(), State::Idle));
+}
+
+#[test]
+fn user_hooks() {
+    let mut machine = Builder::new(Top::default()).build();
+
+    // the user's handler is matched before the chart's transition
+    machine.handle_event(&Event::MediaLoad);
+    machine.handle_event(&Event::Pause);
+    assert!(matches!(machine.state(), State::Playing));
+
+    machine.update();
+    machine.update();
+    let playing: &Playing = machine.state_ref().unwrap();
+    assert_eq!(playing.position, 2);
+
+    machine.handle_event(&Event::Pause);
+    assert!(matches!(machine.state(), State::Paused));
+
+    machine.handle_event(&Event::Play);
+    assert_eq!(machine.top_ref().plays, 2);
+}
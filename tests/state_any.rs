@@ -0,0 +1,70 @@
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, State},
+    Counting, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+
+    #[derive(Debug)]
+    pub struct Top<T> {
+        pub value: T,
+    }
+
+    impl<T> TopState for Top<T> {}
+
+    #[derive(Debug, PartialEq)]
+    pub struct Counting {
+        pub count: u8,
+    }
+
+    impl<T> Substate<Top<T>> for Counting {
+        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            Self { count: 0 }
+        }
+    }
+
+    #[derive(Debug)]
+    struct Idle;
+    impl<T> Substate<Top<T>> for Idle {}
+}
+
+#[test]
+fn state_any() {
+    let mut machine = Builder::new(Top { value: 'x' }).build();
+    machine.transition(State::Counting);
+
+    let counting = machine.state_any(State::Counting).unwrap();
+    assert_eq!(counting.downcast_ref(), Some(&Counting { count: 0 }));
+    assert!(machine.state_any(State::Idle).is_none());
+
+    let top = machine.state_any(State::Top).unwrap();
+    assert_eq!(top.downcast_ref::<Top<char>>().unwrap().value, 'x');
+
+    let counting = machine.state_any_mut(State::Counting).unwrap();
+    counting.downcast_mut::<Counting>().unwrap().count += 1;
+    let counting: &Counting = machine.state_ref().unwrap();
+    assert_eq!(counting.count, 1);
+}
+
+#[test]
+fn state_debug() {
+    let mut machine = Builder::new(Top { value: 'x' }).build();
+    assert_eq!(
+        format!("{:?}", machine.state_debug(State::Top).unwrap()),
+        "Top { value: 'x' }"
+    );
+    assert!(machine.state_debug(State::Counting).is_none());
+
+    machine.transition(State::Counting);
+    assert_eq!(
+        format!("{:?}", machine.state_debug(State::Counting).unwrap()),
+        "Counting { count: 0 }"
+    );
+}
@@ -0,0 +1,382 @@
+use moku::*;
+use test_log::test;
+
+#[state_machine]
+mod inner {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Finish,
+    }
+
+    pub struct Top;
+
+    impl TopState for Top {
+        type Resources = Vec<&'static str>;
+        type Input = u8;
+
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Leaf
+        }
+    }
+
+    struct Branch;
+
+    impl Substate<Top> for Branch {
+        fn exit(self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            ctx.resources.push("exit branch");
+        }
+    }
+
+    struct Leaf;
+
+    impl Substate<Branch> for Leaf {
+        fn top_down_update(
+            &mut self,
+            ctx: &mut Self::Context<'_>,
+            input: &u8,
+        ) -> impl Into<Next<Self::State>> {
+            if *input > 0 {
+                ctx.resources.push("top down leaf");
+            }
+        }
+
+        fn exit(self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            ctx.resources.push("exit leaf");
+            // ignored when shutting down
+            State::Done
+        }
+
+        #[on(Event::Finish)]
+        fn finish() -> State {
+            State::Done
+        }
+    }
+
+    pub struct Done;
+
+    impl Substate<Top> for Done {
+        fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            ctx.resources.push("enter done");
+            Self
+        }
+    }
+}
+
+#[test]
+fn shutdown() {
+    let mut log = Vec::new();
+    let mut machine: inner::machine::Machine<testing::Recorder<inner::machine::State>> =
+        inner::machine::Builder::new(inner::Top)
+            .observer(testing::Recorder::new())
+            .build_with(&mut log);
+    machine.observer_mut().clear();
+
+    machine.shutdown_with(&mut log);
+    assert_eq!(machine.state(), inner::machine::State::Top);
+    assert_eq!(log, ["exit leaf", "exit branch"]);
+    assert_eq!(
+        machine.observer().iter().collect::<Vec<_>>(),
+        [
+            Action::Exit(inner::machine::State::Leaf),
+            Action::Exit(inner::machine::State::Branch),
+        ]
+    );
+
+    // shutting down an idle machine does nothing
+    machine.shutdown_with(&mut log);
+    assert_eq!(log.len(), 2);
+}
+
+#[test]
+fn into_response() {
+    let mut log = Vec::new();
+    let mut machine = inner::machine::Builder::new(inner::Top).build_with(&mut log);
+
+    let outcome = machine.handle_event_with(&mut log, &inner::Event::Finish);
+    assert!(matches!(
+        outcome.into_response::<inner::machine::State>(),
+        Response::Handled
+    ));
+
+    let outcome = machine.handle_event_with(&mut log, &inner::Event::Finish);
+    assert!(matches!(
+        outcome.into_response::<inner::machine::State>(),
+        Response::Next(Next::None)
+    ));
+}
+
+#[state_machine]
+mod outer {
+    use moku::*;
+
+    use super::inner;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Finish,
+    }
+
+    impl TryFrom<&Event> for inner::Event {
+        type Error = ();
+
+        fn try_from(event: &Event) -> Result<Self, ()> {
+            match event {
+                Event::Finish => Ok(inner::Event::Finish),
+            }
+        }
+    }
+
+    pub struct Top {
+        pub log: Vec<&'static str>,
+    }
+
+    impl TopState for Top {
+        type Input = u8;
+
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Nested
+        }
+    }
+
+    struct Nested {
+        inner: inner::machine::Machine,
+    }
+
+    #[submachine(inner, resources = &mut ctx.top.log, input = input)]
+    impl Substate<Top> for Nested {
+        fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            Self {
+                inner: inner::machine::Builder::new(inner::Top).build_with(&mut ctx.top.log),
+            }
+        }
+
+        fn update(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            (self.inner.state() == inner::machine::State::Done).then_some(State::Finished)
+        }
+    }
+
+    struct Finished;
+
+    impl Substate<Top> for Finished {
+        #[on(Event::Finish)]
+        fn finish(ctx: &mut Self::Context<'_>) {
+            ctx.top.log.push("finish");
+        }
+    }
+}
+
+#[test]
+fn nested_completion() {
+    let mut machine = outer::machine::Builder::new(outer::Top { log: Vec::new() }).build();
+
+    // resources and input are forwarded from the top down as well
+    machine.top_down_update_input(&1);
+    machine.top_down_update_input(&0);
+    assert_eq!(machine.top_ref().log, ["top down leaf"]);
+
+    // the event is reported as handled by the outer machine, not dropped
+    let outcome = machine.handle_event(&outer::Event::Finish);
+    assert_eq!(
+        outcome,
+        EventOutcome::Handled {
+            state: outer::machine::State::Nested
+        }
+    );
+    assert_eq!(machine.state(), outer::machine::State::Nested);
+
+    machine.update_input(&0);
+    assert_eq!(machine.state(), outer::machine::State::Finished);
+    assert_eq!(
+        machine.top_ref().log,
+        ["top down leaf", "exit leaf", "exit branch", "enter done"]
+    );
+
+    // the outer machine handles the event once the submachine is gone
+    machine.handle_event(&outer::Event::Finish);
+    assert_eq!(machine.top_ref().log.last(), Some(&"finish"));
+}
+
+#[state_machine]
+mod pinger {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Ping,
+    }
+
+    pub struct Top;
+
+    impl TopState for Top {
+        type Output = &'static str;
+
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Waiting
+        }
+    }
+
+    struct Waiting;
+
+    impl Substate<Top> for Waiting {
+        fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            ctx.outputs.push("update waiting").unwrap();
+        }
+
+        fn exit(self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            ctx.outputs.push("exit waiting").unwrap();
+        }
+
+        #[on(Event::Ping)]
+        fn ping() -> State {
+            State::Done
+        }
+    }
+
+    pub struct Done;
+    impl Substate<Top> for Done {}
+}
+
+#[state_machine]
+mod host {
+    use moku::*;
+
+    use super::pinger;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Ping,
+        Pause,
+        Other,
+    }
+
+    impl TryFrom<&Event> for pinger::Event {
+        type Error = ();
+
+        fn try_from(event: &Event) -> Result<Self, ()> {
+            match event {
+                Event::Ping => Ok(pinger::Event::Ping),
+                _ => Err(()),
+            }
+        }
+    }
+
+    pub struct Top;
+
+    impl TopState for Top {
+        type Output = &'static str;
+
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Hosting
+        }
+    }
+
+    struct Hosting {
+        pinger: pinger::machine::Machine,
+    }
+
+    #[submachine(pinger)]
+    impl Substate<Top> for Hosting {
+        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            Self {
+                pinger: pinger::machine::Builder::new(pinger::Top).build(),
+            }
+        }
+
+        fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            for output in self.pinger.drain_outputs() {
+                ctx.outputs.push(output).unwrap();
+            }
+            ctx.outputs.push("update hosting").unwrap();
+            (self.pinger.state() == pinger::machine::State::Done).then_some(State::Idle)
+        }
+
+        // `mut` is added to the receiver to shut the submachine down
+        fn exit(self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            for output in self.pinger.drain_outputs() {
+                ctx.outputs.push(output).unwrap();
+            }
+            ctx.outputs.push("exit hosting").unwrap();
+        }
+
+        #[on(Event::Pause)]
+        fn pause() -> State {
+            State::Idle
+        }
+    }
+
+    struct Idle;
+    impl Substate<Top> for Idle {}
+}
+
+#[test]
+fn attribute_forwarding() {
+    let mut machine = host::machine::Builder::new(host::Top).build();
+
+    // the submachine updates first
+    machine.update();
+    assert_eq!(
+        machine.drain_outputs().collect::<Vec<_>>(),
+        ["update waiting", "update hosting"]
+    );
+
+    assert_eq!(
+        machine.handle_event(&host::Event::Other),
+        EventOutcome::Unhandled
+    );
+    assert_eq!(
+        machine.handle_event(&host::Event::Ping),
+        EventOutcome::Handled {
+            state: host::machine::State::Hosting
+        }
+    );
+    assert_eq!(machine.state(), host::machine::State::Hosting);
+
+    // the state transitions once the submachine completes
+    machine.update();
+    assert_eq!(machine.state(), host::machine::State::Idle);
+    assert_eq!(
+        machine.drain_outputs().collect::<Vec<_>>(),
+        ["exit waiting", "update hosting", "exit hosting"]
+    );
+}
+
+#[test]
+fn attribute_shutdown() {
+    let mut machine = host::machine::Builder::new(host::Top).build();
+
+    // events handled by the state itself aren't forwarded
+    assert_eq!(
+        machine.handle_event(&host::Event::Pause),
+        EventOutcome::Transition {
+            state: host::machine::State::Hosting,
+            target: host::machine::State::Idle,
+        }
+    );
+    assert_eq!(
+        machine.drain_outputs().collect::<Vec<_>>(),
+        ["exit waiting", "exit hosting"]
+    );
+
+    assert_eq!(
+        machine.handle_event(&host::Event::Ping),
+        EventOutcome::Unhandled
+    );
+}
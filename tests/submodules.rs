@@ -0,0 +1,207 @@
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, State},
+    Event, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Toggle,
+        Dim,
+    }
+
+    #[derive(Default)]
+    pub struct Top {
+        pub log: Vec<&'static str>,
+    }
+
+    impl TopState for Top {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Off
+        }
+    }
+
+    struct Off;
+
+    impl Substate<Top> for Off {
+        #[on(Event::Toggle)]
+        fn toggle() -> State {
+            State::Bright
+        }
+    }
+
+    mod on {
+        use super::*;
+
+        struct On;
+
+        impl Substate<Top> for On {
+            fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+                ctx.top.log.push("enter on");
+                Self
+            }
+
+            #[on(Event::Toggle)]
+            fn toggle() -> State {
+                State::Off
+            }
+        }
+
+        mod levels {
+            use super::*;
+
+            struct Bright {
+                level: u8,
+            }
+
+            impl Substate<On> for Bright {
+                fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+                    Self { level: 10 }
+                }
+
+                fn exit(self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+                    if self.level == 10 {
+                        ctx.top.log.push("exit bright");
+                    }
+                }
+
+                #[on(Event::Dim)]
+                fn dim() -> State {
+                    State::Dim
+                }
+            }
+
+            struct Dim;
+
+            impl Substate<super::On> for Dim {
+                fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+                    ctx.on.log();
+                    ctx.top.log.push("update dim");
+                }
+            }
+        }
+
+        impl On {
+            fn log(&self) {}
+        }
+    }
+}
+
+#[test]
+fn state_chart() {
+    assert_eq!(
+        tester::machine::STATE_CHART,
+        "Top\n├─ Off\n└─ On\n   ├─ Bright\n   └─ Dim"
+    );
+}
+
+#[test]
+fn states_in_submodules() {
+    let mut machine = Builder::new(Top::default()).build();
+
+    machine.handle_event(&Event::Toggle);
+    assert_eq!(machine.state(), State::Bright);
+
+    machine.handle_event(&Event::Dim);
+    machine.update();
+    assert_eq!(machine.state(), State::Dim);
+    assert_eq!(
+        machine.top_ref().log,
+        ["enter on", "exit bright", "update dim"]
+    );
+
+    machine.handle_event(&Event::Toggle);
+    assert_eq!(machine.state(), State::Off);
+}
+
+#[state_machine]
+mod narrow {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(StateMachineEvent)]
+    pub struct Press(pub u8);
+
+    #[derive(StateMachineEvent)]
+    pub struct Reset;
+
+    /// A button press, only handled by the `Pressed` subtree.
+    pub struct Button(u8);
+
+    impl TryFrom<&machine::Event> for Button {
+        type Error = ();
+
+        fn try_from(event: &machine::Event) -> Result<Self, ()> {
+            match event {
+                machine::Event::Press(Press(button)) => Ok(Button(*button)),
+                _ => Err(()),
+            }
+        }
+    }
+
+    pub struct Top<T: Copy> {
+        pub presses: Vec<T>,
+        pub value: T,
+    }
+
+    impl<T: Copy> TopState for Top<T> {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Idle
+        }
+
+        #[on(machine::Event::Reset(_))]
+        fn reset(&mut self) {
+            self.presses.clear();
+        }
+    }
+
+    struct Pressed;
+
+    impl<T: Copy> Substate<Top<T>> for Pressed {
+        type Event = Button;
+    }
+
+    mod idle {
+        use super::*;
+
+        pub struct Idle;
+
+        impl Substate<Pressed> for Idle {
+            #[on(Button(button))]
+            fn press(ctx: &mut Self::Context<'_>, button: &u8) {
+                if *button > 0 {
+                    ctx.top.presses.push(ctx.top.value);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn narrow_events_in_submodules() {
+    let mut machine = narrow::machine::Builder::new(narrow::Top {
+        presses: Vec::new(),
+        value: 'x',
+    })
+    .build();
+
+    machine.handle_press(narrow::Press(1));
+    machine.handle_press(narrow::Press(0));
+    assert_eq!(machine.top_ref().presses, ['x']);
+
+    // not converted for the subtree, and handled by the top state
+    machine.handle_reset(narrow::Reset);
+    assert!(machine.top_ref().presses.is_empty());
+}
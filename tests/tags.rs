@@ -0,0 +1,95 @@
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, State, Tag},
+    Event, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Fault,
+    }
+
+    /// The device.
+    #[tag(Device)]
+    pub struct Top;
+
+    impl TopState for Top {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::Idle
+        }
+    }
+
+    /// Waiting for work.
+    ///
+    /// Nothing to see here.
+    #[tag(UserVisible)]
+    struct Idle;
+
+    impl Substate<Top> for Idle {
+        #[on(Event::Fault)]
+        fn fault() -> State {
+            State::Stopping
+        }
+    }
+
+    /// Overridden by the impl.
+    struct Faulted;
+
+    #[describe("Recovering from a fault.")]
+    #[tag(Safety)]
+    #[moku::tag(Safety, UserVisible)]
+    impl Substate<Top> for Faulted {}
+
+    struct Stopping;
+
+    #[tag(Safety)]
+    impl Substate<Faulted> for Stopping {}
+}
+
+#[test]
+fn descriptions() {
+    assert_eq!(State::Top.description(), Some("The device."));
+    assert_eq!(
+        State::Idle.description(),
+        Some("Waiting for work.\n\nNothing to see here.")
+    );
+    assert_eq!(
+        State::Faulted.description(),
+        Some("Recovering from a fault.")
+    );
+    assert_eq!(State::Stopping.description(), None);
+}
+
+#[test]
+fn tags() {
+    assert_eq!(State::Top.tags(), [Tag::Device]);
+    assert_eq!(State::Idle.tags(), [Tag::UserVisible]);
+    assert_eq!(State::Faulted.tags(), [Tag::Safety, Tag::UserVisible]);
+    assert_eq!(State::Stopping.tags(), [Tag::Safety]);
+}
+
+#[test]
+fn in_tag() {
+    let mut machine = Builder::new(Top).build();
+    assert!(machine.in_tag(Tag::Device));
+    assert!(machine.in_tag(Tag::UserVisible));
+    assert!(!machine.in_tag(Tag::Safety));
+
+    machine.handle_event(&Event::Fault);
+    assert_eq!(machine.state(), State::Stopping);
+    assert!(machine.in_tag(Tag::Safety));
+    assert!(machine.in_tag(Tag::UserVisible));
+
+    machine.transition(State::Idle);
+    assert!(machine.in_tag(Tag::Device));
+    assert!(!machine.in_tag(Tag::Safety));
+}
@@ -0,0 +1,190 @@
+#![allow(clippy::upper_case_acronyms)]
+
+use moku::{testing::Recorder, *};
+use test_log::test;
+use tester::{
+    machine::{Builder, State::*},
+    Event,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    pub struct Event;
+    impl StateMachineEvent for Event {}
+
+    pub struct Top;
+    impl TopState for Top {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::A
+        }
+    }
+
+    struct A;
+    impl Substate<Top> for A {}
+
+    struct AA;
+    impl Substate<A> for AA {
+        fn handle_event(
+            &mut self,
+            _ctx: &mut Self::Context<'_>,
+            _event: &Self::Event,
+        ) -> impl Into<Response<Self::State>> {
+            Response::Drop
+        }
+    }
+
+    struct B;
+    impl Substate<Top> for B {
+        fn init(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            State::BA
+        }
+
+        fn handle_event(
+            &mut self,
+            _ctx: &mut Self::Context<'_>,
+            _event: &Self::Event,
+        ) -> impl Into<Response<Self::State>> {
+            State::AA
+        }
+    }
+
+    struct BA;
+    impl Substate<B> for BA {
+        fn update(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            State::A
+        }
+    }
+
+    struct C;
+    impl Substate<Top> for C {
+        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            Entry::Target(State::B)
+        }
+    }
+}
+
+#[test]
+fn build_trace() {
+    let machine = Builder::new(tester::Top).observer(Recorder::new()).build();
+    assert!(machine
+        .observer()
+        .matches(&[Action::Init(Top), Action::Enter(A), Action::Init(A)]));
+}
+
+#[test]
+fn transition() {
+    let mut machine = Builder::new(tester::Top).observer(Recorder::new()).build();
+    assert_trace!(machine, transition(A) => []);
+    assert_trace!(machine, transition(AA) => [Enter(AA), Init(AA)]);
+    assert_trace!(
+        machine,
+        transition(B) => [Exit(AA), Exit(A), Enter(B), Init(B), Enter(BA), Init(BA)]
+    );
+}
+
+#[test]
+fn exact_transition() {
+    let mut machine = Builder::new(tester::Top).observer(Recorder::new()).build();
+    assert_trace!(machine, exact_transition(A) => [Exit(A), Enter(A), Init(A)]);
+    assert_trace!(
+        machine,
+        exact_transition(Top) => [Exit(A), Init(Top), Enter(A), Init(A)]
+    );
+}
+
+#[test]
+fn short_circuit() {
+    let mut machine = Builder::new(tester::Top).observer(Recorder::new()).build();
+    assert_trace!(
+        machine,
+        transition(C) => [Exit(A), Enter(C), Enter(B), Init(B), Enter(BA), Init(BA)]
+    );
+}
+
+#[test]
+fn update() {
+    let mut machine = Builder::new(tester::Top).observer(Recorder::new()).build();
+    machine.transition(BA);
+    assert_trace!(
+        machine,
+        update() => [
+            Update(BA),
+            Exit(BA),
+            Exit(B),
+            Enter(A),
+            Init(A),
+            Update(Top),
+        ]
+    );
+}
+
+#[test]
+fn top_down_update() {
+    let mut machine = Builder::new(tester::Top).observer(Recorder::new()).build();
+    machine.transition(AA);
+    assert_trace!(
+        machine,
+        top_down_update() => [TopDownUpdate(Top), TopDownUpdate(A), TopDownUpdate(AA)]
+    );
+}
+
+#[test]
+fn handle_event() {
+    let mut machine = Builder::new(tester::Top).observer(Recorder::new()).build();
+    machine.transition(AA);
+    assert_trace!(machine, handle_event(&Event) => [HandleEvent(AA)]);
+
+    machine.transition(BA);
+    assert_trace!(
+        machine,
+        handle_event(&Event) => [
+            HandleEvent(BA),
+            HandleEvent(B),
+            Exit(BA),
+            Exit(B),
+            Enter(A),
+            Enter(AA),
+            Init(AA),
+        ]
+    );
+}
+
+#[test]
+fn recorder() {
+    let mut machine = Builder::new(tester::Top).observer(Recorder::new()).build();
+    machine.observer_mut().clear();
+    assert!(machine.observer().is_empty());
+
+    machine.transition(AA);
+    let recorder = machine.observer();
+    assert_eq!(recorder.len(), 2);
+    assert_eq!(recorder.get(0), Some(Action::Enter(AA)));
+    assert_eq!(recorder.get(1), Some(Action::Init(AA)));
+    assert_eq!(recorder.get(2), None);
+    assert_eq!(
+        recorder.iter().collect::<Vec<_>>(),
+        [Action::Enter(AA), Action::Init(AA)]
+    );
+    assert_eq!(format!("{recorder:?}"), "[Enter(AA), Init(AA)]");
+}
+
+#[test]
+#[should_panic(expected = "assertion `transition(AA)` failed")]
+fn mismatched_trace() {
+    let mut machine = Builder::new(tester::Top).observer(Recorder::new()).build();
+    assert_trace!(machine, transition(AA) => [Enter(AA)]);
+}
+
+#[test]
+#[should_panic(expected = "Recorder capacity of 2 actions exceeded")]
+fn capacity() {
+    Builder::new(tester::Top)
+        .observer(Recorder::<_, 2>::default())
+        .build();
+}
@@ -0,0 +1,152 @@
+use core::time::Duration;
+use std::cell::Cell;
+
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, State},
+    Top,
+};
+
+thread_local! {
+    static NOW: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+fn now() -> Duration {
+    NOW.with(Cell::get)
+}
+
+fn advance(millis: u64) {
+    NOW.with(|now| now.set(now.get() + Duration::from_millis(millis)));
+}
+
+#[state_machine]
+mod tester {
+    use core::time::Duration;
+
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::State;
+
+    pub struct Top {
+        pub blink_period: Duration,
+        pub entry_times: Vec<Duration>,
+        pub exit_times: Vec<Duration>,
+    }
+
+    impl TopState for Top {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::LedOn
+        }
+    }
+
+    struct Enabled;
+
+    impl Substate<Top> for Enabled {}
+
+    struct LedOn;
+
+    impl Substate<Enabled> for LedOn {
+        fn enter(ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            ctx.top.entry_times.push(ctx.entry_time());
+            Self
+        }
+
+        fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            if ctx.time_in_state() >= ctx.top.blink_period {
+                Next::Target(State::LedOff)
+            } else {
+                Next::None
+            }
+        }
+
+        fn exit(self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            let time_in_state = ctx.time_in_state();
+            ctx.top.exit_times.push(time_in_state);
+        }
+    }
+
+    struct LedOff;
+
+    impl Substate<Enabled> for LedOff {
+        fn update(&mut self, ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            if ctx.time_in_state() >= ctx.top.blink_period {
+                Next::Target(State::LedOn)
+            } else {
+                Next::None
+            }
+        }
+    }
+}
+
+#[test]
+fn time_in_state() {
+    advance(3);
+    let machine = Builder::new(Top {
+        blink_period: Duration::from_millis(10),
+        entry_times: Vec::new(),
+        exit_times: Vec::new(),
+    })
+    .clock(Clock::new(now))
+    .build();
+    assert_eq!(machine.entry_time(State::LedOn), Some(now()));
+    assert_eq!(machine.time_in_state(State::LedOn), Some(Duration::ZERO));
+    assert_eq!(machine.time_in_state(State::LedOff), None);
+
+    advance(4);
+    assert_eq!(
+        machine.time_in_state(State::LedOn),
+        Some(Duration::from_millis(4))
+    );
+    assert_eq!(
+        machine.time_in_state(State::Top),
+        Some(Duration::from_millis(4))
+    );
+}
+
+#[test]
+fn time_in_state_from_context() {
+    let mut machine = Builder::new(Top {
+        blink_period: Duration::from_millis(10),
+        entry_times: Vec::new(),
+        exit_times: Vec::new(),
+    })
+    .clock(Clock::new(now))
+    .build();
+    let start = now();
+
+    advance(9);
+    machine.update();
+    assert_eq!(machine.state(), State::LedOn);
+
+    advance(1);
+    machine.update();
+    assert_eq!(machine.state(), State::LedOff);
+    assert_eq!(machine.top_ref().exit_times, [Duration::from_millis(10)]);
+
+    // the superstate keeps its entry time across transitions between its substates
+    assert_eq!(machine.entry_time(State::Enabled), Some(start));
+    assert_eq!(machine.entry_time(State::LedOff), Some(now()));
+
+    advance(10);
+    machine.update();
+    assert_eq!(machine.state(), State::LedOn);
+    assert_eq!(machine.top_ref().entry_times, [start, now()]);
+}
+
+#[test]
+fn default_clock() {
+    let machine = Builder::new(Top {
+        blink_period: Duration::ZERO,
+        entry_times: Vec::new(),
+        exit_times: Vec::new(),
+    })
+    .build();
+
+    let entered = machine.entry_time(State::LedOn).unwrap();
+    std::thread::sleep(Duration::from_millis(1));
+    assert!(machine.time_in_state(State::LedOn).unwrap() >= Duration::from_millis(1));
+    assert!(machine.entry_time(State::Top).unwrap() <= entered);
+}
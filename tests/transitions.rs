@@ -0,0 +1,119 @@
+use moku::*;
+use test_log::test;
+use tester::{
+    machine::{Builder, LedOnTarget, State},
+    Event, Top,
+};
+
+#[state_machine]
+mod tester {
+    use moku::*;
+
+    #[machine_module]
+    pub mod machine {}
+    use machine::{LedOffTarget, LedOnTarget, State};
+
+    #[derive(StateMachineEvent)]
+    pub enum Event {
+        Toggle,
+        Fault,
+    }
+
+    pub struct Top;
+
+    impl TopState for Top {
+        fn init(&mut self) -> impl Into<Next<Self::State>> {
+            State::LedOff
+        }
+    }
+
+    struct Blinking;
+
+    // initial substates must be declared as well
+    #[transitions(Disabled, LedOn)]
+    impl Substate<Top> for Blinking {
+        fn init(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            Self::State::LedOn
+        }
+
+        #[on(Event::Fault)]
+        fn fault() -> Self::State {
+            Self::State::Disabled
+        }
+    }
+
+    struct LedOff;
+
+    #[transitions(LedOn)]
+    impl Substate<Blinking> for LedOff {
+        #[on(Event::Toggle)]
+        fn toggle() -> LedOffTarget {
+            LedOffTarget::LedOn
+        }
+    }
+
+    struct LedOn;
+
+    #[transitions(LedOff, Disabled)]
+    impl Substate<Blinking> for LedOn {
+        fn enter(_ctx: &mut Self::Context<'_>) -> impl Into<Entry<Self::State, Self>> {
+            Self
+        }
+
+        fn update(&mut self, _ctx: &mut Self::Context<'_>) -> impl Into<Next<Self::State>> {
+            Next::ExactTarget(Self::State::LedOff)
+        }
+
+        #[on(Event::Toggle)]
+        fn toggle() -> LedOnTarget {
+            LedOnTarget::LedOff
+        }
+    }
+
+    // may only stay put
+    struct Disabled;
+
+    #[transitions()]
+    impl Substate<Top> for Disabled {}
+}
+
+#[test]
+fn declared_transitions() {
+    assert_eq!(
+        State::LedOn.transitions(),
+        Some([State::LedOff, State::Disabled].as_slice())
+    );
+    assert_eq!(State::Disabled.transitions(), Some([].as_slice()));
+    assert_eq!(State::Top.transitions(), None);
+    assert_eq!(
+        <LedOnTarget as StateEnum>::ALL,
+        [LedOnTarget::LedOff, LedOnTarget::Disabled]
+    );
+    assert_eq!(State::from(LedOnTarget::Disabled), State::Disabled);
+}
+
+#[test]
+fn narrowed_targets() {
+    let mut machine = Builder::new(Top).build();
+    assert_eq!(machine.state(), State::LedOff);
+
+    machine.handle_event(&Event::Toggle);
+    assert_eq!(machine.state(), State::LedOn);
+
+    machine.update();
+    assert_eq!(machine.state(), State::LedOff);
+
+    machine.handle_event(&Event::Fault);
+    assert_eq!(machine.state(), State::Disabled);
+    assert_eq!(
+        machine.handle_event(&Event::Toggle),
+        EventOutcome::Unhandled
+    );
+}
+
+#[test]
+fn narrowed_init() {
+    let mut machine = Builder::new(Top).build();
+    machine.exact_transition(State::Blinking);
+    assert_eq!(machine.state(), State::LedOn);
+}